@@ -22,7 +22,7 @@ impl<'a, C: WriteColor + 'a> DebugDocument<'a, C> {
 
         self.writer.reset()?;
 
-        for item in tree.clone() {
+        for item in tree {
             match item {
                 Node::Text(string) => self.write_text(string)?,
                 Node::OpenSection(section) => self.write_open_section(section)?,
@@ -1,8 +1,9 @@
-use crate::stylesheet::WriteStyle;
+use crate::stream::NodeWriter;
 use crate::Stylesheet;
 use crate::{Combine, Render};
-use std::io;
+use std::{fmt, io, iter, ops};
 use termcolor::{ColorChoice, StandardStream, WriteColor};
+use unicode_width::UnicodeWidthStr;
 
 #[derive(Debug, Clone)]
 pub enum Node {
@@ -12,6 +13,24 @@ pub enum Node {
     Newline,
 }
 
+/// The size of a `Document`'s output, from `Document::measure`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Measurement {
+    /// The number of characters, not counting newlines.
+    pub chars: usize,
+    /// The display width of the widest line.
+    pub max_line_width: usize,
+    /// The number of lines. A trailing newline doesn't start another line.
+    pub lines: usize,
+}
+
+impl Measurement {
+    fn break_line(&mut self, line_width: Option<usize>) {
+        self.max_line_width = self.max_line_width.max(line_width.unwrap_or(0));
+        self.lines += 1;
+    }
+}
+
 /// The `Document` is the root node in a render tree.
 ///
 /// The [`tree!`] macro produces a `Document`, and you can also build
@@ -79,6 +98,77 @@ impl Document {
         renderable.render(Document::empty())
     }
 
+    /// Whether the document has nothing to write. Sections and empty text
+    /// don't count as content, but whitespace and newlines do.
+    ///
+    /// ```
+    /// use render_tree::prelude::*;
+    ///
+    /// assert!(Document::empty().is_empty());
+    /// assert!(Document::empty().add(Section("code", |doc| doc.add(""))).is_empty());
+    /// assert!(!Document::empty().add(" ").is_empty());
+    /// ```
+    pub fn is_empty(&self) -> bool {
+        self.tree().unwrap_or(&[]).iter().all(|node| match node {
+            Node::Text(text) => text.is_empty(),
+            Node::OpenSection(_) | Node::CloseSection => true,
+            Node::Newline => false,
+        })
+    }
+
+    /// The number of nodes in the document, counting each text run, newline,
+    /// and the start and end of each section.
+    pub fn node_count(&self) -> usize {
+        self.tree().map_or(0, |tree| tree.len())
+    }
+
+    /// Measure what the document would write, without writing it. Sections
+    /// are ignored, and widths are display widths, so wide characters count
+    /// as two columns.
+    ///
+    /// ```
+    /// use render_tree::prelude::*;
+    ///
+    /// let measurement = Document::empty()
+    ///     .add(Line("hello"))
+    ///     .add(Section("wide", |doc| doc.add("日本")))
+    ///     .measure();
+    ///
+    /// assert_eq!(measurement.chars, 7);
+    /// assert_eq!(measurement.max_line_width, 5);
+    /// assert_eq!(measurement.lines, 2);
+    /// ```
+    pub fn measure(&self) -> Measurement {
+        let mut measurement = Measurement::default();
+        // The width of the current line, if anything has been written to it
+        let mut line: Option<usize> = None;
+
+        for node in self.tree().unwrap_or(&[]) {
+            match node {
+                Node::Text(text) => {
+                    for (i, part) in text.split('\n').enumerate() {
+                        if i > 0 {
+                            measurement.break_line(line.take());
+                        }
+
+                        if !part.is_empty() {
+                            measurement.chars += part.chars().count();
+                            line = Some(line.unwrap_or(0) + part.width());
+                        }
+                    }
+                }
+                Node::Newline => measurement.break_line(line.take()),
+                Node::OpenSection(_) | Node::CloseSection => {}
+            }
+        }
+
+        if line.is_some() {
+            measurement.break_line(line);
+        }
+
+        measurement
+    }
+
     pub(crate) fn tree(&self) -> Option<&[Node]> {
         match &self.tree {
             None => None,
@@ -86,6 +176,10 @@ impl Document {
         }
     }
 
+    pub(crate) fn into_nodes(self) -> Vec<Node> {
+        self.tree.unwrap_or_default()
+    }
+
     fn initialize_tree(&mut self) -> &mut Vec<Node> {
         if self.tree.is_none() {
             self.tree = Some(vec![]);
@@ -106,24 +200,21 @@ impl Document {
         self
     }
 
-    pub(crate) fn extend_nodes(mut self, other: Vec<Node>) -> Document {
-        if other.len() > 0 {
-            let tree = self.initialize_tree();
-
-            for item in other {
-                tree.push(item)
-            }
+    pub(crate) fn extend_nodes(mut self, mut other: Vec<Node>) -> Document {
+        if !other.is_empty() {
+            // `append` reserves room for all of the nodes at once and moves
+            // them in a single copy
+            self.initialize_tree().append(&mut other);
         }
 
         self
     }
 
     pub(crate) fn extend(self, fragment: Document) -> Document {
-        match (&self.tree, &fragment.tree) {
-            (Some(_), Some(_)) => self.extend_nodes(fragment.tree.unwrap()),
-            (Some(_), None) => self,
-            (None, Some(_)) => fragment,
-            (None, None) => self,
+        match (self.tree, fragment.tree) {
+            (Some(tree), Some(other)) => Document { tree: Some(tree) }.extend_nodes(other),
+            (tree, None) => Document { tree },
+            (None, other) => Document { tree: other },
         }
     }
 
@@ -142,55 +233,518 @@ impl Document {
         Ok(String::from_utf8_lossy(writer.as_slice()).into())
     }
 
+    /// The document's text, without any styling. This is the same as
+    /// `to_string`, but doesn't look up the style of each text node, so it's
+    /// cheaper for output that is never colored, like logs.
+    ///
+    /// ```
+    /// use render_tree::prelude::*;
+    ///
+    /// let document = Document::empty()
+    ///     .add(Line(Section("code", |doc| doc.add("(+ test \"\")"))))
+    ///     .add("after");
+    ///
+    /// assert_eq!(document.to_plain_string(), "(+ test \"\")\nafter");
+    /// ```
+    pub fn to_plain_string(&self) -> String {
+        self.display().to_string()
+    }
+
+    /// Display the document's text without any styling, for `format!` and
+    /// other `Display` types, like `Path::display`.
+    ///
+    /// ```
+    /// use render_tree::prelude::*;
+    ///
+    /// let document = Document::empty().add(Section("code", |doc| doc.add("(+ test \"\")")));
+    ///
+    /// assert_eq!(format!("code: {}", document.display()), "code: (+ test \"\")");
+    /// ```
+    pub fn display(&self) -> DisplayDocument<'_> {
+        DisplayDocument(self)
+    }
+
     pub fn write_styled(self, stylesheet: &Stylesheet) -> io::Result<()> {
         let mut writer = StandardStream::stdout(ColorChoice::Always);
 
         self.write_with(&mut writer, stylesheet)
     }
 
+    /// Write the document to `writer`, styling each section with the
+    /// `termcolor::ColorSpec` of its style in `stylesheet`.
+    ///
+    /// `ColorSpec` can't express `strikethrough` or `underline-color`, so they
+    /// are ignored here. Use
+    /// `write_ansi_with` for writers that are known to write ANSI escapes.
     pub fn write_with(
         self,
         writer: &mut impl WriteColor,
         stylesheet: &Stylesheet,
     ) -> io::Result<()> {
-        let mut nesting = vec![];
+        self.write_nodes(writer, stylesheet, false)
+    }
+
+    /// Like `write_with`, but for writers that write ANSI escape sequences,
+    /// like `termcolor::Buffer::ansi()`. Attributes that `ColorSpec` can't
+    /// express, like `strikethrough` and `underline-color`, are written as raw
+    /// SGR sequences.
+    pub fn write_ansi_with(
+        self,
+        writer: &mut impl WriteColor,
+        stylesheet: &Stylesheet,
+    ) -> io::Result<()> {
+        self.write_nodes(writer, stylesheet, true)
+    }
 
-        writer.reset()?;
+    /// Write the document as HTML, inside a `<pre>`. Each section is a
+    /// `<span>` whose class is the section's name, with its style in
+    /// `stylesheet` as inline CSS. Since the spans nest, a section without a
+    /// style of its own inherits its parent's CSS.
+    ///
+    /// ```
+    /// use render_tree::prelude::*;
+    /// use render_tree::Stylesheet;
+    ///
+    /// let document = Document::empty().add(Section("code", |doc| doc.add("a < b")));
+    /// let stylesheet = Stylesheet::new().add("code", "fg: red");
+    ///
+    /// let mut html = vec![];
+    /// document.write_html(&mut html, &stylesheet).unwrap();
+    ///
+    /// assert_eq!(
+    ///     String::from_utf8(html).unwrap(),
+    ///     "<pre><span class=\"code\" style=\"color:red\">a &lt; b</span></pre>"
+    /// );
+    /// ```
+    pub fn write_html(
+        self,
+        writer: &mut impl io::Write,
+        stylesheet: &Stylesheet,
+    ) -> io::Result<()> {
+        let mut nesting = vec![];
 
-        let tree = match self.tree {
-            None => return Ok(()),
-            Some(nodes) => nodes,
-        };
+        write!(writer, "<pre>")?;
 
-        for item in tree {
+        for item in self.into_nodes() {
             match item {
-                Node::Text(string) => {
-                    if string.len() != 0 {
-                        let style = stylesheet.get(&nesting);
+                Node::Text(string) => write!(writer, "{}", escape_html(&string))?,
+                Node::OpenSection(section) => {
+                    nesting.push(section);
 
-                        match style {
-                            None => writer.reset()?,
-                            Some(style) => writer.set_style(&style)?,
-                        }
+                    write!(writer, "<span class=\"{}\"", escape_html(section))?;
 
-                        write!(writer, "{}", string)?;
+                    if let Some(style) = stylesheet.get(&nesting) {
+                        if style.has_value() {
+                            write!(writer, " style=\"{}\"", escape_html(&style.to_css()))?;
+                        }
                     }
+
+                    write!(writer, ">")?;
                 }
-                Node::OpenSection(section) => nesting.push(section),
                 Node::CloseSection => {
                     nesting.pop().expect("unbalanced push/pop");
+                    write!(writer, "</span>")?;
                 }
-                Node::Newline => {
-                    writer.reset()?;
-                    write!(writer, "\n")?;
-                }
+                Node::Newline => writeln!(writer)?,
             }
         }
 
+        write!(writer, "</pre>")
+    }
+
+    fn write_nodes(
+        self,
+        writer: &mut impl WriteColor,
+        stylesheet: &Stylesheet,
+        ansi: bool,
+    ) -> io::Result<()> {
+        let mut nodes = NodeWriter::new(stylesheet, ansi);
+
+        nodes.start(writer)?;
+
+        for node in self.into_nodes() {
+            nodes.write_node(writer, node)?;
+        }
+
         Ok(())
     }
 }
 
+/// `left + right` is the same as `left.add(right)`, so documents and other
+/// renderable values can be concatenated with `+`.
+///
+/// ```
+/// use render_tree::prelude::*;
+///
+/// let document = Document::with("error") + Section("code", |doc| doc.add("[E0001]")) + ": ";
+///
+/// assert_eq!(document.to_plain_string(), "error[E0001]: ");
+/// ```
+impl<R: Render> ops::Add<R> for Document {
+    type Output = Document;
+
+    fn add(self, right: R) -> Document {
+        right.render(self)
+    }
+}
+
+/// Collects renderable values into a document, adding them in order.
+///
+/// ```
+/// use render_tree::prelude::*;
+///
+/// let document: Document = (1..=3).map(|i| Line(i)).collect();
+///
+/// assert_eq!(document.to_plain_string(), "1\n2\n3\n");
+/// ```
+impl<R: Render> iter::FromIterator<R> for Document {
+    fn from_iter<I: IntoIterator<Item = R>>(iter: I) -> Document {
+        iter.into_iter()
+            .fold(Document::empty(), |document, item| document.add(item))
+    }
+}
+
+/// Displays a document's text without any styling, from `Document::display`.
+///
+/// `Document` can't implement `Display` itself, since every `Display` type
+/// renders as plain text through the blanket `Render` impl, and a document
+/// renders its sections.
+#[derive(Debug, Clone, Copy)]
+pub struct DisplayDocument<'a>(&'a Document);
+
+impl<'a> fmt::Display for DisplayDocument<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for node in self.0.tree().unwrap_or(&[]) {
+            match node {
+                Node::Text(text) => f.write_str(text)?,
+                Node::Newline => f.write_str("\n")?,
+                Node::OpenSection(_) | Node::CloseSection => {}
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Escape text for HTML, including quotes so that it can be used in an
+/// attribute value.
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for ch in text.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            other => escaped.push(other),
+        }
+    }
+
+    escaped
+}
+
 pub fn add<Left: Render, Right: Render>(left: Left, right: Right) -> Combine<Left, Right> {
     Combine { left, right }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+    use crate::stylesheet::ColorAccumulator;
+    use crate::Stylesheet;
+    use termcolor::Buffer;
+
+    fn struck() -> Document {
+        tree! {
+            <Section name="old" as { "before" }>
+            " after"
+        }
+    }
+
+    #[test]
+    fn test_strikethrough_ansi() -> ::std::io::Result<()> {
+        let styles = Stylesheet::new().add("old", "strikethrough: true");
+
+        let mut buffer = Buffer::ansi();
+        struck().write_ansi_with(&mut buffer, &styles)?;
+
+        assert_eq!(
+            String::from_utf8_lossy(buffer.as_slice()),
+            "\x1b[0m\x1b[0m\x1b[9mbefore\x1b[0m\x1b[29m after"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_underline_color_ansi() -> ::std::io::Result<()> {
+        let styles = Stylesheet::new().add("old", "underline: true; underline-color: red");
+
+        let mut buffer = Buffer::ansi();
+        struck().write_ansi_with(&mut buffer, &styles)?;
+
+        assert_eq!(
+            String::from_utf8_lossy(buffer.as_slice()),
+            "\x1b[0m\x1b[0m\x1b[4m\x1b[58;5;1mbefore\x1b[0m\x1b[59m after"
+        );
+
+        let mut buffer = Buffer::ansi();
+        struck().write_with(&mut buffer, &styles)?;
+
+        assert_eq!(
+            String::from_utf8_lossy(buffer.as_slice()),
+            "\x1b[0m\x1b[0m\x1b[4mbefore\x1b[0m after"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_strikethrough_without_ansi() -> ::std::io::Result<()> {
+        let styles = Stylesheet::new().add("old", "strikethrough: true; fg: red");
+
+        let mut buffer = Buffer::no_color();
+        struck().write_ansi_with(&mut buffer, &styles)?;
+        assert_eq!(String::from_utf8_lossy(buffer.as_slice()), "before after");
+
+        let mut writer = ColorAccumulator::new();
+        struck().write_with(&mut writer, &styles)?;
+        assert_eq!(writer.to_string(), "{fg:Red}before{/} after");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_html() -> ::std::io::Result<()> {
+        let styles = Stylesheet::new()
+            .add("header", "fg: red; weight: bold")
+            .add("** code", "underline: true")
+            .add("message", "fg: bright-blue");
+
+        let document = tree! {
+            <Line as {
+                <Section name="header" as {
+                    "error" <Section name="code" as { "[E0001]" }>
+                }>
+                ": "
+                <Section name="message" as { "expected `a < b && c > d`" }>
+            }>
+        };
+
+        let mut html = vec![];
+        document.write_html(&mut html, &styles)?;
+
+        assert_eq!(
+            String::from_utf8_lossy(&html),
+            "<pre><span class=\"header\" style=\"color:red;font-weight:bold\">error\
+             <span class=\"code\" style=\"text-decoration-line:underline\">[E0001]</span>\
+             </span>: \
+             <span class=\"message\" style=\"color:#5c5cff\">\
+             expected `a &lt; b &amp;&amp; c &gt; d`</span>\n</pre>"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_repeated_sections() -> ::std::io::Result<()> {
+        let styles = Stylesheet::new()
+            .add("code", "fg: red")
+            .add("** mark", "weight: bold");
+
+        let document = tree! {
+            <Section name="code" as { "a" <Section name="mark" as { "b" }> "c" }>
+            "d"
+            <Section name="code" as { "e" <Section name="mark" as { "f" }> }>
+            <Section name="mark" as { "g" }>
+        };
+
+        let mut writer = ColorAccumulator::new();
+        document.write_with(&mut writer, &styles)?;
+
+        assert_eq!(
+            writer.to_string(),
+            "{fg:Red}a{bold bright}b{fg:Red}c{/}d{fg:Red}e{bold bright}fg"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_concat() -> ::std::io::Result<()> {
+        let lines = vec![Line("first"), Line("second")];
+        assert_eq!(Document::with(Concat(lines)).to_string()?, "first\nsecond\n");
+
+        let words: &[&str] = &["a", "b"];
+        let document = tree! { "(" {Concat(words.iter().cloned())} ")" };
+        assert_eq!(document.to_string()?, "(ab)");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_plain_string() -> ::std::io::Result<()> {
+        assert_eq!(Document::empty().to_plain_string(), "");
+
+        let document = tree! {
+            <Line as { <Section name="header" as { "error" }> ": message" }>
+            <Section name="code" as { <Line as { "1 | " "(+ test \"\")" }> }>
+            "after\nsplit"
+        };
+
+        assert_eq!(
+            document.to_plain_string(),
+            "error: message\n1 | (+ test \"\")\nafter\nsplit"
+        );
+        assert_eq!(document.to_plain_string(), document.to_string()?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_display() -> ::std::io::Result<()> {
+        let document = tree! {
+            <Line as { <Section name="header" as { "error" }> ": message" }>
+            <Section name="code" as { "1 | " "(+ test \"\")" }>
+        };
+
+        assert_eq!(
+            format!("{}", document.display()),
+            "error: message\n1 | (+ test \"\")"
+        );
+        assert_eq!(format!("{}", document.display()), document.clone().to_string()?);
+        assert_eq!(format!("[{}]", Document::empty().display()), "[]");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_documents() {
+        let header = tree! { <Section name="header" as { "error" }> };
+        let message = tree! { ": " <Line as { "message" }> };
+
+        let both = header.clone() + message.clone();
+        assert_eq!(both.to_plain_string(), "error: message\n");
+        assert_eq!(both.node_count(), header.node_count() + message.node_count());
+
+        let left_empty = Document::empty() + message.clone();
+        assert_eq!(left_empty.to_plain_string(), ": message\n");
+        assert_eq!(left_empty.node_count(), message.node_count());
+
+        let right_empty = header.clone() + Document::empty();
+        assert_eq!(right_empty.node_count(), header.node_count());
+
+        // Adding empty documents doesn't allocate a tree
+        assert!((Document::empty() + Document::empty()).tree().is_none());
+    }
+
+    #[test]
+    fn test_collect() -> ::std::io::Result<()> {
+        let document: Document = vec!["a", "b", "c"].into_iter().collect();
+        assert_eq!(document.to_string()?, "abc");
+
+        let empty: Document = Vec::<&str>::new().into_iter().collect();
+        assert!(empty.tree().is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_extend_many_fragments() {
+        let mut document = Document::empty();
+
+        for i in 0..10_000 {
+            document = document.extend(tree! { <Section name="n" as { {i % 10} }> });
+        }
+
+        assert_eq!(document.node_count(), 30_000);
+        assert_eq!(document.to_plain_string(), "0123456789".repeat(1_000));
+    }
+
+    #[test]
+    fn test_concat_of_nothing_adds_no_nodes() {
+        let empty: Vec<Document> = vec![];
+        assert!(Document::with(Concat(empty)).tree().is_none());
+
+        let document = Document::with("a").add(Concat(Vec::<&str>::new()));
+        assert_eq!(document.tree().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_is_empty() {
+        let empty = Document::empty();
+        assert!(empty.is_empty());
+        assert_eq!(empty.node_count(), 0);
+
+        let empty_text = Document::empty().add("");
+        assert!(empty_text.is_empty());
+
+        let whitespace = tree! { " " {"\t"} };
+        assert!(!whitespace.is_empty());
+        assert_eq!(whitespace.node_count(), 2);
+
+        let section = tree! { <Section name="code" as {}> };
+        assert!(section.is_empty());
+        assert_eq!(section.node_count(), 2);
+
+        let nested = tree! {
+            <Section name="code" as {
+                <Section name="gutter" as { "" }>
+            }>
+        };
+        assert!(nested.is_empty());
+
+        let newline = tree! { <Line as {}> };
+        assert!(!newline.is_empty());
+        assert_eq!(newline.node_count(), 1);
+    }
+
+    #[test]
+    fn test_measure() {
+        use crate::document::Measurement;
+
+        assert_eq!(Document::empty().measure(), Measurement::default());
+
+        let single = tree! {
+            <Section name="header" as { "error" }>
+            ": " {10}
+        };
+        assert_eq!(
+            single.measure(),
+            Measurement {
+                chars: 9,
+                max_line_width: 9,
+                lines: 1,
+            }
+        );
+
+        let multi = tree! {
+            <Line as { "- test:2:9" }>
+            <Line as { "2 | " <Section name="code" as { "(+ test \"\")" }> }>
+            <Line as {}>
+            "trailing"
+        };
+        assert_eq!(
+            multi.measure(),
+            Measurement {
+                chars: 10 + 15 + 8,
+                max_line_width: 15,
+                lines: 4,
+            }
+        );
+
+        let wide = tree! {
+            <Line as { "日本語" }>
+            "e\u{301}\nabc"
+        };
+        assert_eq!(
+            wide.measure(),
+            Measurement {
+                chars: 3 + 2 + 3,
+                max_line_width: 6,
+                lines: 3,
+            }
+        );
+    }
+}
@@ -1,6 +1,7 @@
 use crate::component::OnceBlock;
 use crate::{BlockComponent, Document, IterBlockComponent, Node, Render};
 use std::fmt;
+use unicode_width::UnicodeWidthStr;
 
 /// Creates a `Render` that, when appended into a [`Document`], repeats
 /// a given string a specified number of times.
@@ -190,7 +191,226 @@ pub fn Section(name: &'static str, block: impl FnOnce(Document) -> Document) ->
 //     document
 // }
 
+/// A block whose lines are all shifted right by `size` spaces. Nested
+/// `Indent`s add up.
 ///
+/// Only lines with text on them are indented, so blank lines don't get
+/// trailing whitespace. The spaces go before any sections that are opened
+/// on the line, so a section's style doesn't apply to them.
+///
+/// # Example
+///
+/// ```
+/// # #[macro_use]
+/// # extern crate render_tree;
+/// # use render_tree::prelude::*;
+/// #
+/// # fn main() -> Result<(), ::std::io::Error> {
+/// let document = tree! {
+///     <Line as { "fn main() {" }>
+///     <Indent size={4} as {
+///         <Line as { "if true {" }>
+///         <Indent size={4} as {
+///             <Line as { "println!();" }>
+///         }>
+///         <Line as { "}" }>
+///     }>
+///     <Line as { "}" }>
+/// };
+///
+/// assert_eq!(
+///     document.to_string()?,
+///     "fn main() {\n    if true {\n        println!();\n    }\n}\n"
+/// );
+/// #
+/// # Ok(())
+/// # }
+/// ```
+pub struct Indent {
+    pub size: usize,
+}
+
+impl BlockComponent for Indent {
+    fn append(self, block: impl FnOnce(Document) -> Document, document: Document) -> Document {
+        let inner = block(Document::empty()).into_nodes();
+        let indent = " ".repeat(self.size);
+
+        let mut nodes = Vec::with_capacity(inner.len());
+        // Where the indentation goes if the current line turns out to have
+        // text on it, or `None` once the line has been indented
+        let mut line_start = Some(0);
+
+        for node in inner {
+            match node {
+                Node::Text(text) => {
+                    for (i, part) in text.split('\n').enumerate() {
+                        if i > 0 {
+                            nodes.push(Node::Text("\n".to_string()));
+                            line_start = Some(nodes.len());
+                        }
+
+                        if part.is_empty() {
+                            continue;
+                        }
+
+                        if let Some(start) = line_start.take() {
+                            nodes.insert(start, Node::Text(indent.clone()));
+                        }
+
+                        nodes.push(Node::Text(part.to_string()));
+                    }
+                }
+                Node::Newline => {
+                    nodes.push(Node::Newline);
+                    line_start = Some(nodes.len());
+                }
+                Node::CloseSection => {
+                    nodes.push(Node::CloseSection);
+
+                    // Sections that were opened on an earlier line are
+                    // closed before the indentation
+                    if line_start == Some(nodes.len() - 1) {
+                        line_start = Some(nodes.len());
+                    }
+                }
+                node => nodes.push(node),
+            }
+        }
+
+        document.extend_nodes(nodes)
+    }
+}
+
+#[allow(non_snake_case)]
+pub fn Indent(size: usize, block: impl FnOnce(Document) -> Document) -> Document {
+    Indent { size }.append(block, Document::empty())
+}
+
+/// A block whose text is re-flowed to lines that are at most `width`
+/// columns wide, by breaking lines at spaces.
+///
+/// Explicit line breaks in the block are kept, and sections are kept intact
+/// around the breaks that are inserted, so styles still apply to the text on
+/// both sides of them. The spaces that a line is broken at are dropped. A
+/// word that is wider than `width` gets a line of its own, and text in
+/// adjacent nodes with no space between them is never broken.
+///
+/// # Example
+///
+/// ```
+/// # #[macro_use]
+/// # extern crate render_tree;
+/// # use render_tree::prelude::*;
+/// #
+/// # fn main() -> Result<(), ::std::io::Error> {
+/// let document = tree! {
+///     <Wrap width={12} as {
+///         "the quick " <Section name="fox" as { "brown fox" }> " jumps"
+///     }>
+/// };
+///
+/// assert_eq!(document.to_string()?, "the quick\nbrown fox\njumps");
+/// #
+/// # Ok(())
+/// # }
+/// ```
+pub struct Wrap {
+    pub width: usize,
+}
+
+impl BlockComponent for Wrap {
+    fn append(self, block: impl FnOnce(Document) -> Document, document: Document) -> Document {
+        let mut nodes = vec![];
+        let mut column = 0;
+        // Spaces that haven't been written yet, because the line may be
+        // broken at them, and where in `nodes` they go
+        let mut spaces = String::new();
+        let mut spaces_at = 0;
+
+        for node in block(Document::empty()).into_nodes() {
+            let text = match node {
+                Node::Text(text) => text,
+                Node::Newline => {
+                    nodes.push(Node::Newline);
+                    column = 0;
+                    spaces.clear();
+                    continue;
+                }
+                node => {
+                    nodes.push(node);
+                    continue;
+                }
+            };
+
+            for (i, line) in text.split('\n').enumerate() {
+                if i > 0 {
+                    nodes.push(Node::Text("\n".to_string()));
+                    column = 0;
+                    spaces.clear();
+                }
+
+                for (is_space, run) in runs(line) {
+                    if is_space {
+                        if spaces.is_empty() {
+                            spaces_at = nodes.len();
+                        }
+
+                        spaces.push_str(run);
+                        continue;
+                    }
+
+                    let spaces_width = spaces.width();
+                    let run_width = run.width();
+
+                    let overflows = column + spaces_width + run_width > self.width;
+
+                    if !spaces.is_empty() && column > 0 && overflows {
+                        nodes.insert(spaces_at, Node::Newline);
+                        column = 0;
+                    } else if !spaces.is_empty() {
+                        nodes.insert(spaces_at, Node::Text(spaces.clone()));
+                        column += spaces_width;
+                    }
+
+                    spaces.clear();
+                    nodes.push(Node::Text(run.to_string()));
+                    column += run_width;
+                }
+            }
+        }
+
+        // Spaces at the end of the block aren't followed by a word that could
+        // wrap, so they're kept
+        if !spaces.is_empty() {
+            nodes.insert(spaces_at, Node::Text(spaces));
+        }
+
+        document.extend_nodes(nodes)
+    }
+}
+
+/// Split `text` into runs of spaces and runs of everything else.
+fn runs(text: &str) -> impl Iterator<Item = (bool, &str)> {
+    let mut rest = text;
+
+    ::std::iter::from_fn(move || {
+        let first = rest.chars().next()?;
+        let is_space = first == ' ';
+        let end = rest
+            .find(|ch: char| (ch == ' ') != is_space)
+            .unwrap_or(rest.len());
+
+        let (run, remaining) = rest.split_at(end);
+        rest = remaining;
+
+        Some((is_space, run))
+    })
+}
+
+#[allow(non_snake_case)]
+pub fn Wrap(width: usize, block: impl FnOnce(Document) -> Document) -> Document {
+    Wrap { width }.append(block, Document::empty())
+}
 
 /// Equivalent to [`Each()`], but inserts a joiner between two adjacent elements.
 ///
@@ -264,6 +484,134 @@ where
     }
 }
 
+/// Equivalent to [`Each()`], but passes each item's 0-based index to the
+/// block along with the item.
+///
+/// # Example
+///
+/// ```
+/// # #[macro_use]
+/// # extern crate render_tree;
+/// # use render_tree::prelude::*;
+/// #
+/// # fn main() -> Result<(), ::std::io::Error> {
+/// let items = vec!["a", "b", "c"];
+///
+/// let document = tree! {
+///     <EachIndexed items={items} as |i, item| {
+///         {if i == 0 { "" } else { " " }} {i} ":" {item}
+///     }>
+/// };
+///
+/// assert_eq!(document.to_string()?, "0:a 1:b 2:c");
+/// #
+/// # Ok(())
+/// # }
+/// ```
+pub struct EachIndexed<U, Iterator: IntoIterator<Item = U>> {
+    pub items: Iterator,
+}
+
+impl<U, Iterator: IntoIterator<Item = U>> IterBlockComponent for EachIndexed<U, Iterator> {
+    type Item = (usize, U);
+
+    fn append(
+        self,
+        mut block: impl FnMut((usize, U), Document) -> Document,
+        mut document: Document,
+    ) -> Document {
+        for item in self.items.into_iter().enumerate() {
+            document = block(item, document);
+        }
+
+        document
+    }
+}
+
+impl<U, I: IntoIterator<Item = U>> From<I> for EachIndexed<U, I> {
+    fn from(from: I) -> EachIndexed<U, I> {
+        EachIndexed { items: from }
+    }
+}
+
+#[allow(non_snake_case)]
+pub fn EachIndexed<U, I: IntoIterator<Item = U>>(
+    items: impl Into<EachIndexed<U, I>>,
+    callback: impl Fn((usize, U), Document) -> Document,
+) -> impl Render {
+    IterBlockComponent::with(items.into(), callback)
+}
+
+/// Equivalent to [`Each()`], but prefixes each item with its 1-based index,
+/// followed by `. `. The indexes are right-aligned to the width of the
+/// largest one.
+///
+/// # Example
+///
+/// ```
+/// # #[macro_use]
+/// # extern crate render_tree;
+/// # use render_tree::prelude::*;
+/// #
+/// # fn main() -> Result<(), ::std::io::Error> {
+/// let items = vec!["first", "second"];
+///
+/// let document = tree! {
+///     <Numbered items={items} as |item| {
+///         <Line as { {item} }>
+///     }>
+/// };
+///
+/// assert_eq!(document.to_string()?, "1. first\n2. second\n");
+/// #
+/// # Ok(())
+/// # }
+/// ```
+pub struct Numbered<U, Iterator: IntoIterator<Item = U>> {
+    pub items: Iterator,
+}
+
+impl<U, Iterator: IntoIterator<Item = U>> IterBlockComponent for Numbered<U, Iterator> {
+    type Item = U;
+
+    fn append(
+        self,
+        mut block: impl FnMut(U, Document) -> Document,
+        mut document: Document,
+    ) -> Document {
+        // The width of the largest index isn't known until all of the items
+        // have been seen
+        let items: Vec<U> = self.items.into_iter().collect();
+        let width = items.len().to_string().len();
+
+        for (i, item) in items.into_iter().enumerate() {
+            let number = (i + 1).to_string();
+
+            document = document
+                .add(repeat(" ", width - number.len()))
+                .add(number)
+                .add(". ");
+            document = block(item, document);
+        }
+
+        document
+    }
+}
+
+impl<U, I: IntoIterator<Item = U>> From<I> for Numbered<U, I> {
+    fn from(from: I) -> Numbered<U, I> {
+        Numbered { items: from }
+    }
+}
+
+#[allow(non_snake_case)]
+pub fn Numbered<U, I: IntoIterator<Item = U>>(
+    items: impl Into<Numbered<U, I>>,
+    callback: impl Fn(U, Document) -> Document,
+) -> impl Render {
+    IterBlockComponent::with(items.into(), callback)
+}
+
 /// Inserts a line into a [`Document`]. The contents are inserted first, followed
 /// by a newline.
 #[allow(non_snake_case)]
@@ -297,6 +645,116 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_each_indexed() -> ::std::io::Result<()> {
+        let items = vec!["a", "b", "c"];
+
+        let document = tree! {
+            <EachIndexed items={items} as |i, item| {
+                {if i == 0 { "" } else { " " }} {i} ":" {item}
+            }>
+        };
+
+        assert_eq!(document.to_string()?, "0:a 1:b 2:c");
+
+        let document = EachIndexed(vec!["x", "y"], |(i, item), doc| doc.add(i).add(item));
+        assert_eq!(Document::with(document).to_string()?, "0x1y");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_indent() -> ::std::io::Result<()> {
+        let document = tree! {
+            <Indent size={2} as {
+                <Line as { "one" }>
+                <Line as { "" }>
+                "two\nthree\n"
+                <Indent size={1} as {
+                    <Line as { "four" }>
+                }>
+            }>
+        };
+
+        assert_eq!(document.to_string()?, "  one\n\n  two\n  three\n   four\n");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_indent_function() -> ::std::io::Result<()> {
+        let document = Indent(4, |doc| doc.add(Line("first")).add(Line("second")));
+
+        assert_eq!(document.to_string()?, "    first\n    second\n");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_indent_goes_outside_sections() -> ::std::io::Result<()> {
+        use crate::stylesheet::ColorAccumulator;
+        use crate::Stylesheet;
+
+        let document = tree! {
+            <Indent size={2} as {
+                <Section name="code" as {
+                    <Line as { "one" }>
+                }>
+                <Line as {
+                    <Section name="code" as { "two" }>
+                }>
+            }>
+        };
+
+        let mut writer = ColorAccumulator::new();
+        let stylesheet = Stylesheet::new().add("code", "fg: red");
+        document.write_with(&mut writer, &stylesheet)?;
+
+        assert_eq!(writer.to_string(), "  {fg:Red}one{/}\n  {fg:Red}two{/}\n");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_wrap() -> ::std::io::Result<()> {
+        let document = tree! {
+            <Wrap width={10} as {
+                <Line as { "a supercalifragilistic word" }>
+                "one two\nthree four five"
+            }>
+        };
+
+        assert_eq!(
+            document.to_string()?,
+            "a\nsupercalifragilistic\nword\none two\nthree four\nfive"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_wrap_keeps_sections() -> ::std::io::Result<()> {
+        use crate::stylesheet::ColorAccumulator;
+        use crate::Stylesheet;
+
+        let document = tree! {
+            <Wrap width={8} as {
+                "see " <Section name="code" as { "the code here" }> " now"
+            }>
+        };
+
+        let mut writer = ColorAccumulator::new();
+        let stylesheet = Stylesheet::new().add("code", "fg: red");
+        document.write_with(&mut writer, &stylesheet)?;
+
+        assert_eq!(
+            writer.to_string(),
+            "see {fg:Red}the{/}\n{fg:Red}code{/}\n{fg:Red}here{/} now"
+        );
+
+        Ok(())
+    }
+
     #[test]
     fn test_join() -> ::std::io::Result<()> {
         struct Point(i32, i32);
@@ -316,4 +774,23 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_numbered() -> ::std::io::Result<()> {
+        let items = (1..=12).map(|n| n * 10);
+
+        let document = tree! {
+            <Numbered items={items} as |item| {
+                <Line as { "item " {item} }>
+            }>
+        };
+
+        assert_eq!(
+            document.to_string()?,
+            " 1. item 10\n 2. item 20\n 3. item 30\n 4. item 40\n 5. item 50\n 6. item 60\n \
+             7. item 70\n 8. item 80\n 9. item 90\n10. item 100\n11. item 110\n12. item 120\n"
+        );
+
+        Ok(())
+    }
 }
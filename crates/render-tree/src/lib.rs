@@ -210,6 +210,7 @@ pub mod document;
 mod helpers;
 pub mod prelude;
 mod render;
+mod stream;
 pub mod stylesheet;
 pub(crate) mod utils;
 
@@ -217,4 +218,8 @@ pub use self::component::*;
 pub use self::document::*;
 pub use self::helpers::*;
 pub use self::render::*;
-pub use self::stylesheet::{Color, Segment, Selector, Style, Stylesheet};
+pub use self::stream::RenderStream;
+pub use self::stylesheet::{
+    Color, ColorAccumulator, MatchExplanation, Segment, Selector, Style, Stylesheet,
+    StylesheetParseError,
+};
@@ -32,6 +32,10 @@
 /// - Other [`Document`]s, which are concatenated onto the document.
 /// - A [`SomeValue`] adapter that takes an `Option<impl Renderable>` and inserts its inner
 ///   value if present.
+/// - A [`Maybe`] adapter that does the same for an owned `Option<impl Render>`, without
+///   requiring the inner value to be `Clone`.
+/// - A [`Concat`] adapter that takes a collection of `impl Render` items and inserts
+///   each of them in order.
 /// - An [`Empty`] value that adds nothing to the document.
 ///
 /// # Inline Components
@@ -166,8 +170,58 @@
 /// # Ok(())
 /// # }
 /// ```
+///
+/// # Loops using `@for`
+///
+/// Simple loops can use `@for` instead of an [`Each`] component. The body is
+/// a tree that's added once for each item, and an empty iterator adds
+/// nothing.
+///
+/// ```
+/// # #[macro_use]
+/// # extern crate render_tree;
+/// use render_tree::prelude::*;
+///
+/// # fn main() -> ::std::io::Result<()> {
+/// let points = vec![(10, 20), (5, 10)];
+///
+/// let document = tree! {
+///     "points:"
+///     @for (x, y) in {points} {
+///         " (" {x} "," {y} ")"
+///     }
+/// };
+///
+/// assert_eq!(document.to_string()?, "points: (10,20) (5,10)");
+/// # Ok(())
+/// # }
+/// ```
 #[macro_export]
 macro_rules! tree {
+    // `@for pattern in {items} { ... }` is a loop, which is the same as an
+    // `Each` component, but with the loop variable in front.
+    {
+        trace = [ $($trace:tt)* ]
+        rest = [[ @ for $item:pat in $items:tt { $($block:tt)* } $($rest:tt)* ]]
+    } => {{
+        let component = $crate::IterBlockComponent::with(
+            $crate::Each { items: $items },
+            |$item, doc: $crate::Document| -> $crate::Document {
+                $crate::Render::render(tree! {
+                    trace = [ $($trace)* { for body } ]
+                    rest = [[ $($block)* ]]
+                }, doc)
+            }
+        );
+
+        let rest = tree! {
+            trace = [ $($trace)* { rest tree } ]
+            rest = [[ $($rest)* ]]
+        };
+
+        concat_trees!(component, rest)
+    }};
+
     // We're effectively handling patterns of matched delimiters that aren't intrinsically
     // supported by Rust here.
     //
@@ -567,6 +621,23 @@ macro_rules! block_component {
         concat_trees!(component, rest)
     }};
 
+    // If there were arguments and two closure parameters, the component's
+    // items are pairs, like `EachIndexed`'s `(index, item)`, so destructure
+    // them into the two parameters.
+    {
+        trace = [ $($trace:tt)* ]
+        name = $name:tt
+        args = [ $({ $key:ident = $value:tt })* ]
+        rest = [[ |$id:tt, $id2:tt| { $($block:tt)* }> $($rest:tt)* ]]
+    } => {{
+        block_component! {
+            trace = [ $($trace)* ]
+            name = $name
+            args = [ $({ $key = $value })* ]
+            rest = [[ |($id, $id2)| { $($block)* }> $($rest)* ]]
+        }
+    }};
+
     // Otherwise, if there were arguments and closure parameters, construct
     // the argument object with the component's name and supplied arguments.
     // Then, call the component function with the constructed object and a
@@ -659,4 +730,28 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn for_loops() -> ::std::io::Result<()> {
+        use crate::prelude::*;
+
+        let rows = vec![vec!["a", "b"], vec![], vec!["c"]];
+
+        let document = tree! {
+            @for row in {rows} {
+                <Line as {
+                    "[" @for cell in {row} { {cell} } "]"
+                }>
+            }
+            "done"
+        };
+
+        assert_eq!(document.to_string()?, "[ab]\n[]\n[c]\ndone");
+
+        let empty: Vec<usize> = vec![];
+        let document = tree! { @for i in {empty} { {i} } };
+        assert!(document.is_empty());
+
+        Ok(())
+    }
 }
@@ -2,4 +2,5 @@ pub use crate::component::*;
 pub use crate::document::*;
 pub use crate::helpers::*;
 pub use crate::macros::*;
-pub use crate::render::{Combine, Empty, IfSome, Render, SomeValue};
+pub use crate::render::{Combine, Concat, Empty, IfSome, Maybe, Render, SomeValue};
+pub use crate::stream::RenderStream;
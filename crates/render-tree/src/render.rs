@@ -1,10 +1,11 @@
 use super::{Document, Node};
 
 /// The Render trait defines a type that can be added to a Document.
-/// It is defined for `Node`, `String`, `&str`, and `Document`.alloc
+/// It is defined for `Node`, `String`, `&str`, and `Document`.
 ///
-/// It is also defined for `Option<T>` where `T` is `Render`, as well
-/// as `&T` where `T` is both `Render` and `Clone`.
+/// An `Option<T>` where `T` is `Render` can be rendered by wrapping it in
+/// [`Maybe`], which renders nothing for `None`, and a collection of `Render`
+/// items can be rendered by wrapping it in [`Concat`].
 ///
 /// Generally speaking, if you need to make a type `Render`, and it's
 /// not one of your types, you can ergonomically make a newtype wrapper
@@ -102,19 +103,51 @@ impl Render for Document {
     }
 }
 
-// /// An Option<impl Render> is rendered by doing nothing if None or
-// /// rendering the inner value if Some.
-// impl<T> Render for Option<T>
-// where
-//     T: Render,
-// {
-//     fn render(self, document: Document) -> Document {
-//         match self {
-//             None => document,
-//             Some(item) => item.render(document),
-//         }
-//     }
-// }
+/// An `Option<impl Render>` adapter that renders the inner value if it's
+/// `Some`, and nothing if it's `None`.
+///
+/// `Option<T>` can't implement `Render` itself, because it would overlap with
+/// the blanket implementation for every `T: Display`: the standard library is
+/// allowed to implement `Display` for `Option` in the future, so the compiler
+/// rejects both implementations. `Maybe` is a local type, so it doesn't
+/// overlap, and it takes the option by value so the inner value doesn't need
+/// to be `Clone` (unlike [`SomeValue`]).
+///
+/// ```
+/// # #[macro_use]
+/// # extern crate render_tree;
+/// use render_tree::prelude::*;
+///
+/// # fn main() -> ::std::io::Result<()> {
+/// let code: Option<usize> = Some(42);
+/// let note: Option<&str> = None;
+///
+/// let document = tree! {
+///     "error" {Maybe(code)} ":" {Maybe(note)} " unexpected string"
+/// };
+///
+/// assert_eq!(document.to_string()?, "error42: unexpected string");
+/// #
+/// # Ok(())
+/// # }
+/// ```
+#[allow(non_snake_case)]
+pub fn Maybe<R: Render>(option: Option<R>) -> impl Render {
+    Maybe { option }
+}
+
+struct Maybe<R: Render> {
+    option: Option<R>,
+}
+
+impl<R: Render> Render for Maybe<R> {
+    fn render(self, into: Document) -> Document {
+        match self.option {
+            None => into,
+            Some(item) => item.render(into),
+        }
+    }
+}
 
 struct IfSome<'item, T: 'item, R: Render, F: Fn(&T) -> R + 'item> {
     option: &'item Option<T>,
@@ -166,6 +199,60 @@ pub fn SomeValue<'item, R: Render + Clone>(option: &'item Option<R>) -> impl Ren
     SomeValue { option }
 }
 
+/// An adapter that renders every item of a collection in order, with nothing
+/// between them, like chaining them together with [`Combine`]. An empty
+/// collection adds nothing to the document.
+///
+/// Like `Option`, `Vec<T>` and `&[T]` can't implement `Render` themselves
+/// because of the blanket implementation for `T: Display` (see [`Maybe`]).
+/// `Concat` takes anything that can be iterated over, so a slice of `Clone`
+/// items can be rendered with `Concat(slice.iter().cloned())`.
+///
+/// ```
+/// # #[macro_use]
+/// # extern crate render_tree;
+/// use render_tree::prelude::*;
+///
+/// # fn main() -> ::std::io::Result<()> {
+/// let lines = vec![Line("first"), Line("second")];
+/// let words: &[&str] = &["a", "b", "c"];
+///
+/// let document = Document::empty()
+///     .add(Concat(lines))
+///     .add(Concat(words.iter().cloned()));
+///
+/// assert_eq!(document.to_string()?, "first\nsecond\nabc");
+/// #
+/// # Ok(())
+/// # }
+/// ```
+#[allow(non_snake_case)]
+pub fn Concat<I>(items: I) -> impl Render
+where
+    I: IntoIterator,
+    I::Item: Render,
+{
+    Concat { items }
+}
+
+struct Concat<I: IntoIterator> {
+    items: I,
+}
+
+impl<I> Render for Concat<I>
+where
+    I: IntoIterator,
+    I::Item: Render,
+{
+    fn render(self, mut into: Document) -> Document {
+        for item in self.items {
+            into = item.render(into);
+        }
+
+        into
+    }
+}
+
 pub struct Empty;
 
 impl Render for Empty {
@@ -0,0 +1,234 @@
+use crate::stylesheet::WriteStyle;
+use crate::{Document, Node, Render, Style, Stylesheet};
+use std::collections::HashMap;
+use std::io;
+use termcolor::WriteColor;
+
+/// Writes renderable items to a `WriteColor` as they're rendered, instead
+/// of rendering all of them into one `Document` first, so that a long
+/// stream of output, like thousands of diagnostics, only holds one item's
+/// nodes in memory at a time.
+///
+/// The output is the same, byte for byte, as writing a document with all of
+/// the items added to it with `Document::write_with` (or
+/// `Document::write_ansi_with`, for a stream created with `ansi`), except
+/// that nothing at all is written if no items are.
+///
+/// ```
+/// use render_tree::prelude::*;
+/// use render_tree::Stylesheet;
+///
+/// let stylesheet = Stylesheet::new();
+/// let mut stream = RenderStream::new(termcolor::Buffer::no_color(), &stylesheet);
+///
+/// for i in 0..3 {
+///     stream.write(Line(Section("item", |doc| doc.add(i)))).unwrap();
+/// }
+///
+/// assert_eq!(stream.into_inner().as_slice(), b"0\n1\n2\n");
+/// ```
+pub struct RenderStream<'a, W: WriteColor> {
+    writer: W,
+    nodes: NodeWriter<'a>,
+    started: bool,
+}
+
+impl<'a, W: WriteColor> RenderStream<'a, W> {
+    pub fn new(writer: W, stylesheet: &'a Stylesheet) -> RenderStream<'a, W> {
+        RenderStream {
+            writer,
+            nodes: NodeWriter::new(stylesheet, false),
+            started: false,
+        }
+    }
+
+    /// Like `new`, for writers that write ANSI escape sequences, like
+    /// `Document::write_ansi_with`.
+    pub fn ansi(writer: W, stylesheet: &'a Stylesheet) -> RenderStream<'a, W> {
+        RenderStream {
+            writer,
+            nodes: NodeWriter::new(stylesheet, true),
+            started: false,
+        }
+    }
+
+    /// Render `renderable` and write it.
+    pub fn write(&mut self, renderable: impl Render) -> io::Result<()> {
+        if !self.started {
+            self.nodes.start(&mut self.writer)?;
+            self.started = true;
+        }
+
+        for node in Document::with(renderable).into_nodes() {
+            self.nodes.write_node(&mut self.writer, node)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// The state of writing a sequence of nodes: the sections they're nested
+/// in, and the styles written for the last text.
+pub(crate) struct NodeWriter<'a> {
+    stylesheet: &'a Stylesheet,
+    /// Whether the writer is known to write ANSI escapes
+    ansi: bool,
+    nesting: Vec<&'static str>,
+    // Looking up a style walks the stylesheet, so each section's style is
+    // only looked up once
+    styles: HashMap<Vec<&'static str>, Option<Style>>,
+    struck: bool,
+    underline_colored: bool,
+}
+
+impl<'a> NodeWriter<'a> {
+    pub(crate) fn new(stylesheet: &'a Stylesheet, ansi: bool) -> NodeWriter<'a> {
+        NodeWriter {
+            stylesheet,
+            ansi,
+            nesting: vec![],
+            styles: HashMap::new(),
+            struck: false,
+            underline_colored: false,
+        }
+    }
+
+    /// Reset the writer before the first node.
+    pub(crate) fn start(&mut self, writer: &mut impl WriteColor) -> io::Result<()> {
+        self.ansi = self.ansi && writer.supports_color();
+        writer.reset()
+    }
+
+    pub(crate) fn write_node(
+        &mut self,
+        writer: &mut impl WriteColor,
+        node: Node,
+    ) -> io::Result<()> {
+        match node {
+            Node::Text(string) => {
+                if !string.is_empty() {
+                    self.write_text(writer, &string)?;
+                }
+            }
+            Node::OpenSection(section) => self.nesting.push(section),
+            Node::CloseSection => {
+                self.nesting.pop().expect("unbalanced push/pop");
+            }
+            Node::Newline => {
+                writer.reset()?;
+                self.struck = false;
+                self.underline_colored = false;
+                writeln!(writer)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write_text(&mut self, writer: &mut impl WriteColor, string: &str) -> io::Result<()> {
+        let style = match self.styles.get(&self.nesting) {
+            Some(style) => style.clone(),
+            None => {
+                let style = self.stylesheet.get(&self.nesting);
+                self.styles.insert(self.nesting.clone(), style.clone());
+                style
+            }
+        };
+        let ansi = self.ansi;
+        let strikethrough = ansi && style.as_ref().is_some_and(|style| style.is_strikethrough());
+        let underline_color = match &style {
+            Some(style) if ansi => style.get_underline_color(),
+            _ => None,
+        };
+
+        match style {
+            None => writer.reset()?,
+            Some(style) => writer.set_style(&style)?,
+        }
+
+        if strikethrough {
+            write!(writer, "\x1b[9m")?;
+        } else if self.struck {
+            write!(writer, "\x1b[29m")?;
+        }
+
+        self.struck = strikethrough;
+
+        if let Some(color) = underline_color {
+            write!(writer, "\x1b[{}m", color.underline_sgr())?;
+        } else if self.underline_colored {
+            write!(writer, "\x1b[59m")?;
+        }
+
+        self.underline_colored = underline_color.is_some();
+
+        write!(writer, "{}", string)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+    use crate::Stylesheet;
+    use termcolor::Buffer;
+
+    fn items() -> Vec<Document> {
+        (0..3)
+            .map(|i| {
+                tree! {
+                    <Section name="old" as { "before " {i} }>
+                    " "
+                    <Section name="link" as { "after" }>
+                    {if i == 1 { Document::with(Line("")) } else { Document::empty() }}
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_stream_matches_document() -> ::std::io::Result<()> {
+        let styles = Stylesheet::new()
+            .add("old", "strikethrough: true; fg: red")
+            .add("link", "underline: true; underline-color: blue");
+
+        let mut document = Buffer::ansi();
+        Document::with(Concat(items())).write_ansi_with(&mut document, &styles)?;
+        assert!(String::from_utf8_lossy(document.as_slice()).contains("\x1b[9mbefore "));
+
+        let mut stream = RenderStream::ansi(Buffer::ansi(), &styles);
+
+        for item in items() {
+            stream.write(item)?;
+        }
+
+        assert_eq!(
+            String::from_utf8_lossy(stream.into_inner().as_slice()),
+            String::from_utf8_lossy(document.as_slice())
+        );
+
+        let mut document = Buffer::ansi();
+        Document::with(Concat(items())).write_with(&mut document, &styles)?;
+
+        let mut stream = RenderStream::new(Buffer::ansi(), &styles);
+
+        for item in items() {
+            stream.write(item)?;
+        }
+
+        assert_eq!(stream.into_inner().as_slice(), document.as_slice());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_empty_stream() {
+        let styles = Stylesheet::new();
+        let stream = RenderStream::new(Buffer::ansi(), &styles);
+
+        assert_eq!(stream.into_inner().as_slice(), b"");
+    }
+}
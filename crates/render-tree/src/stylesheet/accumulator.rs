@@ -1,3 +1,4 @@
+use super::{Color, Style};
 use log::*;
 use std::io;
 use std::io::prelude::*;
@@ -5,7 +6,13 @@ use termcolor::ColorSpec;
 use termcolor::WriteColor;
 
 /// A facility for creating visually inspectable representations of colored output
-/// so they can be easily tested.
+/// so they can be easily tested, without a terminal.
+///
+/// The output is available in two forms: `to_string` returns the text with
+/// its colors inlined as markers, and `runs` returns the text as a list of
+/// styled runs, for asserting on styles without parsing the markers.
+///
+/// # The string form
 ///
 /// A new color is represented as `{style}` and a reset is represented by `{/}`.
 ///
@@ -20,7 +27,7 @@ use termcolor::WriteColor;
 /// For example, the style "intense, bold red foreground" would be printed as:
 ///
 /// ```text
-/// {fg:Red bold intense}
+/// {fg:Red bold bright}
 /// ```
 ///
 /// Since this implementation attempts to make it possible to faithfully
@@ -33,9 +40,47 @@ use termcolor::WriteColor;
 ///   color attributes.
 /// - If set_color is called with no style, `{/}` is emitted
 /// - If reset is called, `{/}` is emitted
+///
+/// Setting the current color again emits nothing, and so does resetting
+/// when no color is set, so `reset` and `set_color` with an empty spec are
+/// interchangeable. The markers only appear in the string form, never in
+/// the runs.
+///
+/// # The structured form
+///
+/// Each run is a `Style` and the text written while it was current.
+/// Consecutive writes in the same style are one run, and nothing is
+/// recorded for a color that's set and then replaced before any text is
+/// written. Text written with no color set has an empty style.
+///
+/// ```
+/// # use render_tree::{Color, ColorAccumulator, Style};
+/// # use std::io::Write;
+/// # use termcolor::WriteColor;
+/// # fn main() -> std::io::Result<()> {
+/// let mut writer = ColorAccumulator::new();
+///
+/// writer.set_color(&Style::new().fg(Color::Red).bold().to_color_spec())?;
+/// write!(writer, "Hello")?;
+/// writer.reset()?;
+/// write!(writer, " world")?;
+///
+/// assert_eq!(
+///     writer.runs(),
+///     vec![
+///         (Style::new().fg(Color::Red).bold(), "Hello".to_string()),
+///         (Style::new(), " world".to_string()),
+///     ]
+/// );
+///
+/// assert_eq!(writer.to_string(), "{fg:Red bold bright}Hello{/} world");
+/// # Ok(())
+/// # }
+/// ```
 pub struct ColorAccumulator {
     buf: Vec<u8>,
     color: ColorSpec,
+    runs: Vec<(ColorSpec, String)>,
 }
 
 impl ColorAccumulator {
@@ -43,17 +88,94 @@ impl ColorAccumulator {
         ColorAccumulator {
             buf: Vec::new(),
             color: ColorSpec::new(),
+            runs: vec![],
         }
     }
 
     pub fn to_string(self) -> String {
         String::from_utf8(self.buf).unwrap()
     }
+
+    /// The text written so far, as runs of text in the same style.
+    ///
+    /// A color spec's bold is read as `weight: bold`. `Style::to_color_spec`
+    /// writes both bright colors and `weight: normal` as intense, so intense
+    /// without bold is read as the bright version of the foreground color, or
+    /// of the background color if the foreground isn't one of the eight base
+    /// colors, and as `weight: normal` if neither is. That means that
+    /// `fg: red; weight: normal` comes back as `fg: bright-red`, and that a
+    /// bright color in a bold style comes back as the base color.
+    pub fn runs(&self) -> Vec<(Style, String)> {
+        self.runs
+            .iter()
+            .map(|(spec, text)| (style_for(spec), text.clone()))
+            .collect()
+    }
+}
+
+fn style_for(spec: &ColorSpec) -> Style {
+    let mut style = Style::new();
+
+    let mut fg = spec.fg().map(Color::from);
+    let mut bg = spec.bg().map(Color::from);
+
+    if spec.bold() {
+        style = style.bold();
+    } else if spec.intense() {
+        if let Some(bright) = fg.and_then(bright) {
+            fg = Some(bright);
+        } else if let Some(bright) = bg.and_then(bright) {
+            bg = Some(bright);
+        } else {
+            style = style.normal();
+        }
+    }
+
+    if let Some(fg) = fg {
+        style = style.fg(fg);
+    }
+
+    if let Some(bg) = bg {
+        style = style.bg(bg);
+    }
+
+    if spec.underline() {
+        style = style.underline();
+    }
+
+    style
+}
+
+/// The bright version of one of the eight base colors.
+fn bright(color: Color) -> Option<Color> {
+    match color {
+        Color::Black => Some(Color::BrightBlack),
+        Color::Blue => Some(Color::BrightBlue),
+        Color::Green => Some(Color::BrightGreen),
+        Color::Red => Some(Color::BrightRed),
+        Color::Cyan => Some(Color::BrightCyan),
+        Color::Magenta => Some(Color::BrightMagenta),
+        Color::Yellow => Some(Color::BrightYellow),
+        Color::White => Some(Color::BrightWhite),
+        _ => None,
+    }
 }
 
 impl io::Write for ColorAccumulator {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
         self.buf.extend(buf);
+
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let text = String::from_utf8_lossy(buf);
+
+        match self.runs.last_mut() {
+            Some((spec, run)) if *spec == self.color => run.push_str(&text),
+            _ => self.runs.push((self.color.clone(), text.into_owned())),
+        }
+
         Ok(buf.len())
     }
 
@@ -84,15 +206,15 @@ impl WriteColor for ColorAccumulator {
         }
 
         if spec.is_none() {
-            write!(self, "{{/}}")?;
+            write!(self.buf, "{{/}}")?;
             return Ok(());
         } else {
-            write!(self, "{{")?;
+            write!(self.buf, "{{")?;
         }
 
         let mut first = true;
 
-        fn write_first(first: bool, write: &mut ColorAccumulator) -> io::Result<bool> {
+        fn write_first(first: bool, write: &mut Vec<u8>) -> io::Result<bool> {
             if !first {
                 write!(write, " ")?;
             }
@@ -101,31 +223,31 @@ impl WriteColor for ColorAccumulator {
         };
 
         if let Some(fg) = spec.fg() {
-            first = write_first(first, self)?;
-            write!(self, "fg:{:?}", fg)?;
+            first = write_first(first, &mut self.buf)?;
+            write!(self.buf, "fg:{:?}", fg)?;
         }
 
         if let Some(bg) = spec.bg() {
-            first = write_first(first, self)?;
-            write!(self, "bg:{:?}", bg)?;
+            first = write_first(first, &mut self.buf)?;
+            write!(self.buf, "bg:{:?}", bg)?;
         }
 
         if spec.bold() {
-            first = write_first(first, self)?;
-            write!(self, "bold")?;
+            first = write_first(first, &mut self.buf)?;
+            write!(self.buf, "bold")?;
         }
 
         if spec.underline() {
-            first = write_first(first, self)?;
-            write!(self, "underline")?;
+            first = write_first(first, &mut self.buf)?;
+            write!(self.buf, "underline")?;
         }
 
         if spec.intense() {
-            first = write_first(first, self)?;
-            write!(self, "bright")?;
+            first = write_first(first, &mut self.buf)?;
+            write!(self.buf, "bright")?;
         }
 
-        write!(self, "}}")?;
+        write!(self.buf, "}}")?;
 
         Ok(())
     }
@@ -134,10 +256,99 @@ impl WriteColor for ColorAccumulator {
         let color = self.color.clone();
 
         if color != ColorSpec::new() {
-            write!(self, "{{/}}")?;
+            write!(self.buf, "{{/}}")?;
             self.color = ColorSpec::new();
         }
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use termcolor::Color as TermColor;
+
+    #[test]
+    fn test_reset_and_empty_set_color_are_the_same() -> io::Result<()> {
+        let mut red = ColorSpec::new();
+        red.set_fg(Some(TermColor::Red));
+
+        let mut reset = ColorAccumulator::new();
+        reset.reset()?;
+        reset.set_color(&red)?;
+        write!(reset, "a")?;
+        reset.reset()?;
+        write!(reset, "b")?;
+
+        let mut empty = ColorAccumulator::new();
+        empty.set_color(&ColorSpec::new())?;
+        empty.set_color(&red)?;
+        write!(empty, "a")?;
+        empty.set_color(&ColorSpec::new())?;
+        write!(empty, "b")?;
+
+        assert_eq!(reset.runs(), empty.runs());
+        assert_eq!(reset.to_string(), "{fg:Red}a{/}b");
+        assert_eq!(empty.to_string(), "{fg:Red}a{/}b");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_runs_merge_writes_in_the_same_style() -> io::Result<()> {
+        let mut red = ColorSpec::new();
+        red.set_fg(Some(TermColor::Red)).set_underline(true);
+
+        let mut blue = ColorSpec::new();
+        blue.set_fg(Some(TermColor::Blue));
+
+        let mut writer = ColorAccumulator::new();
+        writer.set_color(&red)?;
+        write!(writer, "a")?;
+        writer.set_color(&red)?;
+        write!(writer, "b")?;
+        writer.set_color(&blue)?;
+        writer.set_color(&red)?;
+        write!(writer, "c")?;
+
+        assert_eq!(
+            writer.runs(),
+            vec![(Style::new().fg(Color::Red).underline(), "abc".to_string())]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_runs_with_bright_colors() -> io::Result<()> {
+        use crate::prelude::*;
+        use crate::Stylesheet;
+
+        let styles = Stylesheet::new()
+            .add("error", "fg: bright-red")
+            .add("link", "fg: bright-blue; bg: black; weight: bold")
+            .add("quiet", "weight: normal");
+
+        let document = Document::with(Section("error", |doc| doc.add("a")))
+            .add(Section("link", |doc| doc.add("b")))
+            .add(Section("quiet", |doc| doc.add("c")));
+
+        let mut writer = ColorAccumulator::new();
+        document.write_with(&mut writer, &styles)?;
+
+        assert_eq!(
+            writer.runs(),
+            vec![
+                (Style::new().fg(Color::BrightRed), "a".to_string()),
+                (
+                    Style::new().fg(Color::Blue).bg(Color::Black).bold(),
+                    "b".to_string()
+                ),
+                (Style::new().normal(), "c".to_string()),
+            ]
+        );
+
+        Ok(())
+    }
+}
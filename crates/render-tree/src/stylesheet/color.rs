@@ -1,3 +1,4 @@
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 use std::fmt;
 use std::str::FromStr;
 use termcolor;
@@ -12,11 +13,25 @@ pub enum Color {
     Magenta,
     Yellow,
     White,
+    BrightBlack,
+    BrightBlue,
+    BrightGreen,
+    BrightRed,
+    BrightCyan,
+    BrightMagenta,
+    BrightYellow,
+    BrightWhite,
+    /// A color from the 256-color ANSI palette, written as `ansi(214)`
+    Ansi256(u8),
+    /// A 24-bit color, written as `#ff8000`
+    Rgb(u8, u8, u8),
 }
 
 impl fmt::Display for Color {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let out = match self {
+            Color::Ansi256(n) => return write!(f, "ansi({})", n),
+            Color::Rgb(r, g, b) => return write!(f, "#{:02x}{:02x}{:02x}", r, g, b),
             Color::Black => "black",
             Color::Blue => "blue",
             Color::Green => "green",
@@ -25,27 +40,141 @@ impl fmt::Display for Color {
             Color::Magenta => "magenta",
             Color::Yellow => "yellow",
             Color::White => "white",
+            Color::BrightBlack => "bright-black",
+            Color::BrightBlue => "bright-blue",
+            Color::BrightGreen => "bright-green",
+            Color::BrightRed => "bright-red",
+            Color::BrightCyan => "bright-cyan",
+            Color::BrightMagenta => "bright-magenta",
+            Color::BrightYellow => "bright-yellow",
+            Color::BrightWhite => "bright-white",
         };
 
         write!(f, "{}", out)
     }
 }
 
+impl Color {
+    /// Whether this is one of the bright colors, which termcolor expresses
+    /// as the base color with `ColorSpec::set_intense`.
+    pub fn is_bright(self) -> bool {
+        matches!(
+            self,
+            Color::BrightBlack
+                | Color::BrightBlue
+                | Color::BrightGreen
+                | Color::BrightRed
+                | Color::BrightCyan
+                | Color::BrightMagenta
+                | Color::BrightYellow
+                | Color::BrightWhite
+        )
+    }
+
+    /// The parameters of the SGR 58 sequence that sets the underline color
+    /// to this color, like `58;5;1` for red.
+    pub fn underline_sgr(self) -> String {
+        let index = match self {
+            Color::Black => 0,
+            Color::Red => 1,
+            Color::Green => 2,
+            Color::Yellow => 3,
+            Color::Blue => 4,
+            Color::Magenta => 5,
+            Color::Cyan => 6,
+            Color::White => 7,
+            Color::BrightBlack => 8,
+            Color::BrightRed => 9,
+            Color::BrightGreen => 10,
+            Color::BrightYellow => 11,
+            Color::BrightBlue => 12,
+            Color::BrightMagenta => 13,
+            Color::BrightCyan => 14,
+            Color::BrightWhite => 15,
+            Color::Ansi256(n) => n,
+            Color::Rgb(r, g, b) => return format!("58;2;{};{};{}", r, g, b),
+        };
+
+        format!("58;5;{}", index)
+    }
+
+    /// The CSS color for this color, like `red` or `#ff8000`. The base colors
+    /// are CSS color keywords, and the others use the xterm palette.
+    pub fn to_css(self) -> String {
+        let (r, g, b) = match self {
+            Color::Black => return "black".to_string(),
+            Color::Blue => return "blue".to_string(),
+            Color::Green => return "green".to_string(),
+            Color::Red => return "red".to_string(),
+            Color::Cyan => return "cyan".to_string(),
+            Color::Magenta => return "magenta".to_string(),
+            Color::Yellow => return "yellow".to_string(),
+            Color::White => return "white".to_string(),
+            Color::BrightBlack => (0x7f, 0x7f, 0x7f),
+            Color::BrightBlue => (0x5c, 0x5c, 0xff),
+            Color::BrightGreen => (0x00, 0xff, 0x00),
+            Color::BrightRed => (0xff, 0x00, 0x00),
+            Color::BrightCyan => (0x00, 0xff, 0xff),
+            Color::BrightMagenta => (0xff, 0x00, 0xff),
+            Color::BrightYellow => (0xff, 0xff, 0x00),
+            Color::BrightWhite => (0xff, 0xff, 0xff),
+            Color::Ansi256(n) if n < 16 => return ANSI_COLORS[n as usize].to_css(),
+            Color::Ansi256(n) if n < 232 => {
+                let level = |i: u8| if i == 0 { 0 } else { 55 + i * 40 };
+                let i = n - 16;
+
+                (level(i / 36), level(i / 6 % 6), level(i % 6))
+            }
+            Color::Ansi256(n) => {
+                let gray = 8 + (n - 232) * 10;
+                (gray, gray, gray)
+            }
+            Color::Rgb(r, g, b) => (r, g, b),
+        };
+
+        format!("#{:02x}{:02x}{:02x}", r, g, b)
+    }
+}
+
+/// The first 16 colors of the 256-color ANSI palette, in palette order.
+const ANSI_COLORS: [Color; 16] = [
+    Color::Black,
+    Color::Red,
+    Color::Green,
+    Color::Yellow,
+    Color::Blue,
+    Color::Magenta,
+    Color::Cyan,
+    Color::White,
+    Color::BrightBlack,
+    Color::BrightRed,
+    Color::BrightGreen,
+    Color::BrightYellow,
+    Color::BrightBlue,
+    Color::BrightMagenta,
+    Color::BrightCyan,
+    Color::BrightWhite,
+];
+
+/// Bright colors convert to their base color, so a `ColorSpec` also needs
+/// `set_intense` to show them. `Style::to_color_spec` does this.
 impl From<Color> for termcolor::Color {
     fn from(color: Color) -> termcolor::Color {
         match color {
-            Color::Black => termcolor::Color::Black,
-            Color::Blue => if cfg!(windows) {
+            Color::Black | Color::BrightBlack => termcolor::Color::Black,
+            Color::Blue | Color::BrightBlue => if cfg!(windows) {
                 termcolor::Color::Cyan
             } else {
                 termcolor::Color::Blue
             },
-            Color::Green => termcolor::Color::Green,
-            Color::Red => termcolor::Color::Red,
-            Color::Cyan => termcolor::Color::Cyan,
-            Color::Magenta => termcolor::Color::Magenta,
-            Color::Yellow => termcolor::Color::Yellow,
-            Color::White => termcolor::Color::White,
+            Color::Green | Color::BrightGreen => termcolor::Color::Green,
+            Color::Red | Color::BrightRed => termcolor::Color::Red,
+            Color::Cyan | Color::BrightCyan => termcolor::Color::Cyan,
+            Color::Magenta | Color::BrightMagenta => termcolor::Color::Magenta,
+            Color::Yellow | Color::BrightYellow => termcolor::Color::Yellow,
+            Color::White | Color::BrightWhite => termcolor::Color::White,
+            Color::Ansi256(n) => termcolor::Color::Ansi256(n),
+            Color::Rgb(r, g, b) => termcolor::Color::Rgb(r, g, b),
         }
     }
 }
@@ -63,11 +192,58 @@ impl FromStr for Color {
             "magenta" => Ok(Color::Magenta),
             "yellow" => Ok(Color::Yellow),
             "white" => Ok(Color::White),
-            _ => Err(("invalid color", s.to_string())),
+            "bright-black" | "brightblack" => Ok(Color::BrightBlack),
+            "bright-blue" | "brightblue" => Ok(Color::BrightBlue),
+            "bright-green" | "brightgreen" => Ok(Color::BrightGreen),
+            "bright-red" | "brightred" => Ok(Color::BrightRed),
+            "bright-cyan" | "brightcyan" => Ok(Color::BrightCyan),
+            "bright-magenta" | "brightmagenta" => Ok(Color::BrightMagenta),
+            "bright-yellow" | "brightyellow" => Ok(Color::BrightYellow),
+            "bright-white" | "brightwhite" => Ok(Color::BrightWhite),
+            other => parse_hex(other)
+                .or_else(|| parse_ansi256(other))
+                .ok_or_else(|| ("invalid color", s.to_string())),
         }
     }
 }
 
+/// A color is serialized as its stylesheet name, like `"red"`, `"ansi(214)"`
+/// or `"#ff8000"`.
+impl Serialize for Color {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Color {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Color, D::Error> {
+        let name = String::deserialize(deserializer)?;
+
+        name.parse()
+            .map_err(|(message, color)| de::Error::custom(format!("{} `{}`", message, color)))
+    }
+}
+
+/// Parse a color like `#ff8000`.
+fn parse_hex(s: &str) -> Option<Color> {
+    let hex = s.strip_prefix('#')?;
+
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+
+    Some(Color::Rgb(channel(0)?, channel(2)?, channel(4)?))
+}
+
+/// Parse a color like `ansi(214)`.
+fn parse_ansi256(s: &str) -> Option<Color> {
+    let n = s.strip_prefix("ansi(")?.strip_suffix(')')?;
+
+    n.trim().parse().ok().map(Color::Ansi256)
+}
+
 impl<'a> From<&'a str> for Color {
     fn from(s: &str) -> Color {
         Color::from_str(s).unwrap()
@@ -85,6 +261,8 @@ impl<'a> From<&'a termcolor::Color> for Color {
             termcolor::Color::Magenta => Color::Magenta,
             termcolor::Color::Yellow => Color::Yellow,
             termcolor::Color::White => Color::White,
+            termcolor::Color::Ansi256(n) => Color::Ansi256(*n),
+            termcolor::Color::Rgb(r, g, b) => Color::Rgb(*r, *g, *b),
 
             other => panic!(
                 "termcolor {:?} is a non-portable color and cannot be converted",
@@ -93,3 +271,89 @@ impl<'a> From<&'a termcolor::Color> for Color {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_hex() {
+        assert_eq!("#ff8000".parse(), Ok(Color::Rgb(255, 128, 0)));
+        assert_eq!("#FF8000".parse(), Ok(Color::Rgb(255, 128, 0)));
+        assert_eq!("#000000".parse(), Ok(Color::Rgb(0, 0, 0)));
+        assert!("#ff800".parse::<Color>().is_err());
+        assert!("#ff80000".parse::<Color>().is_err());
+        assert!("#gg8000".parse::<Color>().is_err());
+        assert!("ff8000".parse::<Color>().is_err());
+    }
+
+    #[test]
+    fn test_parse_bright() {
+        assert_eq!("bright-red".parse(), Ok(Color::BrightRed));
+        assert_eq!("brightred".parse(), Ok(Color::BrightRed));
+        assert_eq!("Bright-Green".parse(), Ok(Color::BrightGreen));
+        assert_eq!(Color::BrightMagenta.to_string(), "bright-magenta");
+        assert_eq!(Color::BrightWhite.to_string().parse(), Ok(Color::BrightWhite));
+        assert!("bright".parse::<Color>().is_err());
+    }
+
+    #[test]
+    fn test_bright_to_termcolor() {
+        assert!(Color::BrightRed.is_bright());
+        assert!(!Color::Red.is_bright());
+        assert_eq!(termcolor::Color::from(Color::BrightRed), termcolor::Color::Red);
+        assert_eq!(
+            termcolor::Color::from(Color::BrightBlue),
+            termcolor::Color::from(Color::Blue)
+        );
+    }
+
+    #[test]
+    fn test_to_css() {
+        assert_eq!(Color::Red.to_css(), "red");
+        assert_eq!(Color::BrightRed.to_css(), "#ff0000");
+        assert_eq!(Color::Ansi256(1).to_css(), "red");
+        assert_eq!(Color::Ansi256(214).to_css(), "#ffaf00");
+        assert_eq!(Color::Ansi256(244).to_css(), "#808080");
+        assert_eq!(Color::Rgb(255, 128, 0).to_css(), "#ff8000");
+    }
+
+    #[test]
+    fn test_parse_ansi256() {
+        assert_eq!("ansi(214)".parse(), Ok(Color::Ansi256(214)));
+        assert!("ansi(256)".parse::<Color>().is_err());
+        assert!("ansi()".parse::<Color>().is_err());
+    }
+
+    #[test]
+    fn test_display_round_trips() {
+        for color in &[Color::Red, Color::Ansi256(214), Color::Rgb(255, 128, 0)] {
+            assert_eq!(color.to_string().parse(), Ok(*color));
+        }
+
+        assert_eq!(Color::Rgb(255, 128, 0).to_string(), "#ff8000");
+        assert_eq!(Color::Ansi256(214).to_string(), "ansi(214)");
+    }
+
+    #[test]
+    fn test_termcolor_round_trips() {
+        let rgb = termcolor::Color::from(Color::Rgb(255, 128, 0));
+        assert_eq!(rgb, termcolor::Color::Rgb(255, 128, 0));
+        assert_eq!(Color::from(&rgb), Color::Rgb(255, 128, 0));
+
+        let ansi = termcolor::Color::from(Color::Ansi256(214));
+        assert_eq!(ansi, termcolor::Color::Ansi256(214));
+        assert_eq!(Color::from(&ansi), Color::Ansi256(214));
+    }
+
+    #[test]
+    fn test_serde_round_trips() {
+        for color in &[Color::BrightRed, Color::Ansi256(214), Color::Rgb(255, 128, 0)] {
+            let json = serde_json::to_string(color).unwrap();
+            assert_eq!(serde_json::from_str::<Color>(&json).unwrap(), *color);
+        }
+
+        assert_eq!(serde_json::to_string(&Color::Red).unwrap(), "\"red\"");
+        assert!(serde_json::from_str::<Color>("\"mauve\"").is_err());
+    }
+}
@@ -1,6 +1,7 @@
 mod accumulator;
 mod color;
 mod format;
+mod recording;
 mod style;
 
 use self::format::{DisplayStyle, NodeDetails};
@@ -8,11 +9,16 @@ use crate::utils::CommaArray;
 use crate::PadItem;
 use itertools::Itertools;
 use log::*;
+use serde::de::{MapAccess, Visitor};
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::collections::HashMap;
+use std::fmt;
 
 pub use self::accumulator::ColorAccumulator;
 pub use self::color::Color;
-pub use self::style::{Style, WriteStyle};
+pub use self::recording::{Event, RecordingWriter};
+pub use self::style::{AttributeName, Style, StyleParseError, WriteStyle};
 
 pub struct Selector {
     segments: Vec<Segment>,
@@ -50,6 +56,43 @@ impl Selector {
         self.segments.push(Segment::Name(segment));
         self
     }
+
+    /// Add a segment that only matches a direct child of the previous
+    /// segment's section, like `header > code`. This is the same as `add`,
+    /// since every segment that doesn't follow a glob requires a direct
+    /// child; it exists to make that explicit.
+    pub fn add_child(self, segment: &'static str) -> Selector {
+        self.add(segment)
+    }
+
+    /// Parse a selector string, or return `None` if it has a `>` that isn't
+    /// between two segments, like `> a`, `a >` or `a > > b`.
+    fn parse(from: &'static str) -> Option<Selector> {
+        let mut segments: Vec<Segment> = vec![];
+        // Whether the last part was a `>`, which must be followed by a segment
+        let mut after_child = false;
+
+        for part in from.split_whitespace() {
+            if part == ">" {
+                if segments.is_empty() || after_child {
+                    return None;
+                }
+
+                after_child = true;
+            } else {
+                segments.push(part.into());
+                after_child = false;
+            }
+        }
+
+        if after_child {
+            return None;
+        }
+
+        segments.dedup_by(|next, prev| *next == Segment::Glob && *prev == Segment::Glob);
+
+        Some(Selector { segments })
+    }
 }
 
 /// This type statically prevents appending a glob right after another glob,
@@ -91,13 +134,24 @@ impl IntoIterator for GlobSelector {
     }
 }
 
+/// Segments in a selector string are separated by whitespace, and each one
+/// matches a section that's a direct child of the previous one's section,
+/// unless it follows a `**`. An explicit `>` between two segments, like
+/// `header > code`, means the same thing.
+///
+/// Since a glob already matches any number of sections, adjacent globs like
+/// the ones in `a ** ** b` are collapsed into one, like `a ** b`.
+///
+/// # Panics
+///
+/// If a `>` isn't between two segments, like in `> a`, `a >` or `a > > b`.
+/// `Stylesheet::parse` reports these as `StylesheetParseError::InvalidSelector`
+/// instead.
 impl From<&'static str> for Selector {
     fn from(from: &'static str) -> Selector {
-        let segments = from.split(' ');
-        let segments = segments.map(|part| part.into());
-
-        Selector {
-            segments: segments.collect(),
+        match Selector::parse(from) {
+            Some(selector) => selector,
+            None => panic!("Invalid selector `{}`: `>` must be between two segments", from),
         }
     }
 }
@@ -181,6 +235,59 @@ impl Node {
         }
     }
 
+    /// Merge another node for the same segment into this one. Its declarations
+    /// are unioned into this node's, taking precedence per attribute, and its
+    /// children are merged recursively.
+    fn merge(&mut self, other: Node) {
+        self.declarations = union(self.declarations.take(), other.declarations);
+
+        for (segment, child) in other.children {
+            match self.children.get_mut(&segment) {
+                Some(existing) => existing.merge(child),
+                None => {
+                    self.children.insert(segment, child);
+                }
+            }
+        }
+    }
+
+    /// Remove the style at exactly the segment path, returning whether there
+    /// was one. Nodes that are left with no style and no children are pruned,
+    /// so that their parents can be terminal again.
+    fn remove(&mut self, mut path: impl Iterator<Item = Segment>) -> bool {
+        let segment = match path.next() {
+            None => return self.declarations.take().is_some(),
+            Some(segment) => segment,
+        };
+
+        let child = match self.children.get_mut(&segment) {
+            None => return false,
+            Some(child) => child,
+        };
+
+        let removed = child.remove(path);
+
+        if child.declarations.is_none() && child.children.is_empty() {
+            self.children.remove(&segment);
+        }
+
+        removed
+    }
+
+    /// Collect the style of this node and all of its descendants, with the
+    /// segment path that leads to each of them from the root.
+    fn rules(&self, path: &mut Vec<Segment>, rules: &mut Vec<(String, Style)>) {
+        if let Some(style) = &self.declarations {
+            rules.push((path.iter().join(" "), style.clone()));
+        }
+
+        for (segment, child) in &self.children {
+            path.push(*segment);
+            child.rules(path, rules);
+            path.pop();
+        }
+    }
+
     /// Find a style for a section path. The resulting style is the merged result of all
     /// matches, with literals taking precedence over stars and stars taking precedence
     /// over globs.
@@ -267,6 +374,77 @@ impl Node {
         style
     }
 
+    /// Collect the rules that `find` would merge for a section path, in the
+    /// same order, with the segment path that leads to each of them from the
+    /// root. `path` is the path to the current node.
+    fn explain(
+        &self,
+        names: &[&'static str],
+        path: &mut Vec<Segment>,
+        rules: &mut Vec<(String, Style)>,
+    ) {
+        let next_name = match names.first() {
+            None => {
+                if let Some(terminal) = self.terminal() {
+                    if let Some(style) = &terminal.declarations {
+                        let is_child = !std::ptr::eq(terminal, self);
+
+                        if is_child {
+                            path.push(terminal.segment);
+                        }
+
+                        rules.push((path.iter().join(" "), style.clone()));
+
+                        if is_child {
+                            path.pop();
+                        }
+                    }
+                }
+
+                return;
+            }
+
+            Some(next_name) => next_name,
+        };
+
+        let matches = self.find_match(next_name);
+
+        // A glob node that absorbs the name is already on the path.
+        if let Some(glob) = matches.glob {
+            let is_child = !std::ptr::eq(glob, self);
+
+            if is_child {
+                path.push(Segment::Glob);
+            }
+
+            glob.explain(&names[1..], path, rules);
+
+            if is_child {
+                path.pop();
+            }
+        }
+
+        if let Some(star) = matches.star {
+            path.push(Segment::Star);
+            star.explain(&names[1..], path, rules);
+            path.pop();
+        }
+
+        if let Some(skipped_glob) = matches.skipped_glob {
+            path.push(Segment::Glob);
+            path.push(skipped_glob.segment);
+            skipped_glob.explain(&names[1..], path, rules);
+            path.pop();
+            path.pop();
+        }
+
+        if let Some(literal) = matches.literal {
+            path.push(literal.segment);
+            literal.explain(&names[1..], path, rules);
+            path.pop();
+        }
+    }
+
     /// Find a match in the current node for a section name.
     ///
     /// - If the current node is a glob, the current node is a match, since a
@@ -367,6 +545,147 @@ impl Stylesheet {
         self
     }
 
+    /// Combine two stylesheets, like a base theme and a plugin's overrides.
+    ///
+    /// Every rule in `other` is added to `self`. If both stylesheets have a
+    /// rule for the same selector, the styles are merged per attribute, with
+    /// `other`'s attributes taking precedence.
+    ///
+    /// ```
+    /// # use render_tree::{Stylesheet, Style};
+    ///
+    /// let base = Stylesheet::new()
+    ///     .add("message header", "fg: red; weight: bold")
+    ///     .add("message ** code", "fg: blue");
+    ///
+    /// let overrides = Stylesheet::new().add("message header", "fg: magenta");
+    ///
+    /// let stylesheet = base.merge(overrides);
+    ///
+    /// assert_eq!(stylesheet.get(&["message", "header"]),
+    ///     Some(Style("fg: magenta; weight: bold")));
+    /// assert_eq!(stylesheet.get(&["message", "code"]), Some(Style("fg: blue")));
+    /// ```
+    pub fn merge(mut self, other: Stylesheet) -> Stylesheet {
+        self.styles.merge(other.styles);
+
+        self
+    }
+
+    /// Remove the rule for exactly `selector`, returning whether there was
+    /// one. Rules for other selectors that match the same sections, like
+    /// globs, are left alone.
+    ///
+    /// ```
+    /// # use render_tree::Stylesheet;
+    ///
+    /// let mut stylesheet = Stylesheet::new().add("message header", "weight: bold");
+    ///
+    /// assert!(stylesheet.remove("message header"));
+    /// assert!(!stylesheet.remove("message header"));
+    /// assert_eq!(stylesheet.get(&["message", "header"]), None);
+    /// ```
+    pub fn remove(&mut self, selector: impl Into<Selector>) -> bool {
+        self.styles.remove(selector.into().into_iter())
+    }
+
+    /// Every rule in the stylesheet, as its selector string and its style,
+    /// sorted by selector.
+    ///
+    /// ```
+    /// # use render_tree::{Stylesheet, Style};
+    ///
+    /// let stylesheet = Stylesheet::new()
+    ///     .add("message ** code", "fg: blue")
+    ///     .add("message header", "weight: bold");
+    ///
+    /// assert_eq!(stylesheet.rules(), vec![
+    ///     ("message ** code".to_string(), Style("fg: blue")),
+    ///     ("message header".to_string(), Style("weight: bold")),
+    /// ]);
+    /// ```
+    pub fn rules(&self) -> Vec<(String, Style)> {
+        let mut rules = vec![];
+        self.styles.rules(&mut vec![], &mut rules);
+        rules.sort_by(|(left, _), (right, _)| left.cmp(right));
+        rules
+    }
+
+    /// The stylesheet as text, one `selector { declarations }` rule per line,
+    /// sorted by selector. This is the same as its `Display` form.
+    ///
+    /// ```
+    /// # use render_tree::Stylesheet;
+    ///
+    /// let stylesheet = Stylesheet::new()
+    ///     .add("message header", "weight: bold")
+    ///     .add("message ** code", "fg: blue");
+    ///
+    /// assert_eq!(
+    ///     stylesheet.to_css_like_string(),
+    ///     "message ** code { fg: blue }\nmessage header { weight: bold }\n"
+    /// );
+    /// ```
+    pub fn to_css_like_string(&self) -> String {
+        self.to_string()
+    }
+
+    /// Parse a stylesheet from rules like the ones its `Display` form prints,
+    /// a selector followed by a style string in braces:
+    ///
+    /// ```
+    /// # use render_tree::{Stylesheet, Style};
+    ///
+    /// let stylesheet = Stylesheet::parse(
+    ///     "header primary { fg: red; weight: bold }
+    ///      ** gutter { fg: blue }",
+    /// ).unwrap();
+    ///
+    /// assert_eq!(stylesheet.get(&["header", "primary"]),
+    ///     Some(Style("fg: red; weight: bold")));
+    /// assert_eq!(stylesheet.get(&["body", "gutter"]), Some(Style("fg: blue")));
+    /// ```
+    ///
+    /// Like `add`, this needs a `&'static str`, because the stylesheet
+    /// borrows its section names from the selectors.
+    pub fn parse(text: &'static str) -> Result<Stylesheet, StylesheetParseError> {
+        let mut stylesheet = Stylesheet::new();
+        let mut rest = text.trim_start();
+
+        while !rest.is_empty() {
+            let open = match rest.find(['{', '}']) {
+                Some(open) if rest[open..].starts_with('{') => open,
+                Some(_) => return Err(StylesheetParseError::UnbalancedBraces(rest.to_string())),
+                None => return Err(StylesheetParseError::MissingBlock(rest.trim().to_string())),
+            };
+
+            let selector = rest[..open].trim();
+            let block = &rest[open + 1..];
+
+            let close = match block.find(['{', '}']) {
+                Some(close) if block[close..].starts_with('}') => close,
+                _ => return Err(StylesheetParseError::UnbalancedBraces(rest.to_string())),
+            };
+
+            let parsed = match Selector::parse(selector) {
+                Some(parsed) if !parsed.segments.is_empty() => parsed,
+                _ => return Err(StylesheetParseError::InvalidSelector(selector.to_string())),
+            };
+
+            let style = Style::try_from_stylesheet(&block[..close]).map_err(|error| {
+                StylesheetParseError::InvalidStyle {
+                    selector: selector.to_string(),
+                    error,
+                }
+            })?;
+
+            stylesheet = stylesheet.add(parsed, style);
+            rest = block[close + 1..].trim_start();
+        }
+
+        Ok(stylesheet)
+    }
+
     /// Get the style associated with a nesting.
     ///
     /// ```
@@ -393,12 +712,155 @@ impl Stylesheet {
 
         style
     }
+
+    /// Explain the style `get` returns for a nesting: every rule that
+    /// contributes to it, and the merged style. Returns `None` if no rule
+    /// matches.
+    ///
+    /// ```
+    /// # use render_tree::{Stylesheet, Style};
+    ///
+    /// let stylesheet = Stylesheet::new()
+    ///     .add("message ** code", "fg: blue")
+    ///     .add("message header * code", "weight: bold; fg: red");
+    ///
+    /// let explanation = stylesheet.explain(&["message", "header", "error", "code"]).unwrap();
+    ///
+    /// assert_eq!(explanation.rules, vec![
+    ///     ("message ** code".to_string(), Style("fg: blue")),
+    ///     ("message header * code".to_string(), Style("weight: bold; fg: red")),
+    /// ]);
+    /// assert_eq!(explanation.winner(), "message header * code");
+    /// assert_eq!(explanation.style, Style("weight: bold; fg: red"));
+    /// ```
+    pub fn explain(&self, names: &[&'static str]) -> Option<MatchExplanation> {
+        let mut rules = vec![];
+        self.styles.explain(names, &mut vec![], &mut rules);
+
+        let style = rules
+            .iter()
+            .fold(None, |style, (_, rule)| union(style, Some(rule.clone())))?;
+
+        Some(MatchExplanation { rules, style })
+    }
+}
+
+/// Why a nesting has the style it has, from `Stylesheet::explain`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchExplanation {
+    /// Every rule that matches the nesting, as its selector string and its
+    /// style. The rules are in precedence order, so a rule's attributes
+    /// override those of the rules before it.
+    pub rules: Vec<(String, Style)>,
+    /// The merged style, which is what `Stylesheet::get` returns.
+    pub style: Style,
+}
+
+impl MatchExplanation {
+    /// The selector of the rule with the highest precedence.
+    pub fn winner(&self) -> &str {
+        let (selector, _) = self.rules.last().expect("an explanation has a rule");
+        selector
+    }
+}
+
+/// A stylesheet is serialized as a map from each selector to its style,
+/// sorted by selector, like `{ "message ** code": { "fg": "blue" } }`.
+impl Serialize for Stylesheet {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let rules = self.rules();
+        let mut map = serializer.serialize_map(Some(rules.len()))?;
+
+        for (selector, style) in &rules {
+            map.serialize_entry(selector, style)?;
+        }
+
+        map.end()
+    }
+}
+
+/// Since a stylesheet borrows its section names from its selectors for
+/// `'static`, deserializing one leaks its selector strings. Themes are
+/// usually loaded once, so this is rarely a problem, but avoid
+/// deserializing stylesheets in a loop.
+impl<'de> Deserialize<'de> for Stylesheet {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Stylesheet, D::Error> {
+        deserializer.deserialize_map(StylesheetVisitor)
+    }
+}
+
+struct StylesheetVisitor;
+
+impl<'de> Visitor<'de> for StylesheetVisitor {
+    type Value = Stylesheet;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a map from selectors to styles")
+    }
+
+    fn visit_map<M: MapAccess<'de>>(self, mut access: M) -> Result<Stylesheet, M::Error> {
+        let mut stylesheet = Stylesheet::new();
+
+        while let Some((selector, style)) = access.next_entry::<String, Style>()? {
+            let selector: &'static str = Box::leak(selector.into_boxed_str());
+            stylesheet = stylesheet.add(selector, style);
+        }
+
+        Ok(stylesheet)
+    }
+}
+
+/// An error from `Stylesheet::parse`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StylesheetParseError {
+    /// A rule whose `{` has no matching `}`, or that has a `}` before its `{`.
+    UnbalancedBraces(String),
+    /// A selector at the end of the text without a `{ ... }` block.
+    MissingBlock(String),
+    /// A rule without a selector, like `{ fg: red }`.
+    InvalidSelector(String),
+    /// A rule whose style string is invalid.
+    InvalidStyle {
+        selector: String,
+        error: StyleParseError,
+    },
+}
+
+impl fmt::Display for StylesheetParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StylesheetParseError::UnbalancedBraces(rule) => {
+                write!(f, "Unbalanced braces in `{}`", rule.trim())
+            }
+            StylesheetParseError::MissingBlock(selector) => {
+                write!(f, "Missing `{{ ... }}` after `{}`", selector)
+            }
+            StylesheetParseError::InvalidSelector(selector) => {
+                write!(f, "Invalid selector `{}`", selector)
+            }
+            StylesheetParseError::InvalidStyle { selector, error } => {
+                write!(f, "Invalid style for `{}`: {}", selector, error)
+            }
+        }
+    }
+}
+
+impl std::error::Error for StylesheetParseError {}
+
+impl fmt::Display for Stylesheet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (selector, style) in self.rules() {
+            writeln!(f, "{} {{ {} }}", selector, style.to_declarations())?;
+        }
+
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::style::Style;
-    use crate::{Color, Stylesheet};
+    use crate::{Color, Selector, Stylesheet};
     use pretty_env_logger;
 
     fn init_logger() {
@@ -583,6 +1045,51 @@ mod tests {
         )
     }
 
+    #[test]
+    fn test_explain_priority() {
+        init_logger();
+
+        let stylesheet = Stylesheet::new()
+            .add("message ** code", "fg: blue; weight: bold")
+            .add("message header * code", "underline: true; bg: black")
+            .add("message header error code", "fg: red; underline: false");
+
+        let explanation = stylesheet
+            .explain(&["message", "header", "error", "code"])
+            .unwrap();
+
+        assert_eq!(
+            explanation.rules,
+            vec![
+                (
+                    "message ** code".to_string(),
+                    Style("fg: blue; weight: bold")
+                ),
+                (
+                    "message header * code".to_string(),
+                    Style("underline: true; bg: black")
+                ),
+                (
+                    "message header error code".to_string(),
+                    Style("fg: red; underline: false")
+                ),
+            ]
+        );
+
+        assert_eq!(explanation.winner(), "message header error code");
+        assert_eq!(
+            Some(explanation.style),
+            stylesheet.get(&["message", "header", "error", "code"])
+        );
+    }
+
+    #[test]
+    fn test_explain_no_match() {
+        let stylesheet = Stylesheet::new().add("message header", "weight: bold");
+
+        assert_eq!(stylesheet.explain(&["message", "body"]), None);
+    }
+
     #[test]
     fn test_priority_with_typed_style() {
         init_logger();
@@ -610,4 +1117,371 @@ mod tests {
             )
         )
     }
+
+    #[test]
+    fn test_merge_overlapping_selectors() {
+        init_logger();
+
+        let base = Stylesheet::new()
+            .add("message header * code", "fg: red; weight: bold")
+            .add("message ** gutter", "fg: blue; underline: true");
+
+        let overrides = Stylesheet::new()
+            .add("message header * code", Style::new().fg(Color::Magenta))
+            .add("message ** gutter", "underline: false");
+
+        let stylesheet = base.merge(overrides);
+
+        assert_eq!(
+            stylesheet.get(&["message", "header", "error", "code"]),
+            Some(Style::new().fg(Color::Magenta).bold())
+        );
+        assert_eq!(
+            stylesheet.get(&["message", "body", "gutter"]),
+            Some(Style::new().fg(Color::Blue).nounderline())
+        );
+    }
+
+    #[test]
+    fn test_merge_disjoint_selectors() {
+        init_logger();
+
+        let base = Stylesheet::new().add("message header", "weight: bold");
+        let plugin = Stylesheet::new()
+            .add("message code", "fg: red")
+            .add("plugin *", "fg: green");
+
+        let stylesheet = base.merge(plugin);
+
+        assert_eq!(
+            stylesheet.get(&["message", "header"]),
+            Some(Style("weight: bold"))
+        );
+        assert_eq!(
+            stylesheet.get(&["message", "code"]),
+            Some(Style("fg: red"))
+        );
+        assert_eq!(
+            stylesheet.get(&["plugin", "status"]),
+            Some(Style("fg: green"))
+        );
+        assert_eq!(stylesheet.get(&["other"]), None);
+    }
+
+    #[test]
+    fn test_merge_with_empty_stylesheet() {
+        init_logger();
+
+        let stylesheet = Stylesheet::new()
+            .add("message header", "weight: bold")
+            .merge(Stylesheet::new());
+
+        assert_eq!(
+            stylesheet.get(&["message", "header"]),
+            Some(Style("weight: bold"))
+        );
+
+        let stylesheet = Stylesheet::new().merge(Stylesheet::new().add("message", "fg: red"));
+
+        assert_eq!(stylesheet.get(&["message"]), Some(Style("fg: red")));
+    }
+
+    #[test]
+    fn test_display_round_trip() {
+        init_logger();
+
+        let stylesheet = Stylesheet::new()
+            .add("message ** code", "fg: blue; weight: bold")
+            .add("message header * code", "underline: true; bg: ansi(130)")
+            .add("message header error code", "fg: #ff0000; underline: false");
+
+        let text = stylesheet.to_string();
+
+        assert_eq!(
+            text,
+            "message ** code { fg: blue; weight: bold }\n\
+             message header * code { bg: ansi(130); underline: true }\n\
+             message header error code { fg: #ff0000; underline: false }\n"
+        );
+        assert_eq!(stylesheet.to_css_like_string(), text);
+
+        let reparsed = text.lines().fold(Stylesheet::new(), |stylesheet, line| {
+            let line: &'static str = Box::leak(line.to_string().into_boxed_str());
+            let open = line.find(" { ").unwrap();
+            let declarations = line[open + 3..].trim_end_matches(" }");
+
+            stylesheet.add(&line[..open], declarations)
+        });
+
+        assert_eq!(reparsed.rules(), stylesheet.rules());
+        assert_eq!(reparsed.to_string(), text);
+    }
+
+    #[test]
+    fn test_parse() {
+        init_logger();
+
+        let stylesheet = Stylesheet::parse(
+            "
+                message ** code { fg: blue; weight: bold }
+                message header * code {
+                    underline: true;
+                    bg: black;
+                }
+                message  header > error code { fg: red; underline: false } ** gutter {}
+            ",
+        )
+        .unwrap();
+
+        assert_eq!(
+            stylesheet.get(&["message", "header", "error", "code"]),
+            Some(
+                Style::new()
+                    .fg(Color::Red)
+                    .bg(Color::Black)
+                    .nounderline()
+                    .bold()
+            )
+        );
+        assert_eq!(stylesheet.get(&["message", "gutter"]), Some(Style::new()));
+
+        let printed = Box::leak(stylesheet.to_string().into_boxed_str());
+
+        assert_eq!(Stylesheet::parse(printed).unwrap().rules(), stylesheet.rules());
+        assert_eq!(Stylesheet::parse("  \n").unwrap().rules(), vec![]);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        use super::{StyleParseError, StylesheetParseError};
+
+        assert_eq!(
+            Stylesheet::parse("header { fg: red").unwrap_err(),
+            StylesheetParseError::UnbalancedBraces("header { fg: red".to_string())
+        );
+        assert_eq!(
+            Stylesheet::parse("header { fg: red } }").unwrap_err(),
+            StylesheetParseError::UnbalancedBraces("}".to_string())
+        );
+        assert_eq!(
+            Stylesheet::parse("header { code { fg: red } }").unwrap_err(),
+            StylesheetParseError::UnbalancedBraces("header { code { fg: red } }".to_string())
+        );
+        assert_eq!(
+            Stylesheet::parse("header { fg: red }\ncode").unwrap_err(),
+            StylesheetParseError::MissingBlock("code".to_string())
+        );
+        assert_eq!(
+            Stylesheet::parse("{ fg: red }").unwrap_err(),
+            StylesheetParseError::InvalidSelector("".to_string())
+        );
+
+        let error = Stylesheet::parse("header { fg: red }\ncode { colour: red }").unwrap_err();
+
+        assert_eq!(
+            error,
+            StylesheetParseError::InvalidStyle {
+                selector: "code".to_string(),
+                error: StyleParseError::UnknownAttribute("colour".to_string()),
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "Invalid style for `code`: Invalid style attribute name colour"
+        );
+    }
+
+    #[test]
+    fn test_adjacent_globs_are_collapsed() {
+        init_logger();
+
+        let selector = Selector::from("a ** ** b");
+        assert_eq!(
+            selector.into_iter().collect::<Vec<_>>(),
+            Selector::from("a ** b").into_iter().collect::<Vec<_>>()
+        );
+
+        let stylesheet = Stylesheet::new().add("a ** ** b", "fg: red");
+
+        assert_eq!(stylesheet.rules(), vec![("a ** b".to_string(), Style("fg: red"))]);
+        assert_eq!(stylesheet.get(&["a", "b"]), Some(Style("fg: red")));
+        assert_eq!(stylesheet.get(&["a", "x", "y", "b"]), Some(Style("fg: red")));
+        assert_eq!(
+            Stylesheet::parse("** ** gutter { fg: blue }").unwrap().rules(),
+            vec![("** gutter".to_string(), Style("fg: blue"))]
+        );
+    }
+
+    #[test]
+    fn test_serde_round_trip() {
+        init_logger();
+
+        let stylesheet = Stylesheet::new()
+            .add("message ** code", "fg: blue; weight: bold")
+            .add("message header * code", "underline: true; bg: black")
+            .add("message header error code", "fg: red; underline: false");
+
+        let json = serde_json::to_string(&stylesheet).unwrap();
+
+        assert_eq!(
+            json,
+            r#"{"message ** code":{"fg":"blue","weight":"bold"},"#.to_string()
+                + r#""message header * code":{"bg":"black","underline":true},"#
+                + r#""message header error code":{"fg":"red","underline":false}}"#
+        );
+
+        let deserialized: Stylesheet = serde_json::from_str(&json).unwrap();
+
+        for path in &[
+            &["message", "header", "error", "code"][..],
+            &["message", "header", "warning", "code"],
+            &["message", "body", "code"],
+            &["message", "header"],
+        ] {
+            assert_eq!(deserialized.get(path), stylesheet.get(path));
+        }
+
+        assert_eq!(deserialized.rules(), stylesheet.rules());
+    }
+
+    #[test]
+    fn test_remove_and_rules() {
+        init_logger();
+
+        let mut stylesheet = Stylesheet::new()
+            .add("message header", "weight: bold")
+            .add("message header code", "fg: red")
+            .add("** gutter", "fg: blue");
+
+        assert!(stylesheet.remove("message header code"));
+        assert!(!stylesheet.remove("message header code"));
+        assert!(!stylesheet.remove("message body"));
+
+        assert_eq!(
+            stylesheet.rules(),
+            vec![
+                ("** gutter".to_string(), Style("fg: blue")),
+                ("message header".to_string(), Style("weight: bold")),
+            ]
+        );
+
+        // Without its child, `message header` is terminal again
+        assert_eq!(
+            stylesheet.get(&["message", "header"]),
+            Some(Style("weight: bold"))
+        );
+        assert_eq!(stylesheet.get(&["message", "header", "code"]), None);
+    }
+
+    #[test]
+    fn test_remove_keeps_descendants() {
+        init_logger();
+
+        let mut stylesheet = Stylesheet::new()
+            .add("message", "weight: bold")
+            .add("message code", "fg: red");
+
+        assert!(stylesheet.remove("message"));
+
+        assert_eq!(
+            stylesheet.rules(),
+            vec![("message code".to_string(), Style("fg: red"))]
+        );
+        assert_eq!(
+            stylesheet.get(&["message", "code"]),
+            Some(Style("fg: red"))
+        );
+    }
+
+    #[test]
+    fn test_child_combinator() {
+        init_logger();
+
+        let path = &["message", "header", "error", "code"];
+
+        let child = Stylesheet::new().add("message header > code", "fg: red");
+        let glob = Stylesheet::new().add("message header ** code", "fg: red");
+
+        assert_eq!(child.get(path), None);
+        assert_eq!(glob.get(path), Some(Style("fg: red")));
+
+        let child = Stylesheet::new().add("message > header > error > code", "fg: red");
+        assert_eq!(child.get(path), Some(Style("fg: red")));
+
+        let selector = Selector::name("message")
+            .add_child("header")
+            .add_child("error")
+            .add_child("code");
+        let child = Stylesheet::new().add(selector, "fg: red");
+        assert_eq!(child.get(path), Some(Style("fg: red")));
+        assert_eq!(
+            child.rules(),
+            vec![("message header error code".to_string(), Style("fg: red"))]
+        );
+    }
+
+    #[test]
+    fn test_misplaced_child_combinators() {
+        use super::StylesheetParseError;
+
+        let invalid = [
+            ("> code { fg: red }", "> code"),
+            ("header > { fg: red }", "header >"),
+            ("header > > code { fg: red }", "header > > code"),
+            ("> { fg: red }", ">"),
+        ];
+
+        for (text, selector) in &invalid {
+            assert_eq!(
+                Stylesheet::parse(text).unwrap_err(),
+                StylesheetParseError::InvalidSelector(selector.to_string())
+            );
+        }
+
+        assert!(Stylesheet::parse("header > ** > code { fg: red }").is_ok());
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid selector `header > > code`")]
+    fn test_doubled_child_combinator_panics() {
+        Stylesheet::new().add("header > > code", "fg: red");
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid selector `> code`")]
+    fn test_leading_child_combinator_panics() {
+        Stylesheet::new().add("> code", "fg: red");
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid selector `header >`")]
+    fn test_trailing_child_combinator_panics() {
+        Stylesheet::new().add("header >", "fg: red");
+    }
+
+    #[test]
+    fn test_important() {
+        init_logger();
+
+        let stylesheet = Stylesheet::new()
+            .add("message ** code", "fg: blue !important; weight: bold")
+            .add("message header code", "fg: red; weight: normal");
+
+        assert_eq!(
+            stylesheet.get(&["message", "header", "code"]),
+            Some(Style("fg: blue !important; weight: normal"))
+        );
+
+        let stylesheet = stylesheet.add("message header code", "fg: green !important");
+
+        assert_eq!(
+            stylesheet.get(&["message", "header", "code"]),
+            Some(Style("fg: green !important; weight: bold"))
+        );
+        assert_eq!(
+            stylesheet.to_string(),
+            "message ** code { fg: blue !important; weight: bold }\n\
+             message header code { fg: green !important }\n"
+        );
+    }
 }
@@ -0,0 +1,98 @@
+use std::io;
+use termcolor::{ColorSpec, WriteColor};
+
+/// Something that happened to a `RecordingWriter`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// `set_color` was called with this spec.
+    SetColor(ColorSpec),
+    /// `reset` was called.
+    Reset,
+    /// Text was written. Consecutive writes are recorded as one event.
+    Write(String),
+}
+
+/// A `WriteColor` that records every call to it as an `Event`, so that tests
+/// can assert the exact sequence of colors, resets and text.
+///
+/// Unlike `ColorAccumulator`, nothing is collapsed: setting the same color
+/// twice is recorded twice.
+#[derive(Debug, Default)]
+pub struct RecordingWriter {
+    events: Vec<Event>,
+}
+
+impl RecordingWriter {
+    pub fn new() -> RecordingWriter {
+        RecordingWriter { events: vec![] }
+    }
+
+    pub fn events(&self) -> &[Event] {
+        &self.events
+    }
+}
+
+impl io::Write for RecordingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let text = String::from_utf8_lossy(buf);
+
+        match self.events.last_mut() {
+            Some(Event::Write(last)) => last.push_str(&text),
+            _ => self.events.push(Event::Write(text.into_owned())),
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl WriteColor for RecordingWriter {
+    fn supports_color(&self) -> bool {
+        true
+    }
+
+    fn set_color(&mut self, spec: &ColorSpec) -> io::Result<()> {
+        self.events.push(Event::SetColor(spec.clone()));
+        Ok(())
+    }
+
+    fn reset(&mut self) -> io::Result<()> {
+        self.events.push(Event::Reset);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use termcolor::Color;
+
+    #[test]
+    fn test_recording_writer() -> io::Result<()> {
+        let mut red = ColorSpec::new();
+        red.set_fg(Some(Color::Red));
+
+        let mut writer = RecordingWriter::new();
+        writer.set_color(&red)?;
+        write!(writer, "error")?;
+        write!(writer, ": ")?;
+        writer.reset()?;
+        writer.write_all("héllo".as_bytes())?;
+
+        assert_eq!(
+            writer.events(),
+            &[
+                Event::SetColor(red),
+                Event::Write("error: ".to_string()),
+                Event::Reset,
+                Event::Write("héllo".to_string()),
+            ]
+        );
+
+        Ok(())
+    }
+}
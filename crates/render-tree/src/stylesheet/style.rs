@@ -1,4 +1,7 @@
 use crate::stylesheet::Color;
+use serde::de::{self, MapAccess, Visitor};
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std;
 use std::fmt;
 use std::io;
@@ -34,10 +37,10 @@ impl AttributeValue for ColorAttribute {
     type ApplyValue = Option<Color>;
     type SetValue = ColorAttribute;
 
-    fn parse(s: &str) -> ColorAttribute {
+    fn parse(s: &str) -> Option<ColorAttribute> {
         match s {
-            "reset" => ColorAttribute::Reset,
-            other => ColorAttribute::Color(other.into()),
+            "reset" => Some(ColorAttribute::Reset),
+            other => other.parse().ok().map(ColorAttribute::Color),
         }
     }
 
@@ -141,12 +144,12 @@ impl AttributeValue for WeightAttribute {
     type ApplyValue = SetWeight;
     type SetValue = WeightAttribute;
 
-    fn parse(s: &str) -> WeightAttribute {
+    fn parse(s: &str) -> Option<WeightAttribute> {
         match s {
-            "normal" => WeightAttribute::Normal,
-            "bold" => WeightAttribute::Bold,
-            "dim" => WeightAttribute::Dim,
-            other => panic!("Unexpected value for `weight`: {}", other),
+            "normal" => Some(WeightAttribute::Normal),
+            "bold" => Some(WeightAttribute::Bold),
+            "dim" => Some(WeightAttribute::Dim),
+            _ => None,
         }
     }
 
@@ -214,11 +217,11 @@ impl AttributeValue for BooleanAttribute {
     type ApplyValue = bool;
     type SetValue = BooleanAttribute;
 
-    fn parse(s: &str) -> BooleanAttribute {
+    fn parse(s: &str) -> Option<BooleanAttribute> {
         match s {
-            "true" => BooleanAttribute::On,
-            "false" => BooleanAttribute::Off,
-            other => panic!("Unexpected boolean attribute {}", other),
+            "true" => Some(BooleanAttribute::On),
+            "false" => Some(BooleanAttribute::Off),
+            _ => None,
         }
     }
 
@@ -270,7 +273,7 @@ pub trait AttributeValue: Default + fmt::Display {
     type ApplyValue;
     type SetValue;
 
-    fn parse(s: &str) -> Self;
+    fn parse(s: &str) -> Option<Self>;
     fn update(self, other: Self) -> Self;
     fn apply(&self, f: impl FnOnce(Self::ApplyValue));
     fn is_default(&self) -> bool;
@@ -282,11 +285,14 @@ pub trait AttributeValue: Default + fmt::Display {
 pub struct Attribute<Value: AttributeValue> {
     name: AttributeName,
     value: Value,
+    /// Whether the attribute was declared `!important`, so that it isn't
+    /// overridden by a higher-precedence attribute that isn't.
+    important: bool,
 }
 
 impl<Value: AttributeValue> fmt::Display for Attribute<Value> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}={}", self.name, self.value)
+        write!(f, "{}={}", self.name, self.declaration_value())
     }
 }
 
@@ -298,11 +304,31 @@ impl<Value: AttributeValue> Attribute<Value> {
     pub fn tuple(&self) -> (AttributeName, Option<String>) {
         (self.name, self.value.debug_value())
     }
+
+    /// The value as it's written in a style string, like `red` or
+    /// `red !important`.
+    fn declaration_value(&self) -> String {
+        if self.important {
+            format!("{} !important", self.value)
+        } else {
+            self.value.to_string()
+        }
+    }
 }
 
 impl<Value: AttributeValue> Attribute<Value> {
+    /// Override this attribute with a higher-precedence one, unless this one
+    /// is important and the other one isn't.
     pub fn update(self, attribute: Attribute<Value>) -> Attribute<Value> {
-        Attribute(self.name.clone(), self.value.update(attribute.value))
+        if self.important && !attribute.important {
+            return self;
+        }
+
+        Attribute {
+            name: self.name,
+            value: self.value.update(attribute.value),
+            important: attribute.important,
+        }
     }
 
     pub fn apply(&self, f: impl FnOnce(Value::ApplyValue)) {
@@ -320,6 +346,13 @@ impl<Value: AttributeValue> Attribute<Value> {
     pub fn mutate(&mut self, value: Value) {
         self.value = value
     }
+
+    fn important(self) -> Attribute<Value> {
+        Attribute {
+            important: true,
+            ..self
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -328,16 +361,33 @@ pub enum AttributeName {
     Bg,
     Weight,
     Underline,
+    Italic,
+    Strikethrough,
+    UnderlineColor,
 }
 
+impl AttributeName {
+    pub fn parse(name: &str) -> Result<AttributeName, String> {
+        match name {
+            "fg" => Ok(AttributeName::Fg),
+            "bg" => Ok(AttributeName::Bg),
+            "weight" => Ok(AttributeName::Weight),
+            "underline" => Ok(AttributeName::Underline),
+            "italic" => Ok(AttributeName::Italic),
+            "strikethrough" => Ok(AttributeName::Strikethrough),
+            "underline-color" => Ok(AttributeName::UnderlineColor),
+            other => Err(format!("Invalid style attribute name {}", other)),
+        }
+    }
+}
+
+/// Panics if the name isn't a valid attribute. Use `AttributeName::parse`
+/// to handle invalid names.
 impl<'a> From<&'a str> for AttributeName {
     fn from(from: &'a str) -> AttributeName {
-        match from {
-            "fg" => AttributeName::Fg,
-            "bg" => AttributeName::Bg,
-            "weight" => AttributeName::Weight,
-            "underline" => AttributeName::Underline,
-            other => panic!("Invalid style attribute name {}", other),
+        match AttributeName::parse(from) {
+            Ok(name) => name,
+            Err(message) => panic!("{}", message),
         }
     }
 }
@@ -355,6 +405,9 @@ impl fmt::Display for AttributeName {
             AttributeName::Bg => "bg",
             AttributeName::Weight => "weight",
             AttributeName::Underline => "underline",
+            AttributeName::Italic => "italic",
+            AttributeName::Strikethrough => "strikethrough",
+            AttributeName::UnderlineColor => "underline-color",
         };
 
         write!(f, "{}", name)
@@ -366,6 +419,7 @@ fn Attribute<Value: AttributeValue>(name: AttributeName, value: Value) -> Attrib
     Attribute {
         name: name.into(),
         value,
+        important: false,
     }
 }
 
@@ -373,6 +427,9 @@ fn Attribute<Value: AttributeValue>(name: AttributeName, value: Value) -> Attrib
 pub struct Style {
     weight: Attribute<WeightAttribute>,
     underline: Attribute<BooleanAttribute>,
+    italic: Attribute<BooleanAttribute>,
+    strikethrough: Attribute<BooleanAttribute>,
+    underline_color: Attribute<ColorAttribute>,
     fg: Attribute<ColorAttribute>,
     bg: Attribute<ColorAttribute>,
 }
@@ -413,6 +470,21 @@ impl fmt::Display for Style {
             write!(f, "{}", self.underline)?;
         }
 
+        if self.italic.has_value() {
+            space(f)?;
+            write!(f, "{}", self.italic)?;
+        }
+
+        if self.strikethrough.has_value() {
+            space(f)?;
+            write!(f, "{}", self.strikethrough)?;
+        }
+
+        if self.underline_color.has_value() {
+            space(f)?;
+            write!(f, "{}", self.underline_color)?;
+        }
+
         write!(f, "}}")?;
 
         Ok(())
@@ -431,6 +503,9 @@ impl Style {
             bg: Attribute(AttributeName::Bg, ColorAttribute::default()),
             weight: Attribute(AttributeName::Weight, WeightAttribute::default()),
             underline: Attribute(AttributeName::Underline, BooleanAttribute::default()),
+            italic: Attribute(AttributeName::Italic, BooleanAttribute::default()),
+            strikethrough: Attribute(AttributeName::Strikethrough, BooleanAttribute::default()),
+            underline_color: Attribute(AttributeName::UnderlineColor, ColorAttribute::default()),
         }
     }
 
@@ -438,29 +513,55 @@ impl Style {
         Style::empty()
     }
 
+    /// Parse a style string like `"fg: red; weight: bold"`, panicking if it's
+    /// invalid. Use `Style::try_from_stylesheet` to handle invalid styles.
     pub fn from_stylesheet(input: &str) -> Style {
+        match Style::try_from_stylesheet(input) {
+            Ok(style) => style,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    pub fn try_from_stylesheet(input: &str) -> Result<Style, StyleParseError> {
+        Style::from_declarations(StyleString::new(input))
+    }
+
+    /// Build a style from `(name, value)` declarations, like the ones in a
+    /// style string.
+    fn from_declarations<'a>(
+        declarations: impl IntoIterator<Item = Result<(AttributeName, &'a str), StyleParseError>>,
+    ) -> Result<Style, StyleParseError> {
         let mut fg = Attribute::inherit(AttributeName::Fg);
         let mut bg = Attribute::inherit(AttributeName::Bg);
         let mut weight = Attribute::inherit(AttributeName::Weight);
         let mut underline = Attribute::inherit(AttributeName::Underline);
+        let mut italic = Attribute::inherit(AttributeName::Italic);
+        let mut strikethrough = Attribute::inherit(AttributeName::Strikethrough);
+        let mut underline_color = Attribute::inherit(AttributeName::UnderlineColor);
+
+        for declaration in declarations {
+            let (key, value) = declaration?;
 
-        for (key, value) in StyleString::new(input) {
             match key {
-                AttributeName::Fg => fg = Attribute(key, ColorAttribute::parse(value)),
-                AttributeName::Bg => bg = Attribute(key, ColorAttribute::parse(value)),
-                AttributeName::Weight => weight = Attribute(key, WeightAttribute::parse(value)),
-                AttributeName::Underline => {
-                    underline = Attribute(key, BooleanAttribute::parse(value))
-                }
+                AttributeName::Fg => fg = parse_attribute(key, value)?,
+                AttributeName::Bg => bg = parse_attribute(key, value)?,
+                AttributeName::Weight => weight = parse_attribute(key, value)?,
+                AttributeName::Underline => underline = parse_attribute(key, value)?,
+                AttributeName::Italic => italic = parse_attribute(key, value)?,
+                AttributeName::Strikethrough => strikethrough = parse_attribute(key, value)?,
+                AttributeName::UnderlineColor => underline_color = parse_attribute(key, value)?,
             }
         }
 
-        Style {
+        Ok(Style {
             weight,
             underline,
+            italic,
+            strikethrough,
+            underline_color,
             bg,
             fg,
-        }
+        })
     }
 
     pub fn from_color_spec(spec: ColorSpec) -> Style {
@@ -488,11 +589,95 @@ impl Style {
         Style {
             weight: Attribute(AttributeName::Weight, weight),
             underline: Attribute(AttributeName::Underline, underline),
+            italic: Attribute::inherit(AttributeName::Italic),
+            strikethrough: Attribute::inherit(AttributeName::Strikethrough),
+            underline_color: Attribute::inherit(AttributeName::UnderlineColor),
             fg: Attribute(AttributeName::Fg, foreground),
             bg: Attribute(AttributeName::Bg, background),
         }
     }
 
+    /// The attributes this style sets, as a style string like
+    /// `"fg: red; weight: bold"` that parses back to the same style.
+    pub fn to_declarations(&self) -> String {
+        fn push(declarations: &mut Vec<String>, attribute: &Attribute<impl AttributeValue>) {
+            if attribute.has_value() {
+                declarations.push(format!("{}: {}", attribute.name, attribute.declaration_value()));
+            }
+        }
+
+        let mut declarations = vec![];
+
+        push(&mut declarations, &self.fg);
+        push(&mut declarations, &self.bg);
+        push(&mut declarations, &self.weight);
+        push(&mut declarations, &self.underline);
+        push(&mut declarations, &self.italic);
+        push(&mut declarations, &self.strikethrough);
+        push(&mut declarations, &self.underline_color);
+
+        declarations.join("; ")
+    }
+
+    /// The attributes this style sets, as inline CSS like
+    /// `color:red;font-weight:bold`.
+    pub fn to_css(&self) -> String {
+        fn color(attribute: &ColorAttribute) -> Option<String> {
+            match attribute {
+                ColorAttribute::Color(color) => Some(color.to_css()),
+                ColorAttribute::Reset => Some("initial".to_string()),
+                ColorAttribute::Inherit => None,
+            }
+        }
+
+        let mut declarations = vec![];
+
+        if let Some(fg) = color(&self.fg.value) {
+            declarations.push(format!("color:{}", fg));
+        }
+
+        if let Some(bg) = color(&self.bg.value) {
+            declarations.push(format!("background-color:{}", bg));
+        }
+
+        match self.weight.value {
+            WeightAttribute::Bold => declarations.push("font-weight:bold".to_string()),
+            WeightAttribute::Normal => declarations.push("font-weight:normal".to_string()),
+            WeightAttribute::Dim => declarations.push("font-weight:lighter".to_string()),
+            WeightAttribute::Inherit => {}
+        }
+
+        match self.italic.value {
+            BooleanAttribute::On => declarations.push("font-style:italic".to_string()),
+            BooleanAttribute::Off => declarations.push("font-style:normal".to_string()),
+            BooleanAttribute::Inherit => {}
+        }
+
+        let mut lines = vec![];
+
+        if self.underline.value == BooleanAttribute::On {
+            lines.push("underline");
+        }
+
+        if self.strikethrough.value == BooleanAttribute::On {
+            lines.push("line-through");
+        }
+
+        if !lines.is_empty() {
+            declarations.push(format!("text-decoration-line:{}", lines.join(" ")));
+        } else if self.underline.value == BooleanAttribute::Off
+            || self.strikethrough.value == BooleanAttribute::Off
+        {
+            declarations.push("text-decoration-line:none".to_string());
+        }
+
+        if let Some(underline_color) = color(&self.underline_color.value) {
+            declarations.push(format!("text-decoration-color:{}", underline_color));
+        }
+
+        declarations.join(";")
+    }
+
     pub fn debug_attributes(&self) -> Vec<(AttributeName, Option<String>)> {
         let mut attrs: Vec<(AttributeName, Option<String>)> = vec![];
 
@@ -508,6 +693,18 @@ impl Style {
             attrs.push(self.bg.tuple());
         }
 
+        if self.italic.has_value() {
+            attrs.push(self.italic.tuple());
+        }
+
+        if self.strikethrough.has_value() {
+            attrs.push(self.strikethrough.tuple());
+        }
+
+        if self.underline_color.has_value() {
+            attrs.push(self.underline_color.tuple());
+        }
+
         attrs
     }
 
@@ -515,6 +712,9 @@ impl Style {
         Style {
             weight: self.weight.update(other.weight),
             underline: self.underline.update(other.underline),
+            italic: self.italic.update(other.italic),
+            strikethrough: self.strikethrough.update(other.strikethrough),
+            underline_color: self.underline_color.update(other.underline_color),
             fg: self.fg.update(other.fg),
             bg: self.bg.update(other.bg),
         }
@@ -539,12 +739,27 @@ impl Style {
             spec.set_underline(b);
         });
 
+        // termcolor 1.0 can't express italic, so `italic` is dropped here
+        // until termcolor is upgraded to 1.1, which adds `set_italic`.
+        //
+        // `ColorSpec` has no strikethrough or underline color at all, so they
+        // are also dropped here. `Document::write_ansi_with` writes them as
+        // raw escape sequences.
+
         self.fg.apply(|fg| {
             spec.set_fg(fg.map(|fg| fg.into()));
+
+            if fg.is_some_and(Color::is_bright) {
+                spec.set_intense(true);
+            }
         });
 
         self.bg.apply(|bg| {
             spec.set_bg(bg.map(|bg| bg.into()));
+
+            if bg.is_some_and(Color::is_bright) {
+                spec.set_intense(true);
+            }
         });
 
         spec
@@ -557,6 +772,9 @@ impl Style {
     pub fn is_default(&self) -> bool {
         self.weight.is_default()
             && self.underline.is_default()
+            && self.italic.is_default()
+            && self.strikethrough.is_default()
+            && self.underline_color.is_default()
             && self.fg.is_default()
             && self.bg.is_default()
     }
@@ -595,6 +813,43 @@ impl Style {
         self.update(|style| style.underline.mutate(BooleanAttribute::Off))
     }
 
+    pub fn italic(&self) -> Style {
+        self.update(|style| style.italic.mutate(BooleanAttribute::On))
+    }
+
+    pub fn noitalic(&self) -> Style {
+        self.update(|style| style.italic.mutate(BooleanAttribute::Off))
+    }
+
+    pub fn strikethrough(&self) -> Style {
+        self.update(|style| style.strikethrough.mutate(BooleanAttribute::On))
+    }
+
+    pub fn nostrikethrough(&self) -> Style {
+        self.update(|style| style.strikethrough.mutate(BooleanAttribute::Off))
+    }
+
+    pub fn underline_color(&self, color: impl Into<Color>) -> Style {
+        let color_attribute = ColorAttribute::Color(color.into());
+        self.update(|style| style.underline_color.mutate(color_attribute))
+    }
+
+    /// The color that this style sets underlines to, if any. `to_color_spec`
+    /// can't express underline colors, so writers that support them check
+    /// this instead.
+    pub fn get_underline_color(&self) -> Option<Color> {
+        match self.underline_color.value {
+            ColorAttribute::Color(color) => Some(color),
+            _ => None,
+        }
+    }
+
+    /// Whether this style turns strikethrough on. `to_color_spec` can't
+    /// express strikethrough, so writers that support it check this instead.
+    pub fn is_strikethrough(&self) -> bool {
+        self.strikethrough.value == BooleanAttribute::On
+    }
+
     fn update(&self, f: impl FnOnce(&mut Style)) -> Style {
         let mut style = self.clone();
         f(&mut style);
@@ -602,6 +857,188 @@ impl Style {
     }
 }
 
+/// Attribute values are serialized like they're written in a style string,
+/// and deserialized with `AttributeValue::parse`. `inherit` round-trips to
+/// the default value.
+macro_rules! serde_attribute_value {
+    ($name:ident) => {
+        impl Serialize for $name {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.collect_str(self)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $name {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<$name, D::Error> {
+                let value = String::deserialize(deserializer)?;
+
+                match &value[..] {
+                    "inherit" => Ok($name::default()),
+                    other => $name::parse(other).ok_or_else(|| {
+                        de::Error::custom(format!("invalid {} `{}`", stringify!($name), other))
+                    }),
+                }
+            }
+        }
+    };
+}
+
+serde_attribute_value!(ColorAttribute);
+serde_attribute_value!(WeightAttribute);
+serde_attribute_value!(BooleanAttribute);
+
+/// A style is serialized as a map of the attributes that it sets, using the
+/// same names and values as a style string, like
+/// `{ "fg": "red", "weight": "bold", "underline": true }`. Boolean
+/// attributes are serialized as booleans, and can be deserialized from
+/// either booleans or strings.
+impl Serialize for Style {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+
+        for color in &[&self.fg, &self.bg] {
+            if color.has_value() {
+                map.serialize_entry(&color.name.to_string(), &color.declaration_value())?;
+            }
+        }
+
+        if self.weight.has_value() {
+            let name = self.weight.name.to_string();
+            map.serialize_entry(&name, &self.weight.declaration_value())?;
+        }
+
+        for boolean in &[&self.underline, &self.italic, &self.strikethrough] {
+            let name = boolean.name.to_string();
+
+            match boolean.value {
+                BooleanAttribute::Inherit => {}
+                _ if boolean.important => map.serialize_entry(&name, &boolean.declaration_value())?,
+                BooleanAttribute::On => map.serialize_entry(&name, &true)?,
+                BooleanAttribute::Off => map.serialize_entry(&name, &false)?,
+            }
+        }
+
+        if self.underline_color.has_value() {
+            let name = self.underline_color.name.to_string();
+            map.serialize_entry(&name, &self.underline_color.declaration_value())?;
+        }
+
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Style {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Style, D::Error> {
+        deserializer.deserialize_map(StyleVisitor)
+    }
+}
+
+struct StyleVisitor;
+
+impl<'de> Visitor<'de> for StyleVisitor {
+    type Value = Style;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a map of style attributes")
+    }
+
+    fn visit_map<M: MapAccess<'de>>(self, mut access: M) -> Result<Style, M::Error> {
+        let mut declarations: Vec<(String, String)> = vec![];
+
+        while let Some((name, value)) = access.next_entry::<String, DeclarationValue>()? {
+            declarations.push((name, value.0));
+        }
+
+        Style::from_declarations(declarations.iter().map(|(name, value)| {
+            AttributeName::parse(name)
+                .map(|name| (name, &value[..]))
+                .map_err(|_| StyleParseError::UnknownAttribute(name.to_string()))
+        }))
+        .map_err(de::Error::custom)
+    }
+}
+
+/// The value of a serialized style attribute, which is a string or, for
+/// boolean attributes, a boolean.
+struct DeclarationValue(String);
+
+impl<'de> Deserialize<'de> for DeclarationValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<DeclarationValue, D::Error> {
+        struct ValueVisitor;
+
+        impl<'de> Visitor<'de> for ValueVisitor {
+            type Value = DeclarationValue;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a string or a boolean")
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<DeclarationValue, E> {
+                Ok(DeclarationValue(value.to_string()))
+            }
+
+            fn visit_bool<E: de::Error>(self, value: bool) -> Result<DeclarationValue, E> {
+                Ok(DeclarationValue(value.to_string()))
+            }
+        }
+
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
+
+/// Parse a declared value, which may be followed by `!important`.
+fn parse_attribute<Value: AttributeValue>(
+    name: AttributeName,
+    value: &str,
+) -> Result<Attribute<Value>, StyleParseError> {
+    match value.strip_suffix("!important") {
+        Some(value) => Ok(Attribute(name, parse_value(name, value.trim_end())?).important()),
+        None => Ok(Attribute(name, parse_value(name, value)?)),
+    }
+}
+
+fn parse_value<Value: AttributeValue>(
+    name: AttributeName,
+    value: &str,
+) -> Result<Value, StyleParseError> {
+    Value::parse(value).ok_or_else(|| StyleParseError::InvalidValue {
+        name,
+        value: value.to_string(),
+    })
+}
+
+/// An error from `Style::try_from_stylesheet`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StyleParseError {
+    /// A declaration without a `:` between the attribute name and its value.
+    MissingColon(String),
+    /// An attribute name other than `fg`, `bg`, `weight`, `underline`,
+    /// `italic`, `strikethrough` or `underline-color`.
+    UnknownAttribute(String),
+    /// A value that isn't valid for its attribute, like `weight: heavy`.
+    InvalidValue { name: AttributeName, value: String },
+}
+
+impl fmt::Display for StyleParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StyleParseError::MissingColon(declaration) => write!(
+                f,
+                "Unexpected style string, missing `:` in `{}`",
+                declaration
+            ),
+            StyleParseError::UnknownAttribute(name) => {
+                write!(f, "Invalid style attribute name {}", name)
+            }
+            StyleParseError::InvalidValue { name, value } => {
+                write!(f, "Unexpected value for `{}`: {}", name, value)
+            }
+        }
+    }
+}
+
+impl std::error::Error for StyleParseError {}
+
 struct StyleString<'a> {
     rest: &'a str,
 }
@@ -613,29 +1050,345 @@ impl<'a> StyleString<'a> {
 }
 
 impl<'a> Iterator for StyleString<'a> {
-    type Item = (AttributeName, &'a str);
+    type Item = Result<(AttributeName, &'a str), StyleParseError>;
 
-    fn next(&mut self) -> Option<(AttributeName, &'a str)> {
-        if self.rest.len() == 0 {
-            return None;
-        }
+    fn next(&mut self) -> Option<Self::Item> {
+        // Skip empty declarations, like the ones in `fg: red; ; weight: bold;`
+        let declaration = loop {
+            if self.rest.is_empty() {
+                return None;
+            }
 
-        let name = if let Some(next) = self.rest.find(':') {
-            let next_part = &self.rest[..next];
-            self.rest = &self.rest[(next + 1)..];
-            next_part.trim()
-        } else {
-            panic!("Unexpected style string, missing `:`")
+            let (declaration, rest) = match self.rest.find(';') {
+                Some(next) => (&self.rest[..next], &self.rest[(next + 1)..]),
+                None => (self.rest, ""),
+            };
+
+            self.rest = rest;
+
+            let declaration = declaration.trim();
+
+            if !declaration.is_empty() {
+                break declaration;
+            }
         };
 
-        if let Some(next) = self.rest.find(';') {
-            let next_part = self.rest[..next].trim();
-            self.rest = &self.rest[(next + 1)..];
-            Some((AttributeName::from(name), next_part))
-        } else {
-            let next_part = self.rest.trim();
-            self.rest = "";
-            Some((AttributeName::from(name), next_part))
+        let (name, value) = match declaration.find(':') {
+            Some(colon) => (
+                declaration[..colon].trim(),
+                declaration[(colon + 1)..].trim(),
+            ),
+            None => {
+                self.rest = "";
+                let declaration = declaration.to_string();
+                return Some(Err(StyleParseError::MissingColon(declaration)));
+            }
+        };
+
+        match AttributeName::parse(name) {
+            Ok(name) => Some(Ok((name, value))),
+            Err(_) => {
+                self.rest = "";
+                Some(Err(StyleParseError::UnknownAttribute(name.to_string())))
+            }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_try_from_stylesheet() {
+        assert_eq!(
+            Style::try_from_stylesheet("fg: red; weight: bold;"),
+            Ok(Style::new().fg(Color::Red).bold())
+        );
+    }
+
+    #[test]
+    fn test_italic() {
+        let style = Style::try_from_stylesheet("italic: true").unwrap();
+
+        assert_eq!(style, Style::new().italic());
+        assert_eq!(style.to_string(), "Style {italic=true}");
+        assert_eq!(style.debug_attributes(), vec![(AttributeName::Italic, None)]);
+        assert!(!style.is_default());
+
+        assert_eq!(
+            Style::try_from_stylesheet("italic: false"),
+            Ok(Style::new().noitalic())
+        );
+        assert_eq!(
+            Style::new().italic().union(Style::new().noitalic()),
+            Style::new().noitalic()
+        );
+        assert_eq!(
+            Style::new().italic().union(Style::new().fg(Color::Red)),
+            Style::new().fg(Color::Red).italic()
+        );
+    }
+
+    #[test]
+    fn test_strikethrough() {
+        let style = Style::try_from_stylesheet("strikethrough: true").unwrap();
+
+        assert_eq!(style, Style::new().strikethrough());
+        assert_eq!(style.to_string(), "Style {strikethrough=true}");
+        assert!(style.is_strikethrough());
+        assert!(!Style::new().nostrikethrough().is_strikethrough());
+        assert_eq!(style.to_color_spec(), ColorSpec::new());
+    }
+
+    #[test]
+    fn test_bright_colors() {
+        let style = Style::try_from_stylesheet("fg: bright-green; weight: dim").unwrap();
+
+        assert_eq!(style, Style::new().fg(Color::BrightGreen).dim());
+
+        let mut spec = ColorSpec::new();
+        spec.set_fg(Some(termcolor::Color::Green)).set_intense(true);
+
+        assert_eq!(style.to_color_spec(), spec);
+        assert_eq!(Style::new().fg(Color::BrightGreen).to_color_spec(), spec);
+        assert!(!Style::new().fg(Color::Green).to_color_spec().intense());
+    }
+
+    #[test]
+    fn test_extended_colors() {
+        let style = Style::try_from_stylesheet("fg: #ff8000; bg: ansi(214)").unwrap();
+
+        assert_eq!(
+            style,
+            Style::new().fg(Color::Rgb(255, 128, 0)).bg(Color::Ansi256(214))
+        );
+
+        let mut spec = ColorSpec::new();
+        spec.set_fg(Some(termcolor::Color::Rgb(255, 128, 0)))
+            .set_bg(Some(termcolor::Color::Ansi256(214)));
+
+        assert_eq!(style.to_color_spec(), spec);
+        assert_eq!(Style::from_color_spec(spec.clone()).to_color_spec(), spec);
+    }
+
+    #[test]
+    fn test_underline_color() {
+        let style = Style::try_from_stylesheet("underline: true; underline-color: red").unwrap();
+
+        assert_eq!(style, Style::new().underline().underline_color(Color::Red));
+        assert_eq!(style.get_underline_color(), Some(Color::Red));
+        assert_eq!(style.to_string(), "Style {underline=true underline-color=red}");
+        assert_eq!(
+            Style::new()
+                .underline_color(Color::Red)
+                .union(Style::new().underline_color(Color::Blue))
+                .get_underline_color(),
+            Some(Color::Blue)
+        );
+        assert_eq!(
+            Style::new()
+                .underline_color(Color::Red)
+                .union(Style::new().bold())
+                .get_underline_color(),
+            Some(Color::Red)
+        );
+        assert_eq!(Style::new().get_underline_color(), None);
+    }
+
+    #[test]
+    fn test_empty_declarations() {
+        let style = Style::new().fg(Color::Red).bold();
+
+        assert_eq!(
+            Style::try_from_stylesheet("fg: red; ; weight: bold"),
+            Ok(style.clone())
+        );
+        assert_eq!(
+            Style::try_from_stylesheet(";fg:red;;weight:bold;;"),
+            Ok(style.clone())
+        );
+        assert_eq!(
+            Style::try_from_stylesheet("  fg :  red ;\n weight:\tbold  "),
+            Ok(style)
+        );
+    }
+
+    #[test]
+    fn test_empty_stylesheet() {
+        assert_eq!(Style::try_from_stylesheet(""), Ok(Style::new()));
+        assert_eq!(Style::try_from_stylesheet("  "), Ok(Style::new()));
+        assert_eq!(Style::try_from_stylesheet(" ; ; "), Ok(Style::new()));
+    }
+
+    #[test]
+    fn test_trailing_semicolon() {
+        assert_eq!(
+            Style::try_from_stylesheet("fg: red;"),
+            Ok(Style::new().fg(Color::Red))
+        );
+    }
+
+    #[test]
+    fn test_missing_value() {
+        assert_eq!(
+            Style::try_from_stylesheet("weight: bold; fg:"),
+            Err(StyleParseError::InvalidValue {
+                name: AttributeName::Fg,
+                value: "".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_important() {
+        let style = Style::try_from_stylesheet("fg: red !important; underline: true  !important")
+            .unwrap();
+
+        assert!(style.fg.important);
+        assert!(style.underline.important);
+        assert!(!style.weight.important);
+        assert_eq!(style.to_declarations(), "fg: red !important; underline: true !important");
+
+        let plain = Style::new().fg(Color::Blue).nounderline();
+        assert_eq!(style.clone().union(plain), style);
+
+        assert_eq!(
+            Style::try_from_stylesheet("fg: !important"),
+            Err(StyleParseError::InvalidValue {
+                name: AttributeName::Fg,
+                value: "".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_to_css() {
+        assert_eq!(Style::new().to_css(), "");
+        assert_eq!(
+            Style("fg: red; bg: ansi(214); weight: bold; italic: true").to_css(),
+            "color:red;background-color:#ffaf00;font-weight:bold;font-style:italic"
+        );
+        assert_eq!(
+            Style("underline: true; strikethrough: true; underline-color: blue").to_css(),
+            "text-decoration-line:underline line-through;text-decoration-color:blue"
+        );
+        assert_eq!(
+            Style("fg: reset; underline: false").to_css(),
+            "color:initial;text-decoration-line:none"
+        );
+    }
+
+    #[test]
+    fn test_unknown_attribute_name() {
+        assert_eq!(
+            AttributeName::parse("colour"),
+            Err("Invalid style attribute name colour".to_string())
+        );
+        assert_eq!(
+            Style::try_from_stylesheet("weight: bold; colour: red"),
+            Err(StyleParseError::UnknownAttribute("colour".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_missing_colon() {
+        assert_eq!(
+            Style::try_from_stylesheet("fg"),
+            Err(StyleParseError::MissingColon("fg".to_string()))
+        );
+        assert_eq!(
+            Style::try_from_stylesheet("fg: red; weight bold"),
+            Err(StyleParseError::MissingColon("weight bold".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_invalid_value() {
+        let err = Style::try_from_stylesheet("weight: heavy").unwrap_err();
+
+        assert_eq!(
+            err,
+            StyleParseError::InvalidValue {
+                name: AttributeName::Weight,
+                value: "heavy".to_string()
+            }
+        );
+        assert_eq!(err.to_string(), "Unexpected value for `weight`: heavy");
+    }
+
+    #[test]
+    fn test_serde_round_trips_a_full_style() {
+        let style = Style::new()
+            .fg(Color::Red)
+            .bg(Color::Ansi256(236))
+            .bold()
+            .underline()
+            .noitalic()
+            .strikethrough()
+            .underline_color(Color::Rgb(255, 128, 0));
+
+        let json = serde_json::to_string(&style).unwrap();
+
+        assert_eq!(
+            json,
+            concat!(
+                r##"{"fg":"red","bg":"ansi(236)","weight":"bold","underline":true,"##,
+                r##""italic":false,"strikethrough":true,"underline-color":"#ff8000"}"##
+            )
+        );
+        assert_eq!(serde_json::from_str::<Style>(&json).unwrap(), style);
+    }
+
+    #[test]
+    fn test_serde_round_trips_a_default_style() {
+        let json = serde_json::to_string(&Style::new()).unwrap();
+
+        assert_eq!(json, "{}");
+        assert_eq!(serde_json::from_str::<Style>(&json).unwrap(), Style::new());
+    }
+
+    #[test]
+    fn test_deserialize_style() {
+        assert_eq!(
+            serde_json::from_str::<Style>(r#"{ "fg": "red", "weight": "bold", "italic": "true" }"#)
+                .unwrap(),
+            Style::from_stylesheet("fg: red; weight: bold; italic: true")
+        );
+
+        let err = serde_json::from_str::<Style>(r#"{ "weight": "heavy" }"#).unwrap_err();
+        assert!(err.to_string().contains("Unexpected value for `weight`: heavy"));
+
+        let err = serde_json::from_str::<Style>(r#"{ "colour": "red" }"#).unwrap_err();
+        assert!(err.to_string().contains("Invalid style attribute name colour"));
+    }
+
+    #[test]
+    fn test_serde_attribute_values() {
+        let weight = serde_json::to_string(&WeightAttribute::Dim).unwrap();
+        assert_eq!(weight, "\"dim\"");
+        assert_eq!(
+            serde_json::from_str::<WeightAttribute>(&weight).unwrap(),
+            WeightAttribute::Dim
+        );
+
+        let inherit = serde_json::to_string(&ColorAttribute::Inherit).unwrap();
+        assert_eq!(inherit, "\"inherit\"");
+        assert_eq!(
+            serde_json::from_str::<ColorAttribute>(&inherit).unwrap(),
+            ColorAttribute::Inherit
+        );
+
+        assert_eq!(
+            serde_json::from_str::<BooleanAttribute>("\"true\"").unwrap(),
+            BooleanAttribute::On
+        );
+        assert!(serde_json::from_str::<BooleanAttribute>("\"maybe\"").is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid style attribute name colour")]
+    fn test_from_stylesheet_panics() {
+        Style::from_stylesheet("colour: red");
+    }
+}
@@ -0,0 +1,144 @@
+use crate::simple::SimpleSpan;
+use crate::span::column_offset;
+use crate::{FileName, Location};
+use std::borrow::Cow;
+use std::fmt;
+use std::rc::Rc;
+
+/// A `ReportingFiles` implementation whose files are backed by closures,
+/// for source that is fetched lazily (from a VFS or over the network) rather
+/// than stored in a database up front.
+///
+/// The closures have the same signatures as the `ReportingFiles` methods
+/// they back. Files are identified by a `usize`, and spans are `SimpleSpan`s.
+/// The `location` closure should count columns in characters, like
+/// `Location` does.
+#[derive(Clone)]
+pub struct CallbackFiles<'a> {
+    file_name: Rc<dyn Fn(usize) -> FileName + 'a>,
+    location: Rc<dyn Fn(usize, usize) -> Option<Location> + 'a>,
+    line_span: Rc<dyn Fn(usize, usize) -> Option<SimpleSpan> + 'a>,
+    source: Rc<dyn Fn(SimpleSpan) -> Option<Cow<'a, str>> + 'a>,
+}
+
+impl<'a> CallbackFiles<'a> {
+    pub fn new(
+        file_name: impl Fn(usize) -> FileName + 'a,
+        location: impl Fn(usize, usize) -> Option<Location> + 'a,
+        line_span: impl Fn(usize, usize) -> Option<SimpleSpan> + 'a,
+        source: impl Fn(SimpleSpan) -> Option<Cow<'a, str>> + 'a,
+    ) -> CallbackFiles<'a> {
+        CallbackFiles {
+            file_name: Rc::new(file_name),
+            location: Rc::new(location),
+            line_span: Rc::new(line_span),
+            source: Rc::new(source),
+        }
+    }
+}
+
+impl<'a> fmt::Debug for CallbackFiles<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CallbackFiles").finish()
+    }
+}
+
+impl<'a> crate::ReportingFiles for CallbackFiles<'a> {
+    type Span = SimpleSpan;
+    type FileId = usize;
+
+    fn file_id(&self, span: SimpleSpan) -> usize {
+        span.file_id
+    }
+
+    fn file_name(&self, file: usize) -> FileName {
+        (self.file_name)(file)
+    }
+
+    fn byte_span(&self, file: usize, from_index: usize, to_index: usize) -> Option<SimpleSpan> {
+        if from_index <= to_index {
+            Some(SimpleSpan::new(file, from_index, to_index))
+        } else {
+            None
+        }
+    }
+
+    fn byte_index(&self, file: usize, line: usize, column: usize) -> Option<usize> {
+        let span = (self.line_span)(file, line)?;
+        let source = (self.source)(span)?;
+
+        Some(span.start + column_offset(&source, column)?)
+    }
+
+    fn location(&self, file: usize, index: usize) -> Option<Location> {
+        (self.location)(file, index)
+    }
+
+    fn line_span(&self, file: usize, line: usize) -> Option<SimpleSpan> {
+        (self.line_span)(file, line)
+    }
+
+    fn source(&self, span: SimpleSpan) -> Option<Cow<'_, str>> {
+        (self.source)(span)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{emit_to_string, DefaultConfig, Diagnostic, Label, ReportingFiles};
+    use unindent::unindent;
+
+    #[test]
+    fn test_emit_from_callbacks() {
+        let source = unindent(
+            r##"
+                (define test 123)
+                (+ test "")
+                ()
+            "##,
+        );
+
+        let lines = || {
+            source.split('\n').scan(0, |start, line| {
+                let span = (*start, *start + line.len());
+                *start = span.1 + 1;
+                Some(span)
+            })
+        };
+
+        let files = CallbackFiles::new(
+            |_| FileName::Verbatim("lazy".to_string()),
+            |_, index| {
+                let (line, (start, _)) = lines()
+                    .enumerate()
+                    .find(|(_, (start, end))| *start <= index && index <= *end)?;
+
+                Some(Location::new(line, source[start..index].chars().count()))
+            },
+            |file, line| {
+                let (start, end) = lines().nth(line)?;
+                Some(SimpleSpan::new(file, start, end))
+            },
+            |span| source.get(span.start..span.end).map(Cow::Borrowed),
+        );
+
+        let str_start = files.byte_index(0, 1, 8).unwrap();
+        let error = Diagnostic::new_error("Unexpected type in `+` application").with_label(
+            Label::new_primary(SimpleSpan::new(0, str_start, str_start + 2))
+                .with_message("Expected integer but got string"),
+        );
+
+        assert_eq!(
+            emit_to_string(&files, &error, &DefaultConfig),
+            unindent(
+                r##"
+                    error: Unexpected type in `+` application
+                    - lazy:2:9
+                    2 | (+ test "")
+                      |         ^^ Expected integer but got string
+                "##,
+            ),
+        );
+    }
+}
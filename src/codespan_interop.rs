@@ -0,0 +1,138 @@
+use crate::span::{char_column, column_offset};
+use codespan::{ByteIndex, FileId, Files, LineIndex, Span};
+use std::borrow::Cow;
+use std::fmt;
+
+/// A `ReportingFiles` implementation that borrows a `codespan::Files`, so
+/// that sources which are already stored there don't need to be copied.
+///
+/// Lines are zero-based, like codespan's `LineIndex`, and columns count
+/// characters, like codespan's `ColumnIndex`.
+pub struct CodespanFiles<'files, Source> {
+    files: &'files Files<Source>,
+}
+
+impl<'files, Source> CodespanFiles<'files, Source> {
+    pub fn new(files: &'files Files<Source>) -> CodespanFiles<'files, Source> {
+        CodespanFiles { files }
+    }
+}
+
+impl<'files, Source> Clone for CodespanFiles<'files, Source> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'files, Source> Copy for CodespanFiles<'files, Source> {}
+
+impl<'files, Source: fmt::Debug> fmt::Debug for CodespanFiles<'files, Source> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("CodespanFiles").field(self.files).finish()
+    }
+}
+
+/// A `codespan::Span` in a particular file. Codespan's spans don't know
+/// which file they're in, but `ReportingFiles::file_id` needs to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct CodespanSpan {
+    pub file: FileId,
+    pub span: Span,
+}
+
+impl CodespanSpan {
+    pub fn new(file: FileId, span: impl Into<Span>) -> CodespanSpan {
+        CodespanSpan {
+            file,
+            span: span.into(),
+        }
+    }
+}
+
+impl crate::ReportingSpan for CodespanSpan {
+    fn with_start(&self, start: usize) -> Self {
+        CodespanSpan::new(self.file, Span::new(byte_index(start), self.span.end()))
+    }
+
+    fn with_end(&self, end: usize) -> Self {
+        CodespanSpan::new(self.file, Span::new(self.span.start(), byte_index(end)))
+    }
+
+    fn start(&self) -> usize {
+        self.span.start().to_usize()
+    }
+
+    fn end(&self) -> usize {
+        self.span.end().to_usize()
+    }
+}
+
+fn byte_index(index: usize) -> ByteIndex {
+    ByteIndex::from(index as u32)
+}
+
+fn line_index(line: usize) -> LineIndex {
+    LineIndex::from(line as u32)
+}
+
+impl<'files, Source> crate::ReportingFiles for CodespanFiles<'files, Source>
+where
+    Source: AsRef<str> + fmt::Debug,
+{
+    type Span = CodespanSpan;
+    type FileId = FileId;
+
+    fn byte_span(&self, file: FileId, from_index: usize, to_index: usize) -> Option<CodespanSpan> {
+        let len = self.files.source(file).as_ref().len();
+
+        if from_index <= to_index && to_index <= len {
+            Some(CodespanSpan::new(file, from_index as u32..to_index as u32))
+        } else {
+            None
+        }
+    }
+
+    fn file_id(&self, span: CodespanSpan) -> FileId {
+        span.file
+    }
+
+    fn file_name(&self, file: FileId) -> crate::FileName {
+        crate::FileName::Verbatim(self.files.name(file).to_string_lossy().into_owned())
+    }
+
+    fn byte_index(&self, file: FileId, line: usize, column: usize) -> Option<usize> {
+        let span = self.files.line_span(file, line_index(line)).ok()?;
+        let source = self.files.source_slice(file, span).ok()?;
+        let source = source.trim_end_matches(['\r', '\n']);
+
+        Some(span.start().to_usize() + column_offset(source, column)?)
+    }
+
+    fn location(&self, file: FileId, index: usize) -> Option<crate::Location> {
+        if index > self.files.source(file).as_ref().len() {
+            return None;
+        }
+
+        let line = self.files.line_index(file, byte_index(index));
+        let start = self.files.line_span(file, line).ok()?.start().to_usize();
+        let source = &self.files.source(file).as_ref()[start..];
+
+        Some(crate::Location::new(
+            line.to_usize(),
+            char_column(source, index - start),
+        ))
+    }
+
+    fn line_span(&self, file: FileId, line: usize) -> Option<CodespanSpan> {
+        let span = self.files.line_span(file, line_index(line)).ok()?;
+
+        Some(CodespanSpan::new(file, span))
+    }
+
+    fn source(&self, span: CodespanSpan) -> Option<Cow<'_, str>> {
+        self.files
+            .source_slice(span.file, span.span)
+            .ok()
+            .map(Cow::Borrowed)
+    }
+}
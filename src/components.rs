@@ -7,65 +7,350 @@ use crate::ReportingFiles;
 use crate::{models, Location};
 
 pub(crate) fn Diagnostic<'args>(data: DiagnosticData<'args, impl ReportingFiles>, into: Document) -> Document {
-    let header = models::Header::new(&data.diagnostic);
+    let header = models::Header::new(&data.diagnostic, data.config);
+    let labelled_lines = labelled_lines(&data);
+    let mut notes = models::label_notes(data.files, &data.diagnostic.labels, data.config);
+
+    notes.extend(
+        data.diagnostic
+            .notes
+            .iter()
+            .map(|note| models::NoteLine::new(note, data.config)),
+    );
+
+    let has_rtl = labelled_lines
+        .iter()
+        .any(|labelled_line| labelled_line.source_line().has_rtl());
+
+    if has_rtl && !data.config.assume_ltr() {
+        notes.push(models::NoteLine::right_to_left(data.config));
+    }
+
+    let gutter_width = gutter_width(&labelled_lines);
+    let suggestions: Vec<_> = data
+        .diagnostic
+        .suggestions
+        .iter()
+        .map(|suggestion| models::SuggestionLine::new(data.files, suggestion, data.config))
+        .filter(|suggestion| suggestion.resolves())
+        .collect();
 
     into.add(tree! {
         <Section name={severity(&data.diagnostic)} as {
             <Header args={header}>
             <Body args={data}>
+            <Notes args={(notes, gutter_width)}>
+
+            <Each items={suggestions} as |suggestion| {
+                <Suggestion args={(suggestion, gutter_width)}>
+            }>
         }>
     })
 }
 
 pub(crate) fn Header<'args>(header: models::Header<'args>, into: Document) -> Document {
+    let prefix_width = header.prefix_width();
+    let mut lines = header.message_lines().into_iter();
+    let first_line = lines.next().unwrap_or_default();
+
     into.add(tree! {
         <Section name="header" as {
             <Line as {
                 <Section name="primary" as {
                     // error
-                    {header.severity()}
+                    {header.severity_name()}
                     // [E0001]
-                    {IfSome(header.code(), |code| tree! { "[" {code} "]" })}
+                    {IfSome(&header.code(), |code| tree! { {code} })}
                 }>
-                ": "
+                <Section name="separator" as { ": " }>
                 // Unexpected type in `+` application
-                {header.message()}
+                {first_line}
+            }>
+
+            // Wrapped lines of the message are indented to line up with the
+            // first line
+            <Each items={lines} as |line| {
+                <Line as {
+                    {repeat(" ", prefix_width)}
+                    {line}
+                }>
+            }>
+        }>
+    })
+}
+
+/// The notes below the source code, with the `=` lined up under the gutter's
+/// `|`:
+///
+/// ```text
+/// 2 | (+ test "")
+///   |         ^^ Expected integer but got string
+///   = help: convert the string with `parse`
+/// ```
+pub(crate) fn Notes<'args>(
+    (notes, gutter_width): (Vec<models::NoteLine<'args>>, usize),
+    into: Document,
+) -> Document {
+    into.add(tree! {
+        <Each items={notes} as |note| {
+            <Line as {
+                <Section name="gutter" as {
+                    {repeat(" ", gutter_width + 1)} "="
+                }>
+                " "
+                <Section name="note-prefix" as { {note.prefix()} }>
+                {note.message()}
+            }>
+        }>
+    })
+}
+
+/// A suggested edit, shown as the line would read after the edit, with the
+/// edited text marked:
+///
+/// ```text
+/// help: use an integer
+/// - test:2:9
+/// 2 | (+ test 0)
+///   |         ~
+/// ```
+///
+/// The gutter is as wide as the rest of the diagnostic's, so that the `|`s
+/// line up.
+pub(crate) fn Suggestion<'args>(
+    (model, gutter_width): (models::SuggestionLine<'args, impl ReportingFiles>, usize),
+    into: Document,
+) -> Document {
+    let source_line = model.source_line();
+    let gutter_width = gutter_width.max(source_line.line_number_len());
+
+    if model.rustc_style() {
+        return RustcSuggestion((model, gutter_width), into);
+    }
+
+    let Location { line, column } = source_line.display_location();
+    let filename = source_line.filename();
+    let separator = source_line.gutter_separator();
+
+    into.add(tree! {
+        <Section name="suggestion" as {
+            <Line as {
+                <Section name="note-prefix" as { {model.prefix()} }>
+                {model.message()}
+            }>
+
+            <Section name="source-code-location" as {
+                <Line as {
+                    {source_line.location_prefix()} {filename} ":" {line} ":" {column}
+                }>
+            }>
+
+            <Line as {
+                <Section name="gutter" as {
+                    {source_line.line_number()}
+                    {repeat(" ", gutter_width - source_line.line_number_len())}
+                    {&separator}
+                }>
+
+                {model.before()}
+                <Section name={model.style()} as { {model.edited()} }>
+                {model.after()}
+            }>
+
+            <Line as {
+                <Section name="gutter" as {
+                    {repeat(" ", gutter_width)} {&separator}
+                }>
+
+                {repeat(" ", model.before_width())}
+                <Section name={model.style()} as {
+                    {repeat(model.mark(), model.underline_width())}
+                }>
+            }>
+        }>
+    })
+}
+
+/// A suggested edit in the rustc-style layout, with the same location line
+/// and gutters as the snippets in `RustcBody`:
+///
+/// ```text
+/// help: use an integer
+///  --> test:2:9
+///   |
+/// 2 | (+ test 0)
+///   |         ~
+///   |
+/// ```
+pub(crate) fn RustcSuggestion<'args>(
+    (model, gutter_width): (models::SuggestionLine<'args, impl ReportingFiles>, usize),
+    into: Document,
+) -> Document {
+    let source_line = model.source_line();
+    let separator = source_line.gutter_separator();
+
+    let leading_gutter = if model.leading_gutter_line() {
+        Document::with(RustcBlankGutter((gutter_width, &separator), Document::empty()))
+    } else {
+        Document::empty()
+    };
+
+    into.add(tree! {
+        <Section name="suggestion" as {
+            <Line as {
+                <Section name="note-prefix" as { {model.prefix()} }>
+                {model.message()}
+            }>
+
+            <RustcSourceCodeLocation args={(source_line.clone(), gutter_width)}>
+            {leading_gutter}
+
+            <Line as {
+                <RustcGutter args={(
+                    Some(source_line.line_number()),
+                    gutter_width,
+                    separator.clone(),
+                )}>
+
+                {model.before()}
+                <Section name={model.style()} as { {model.edited()} }>
+                {model.after()}
+            }>
+
+            <Line as {
+                <RustcGutter args={(None, gutter_width, separator.clone())}>
+
+                {repeat(" ", model.before_width())}
+                <Section name={model.style()} as {
+                    {repeat(model.mark(), model.underline_width())}
+                }>
             }>
+
+            <RustcBlankGutter args={(gutter_width, &separator)}>
         }>
     })
 }
 
 pub(crate) fn Body<'args>(data: DiagnosticData<'args, impl ReportingFiles>, mut into: Document) -> Document {
-    for label in &data.diagnostic.labels {
-        let source_line = models::SourceLine::new(data.files, label, data.config);
-        let labelled_line = models::LabelledLine::new(source_line.clone(), label);
+    if data.config.rustc_style() {
+        return RustcBody(data, into);
+    }
+
+    let labelled_lines = labelled_lines(&data);
+    let primary_file = labelled_lines
+        .iter()
+        .find(|labelled_line| labelled_line.is_primary())
+        .map(|labelled_line| labelled_line.source_line().filename());
+
+    // The file of the cross-file note that's currently open
+    let mut note_file: Option<String> = None;
+
+    for labelled_line in labelled_lines {
+        let filename = labelled_line.source_line().filename();
+        let cross_file = match &primary_file {
+            Some(primary_file) => !labelled_line.is_primary() && *primary_file != filename,
+            None => false,
+        };
+
+        if !cross_file {
+            into = into.add(tree! {
+                // - <test>:2:9
+                <SourceCodeLocation args={(labelled_line.source_line().clone(), 0)}>
+
+                // 2 | (+ test "")
+                //   |         ^^
+                <SourceCodeLine args={labelled_line}>
+            });
+
+            continue;
+        }
+
+        // Only the first label of each file opens a new note
+        let note_prefix = if note_file.as_ref() != Some(&filename) {
+            Some(data.config.note_prefix().trim_end().to_string())
+        } else {
+            None
+        };
+        let labelled_line = labelled_line.with_indent(CROSS_FILE_INDENT);
 
         into = into.add(tree! {
-            // - <test>:2:9
-            <SourceCodeLocation args={source_line}>
+            <Section name="cross-file-note" as {
+                // note:
+                {IfSome(&note_prefix, |prefix| tree! {
+                    <Line as {
+                        <Section name="note-prefix" as { {prefix} }>
+                    }>
+                })}
 
-            // 2 | (+ test "")
-            //   |         ^^
-            <SourceCodeLine args={labelled_line}>
+                //   - <other>:3:1
+                <SourceCodeLocation args={(labelled_line.source_line().clone(), CROSS_FILE_INDENT)}>
+
+                //   3 | trait Foo {}
+                //     |       --- defined here
+                <SourceCodeLine args={labelled_line}>
+            }>
         });
+
+        note_file = Some(filename);
     }
 
     into
 }
 
+/// How far secondary labels in another file than the primary label are
+/// indented under their `note:`.
+const CROSS_FILE_INDENT: usize = 2;
+
+/// The diagnostic's labels in the order they're shown, each knowing which
+/// lines its neighbors in the same file show.
+fn labelled_lines<'args, Files: ReportingFiles>(
+    data: &DiagnosticData<'args, Files>,
+) -> Vec<models::LabelledLine<'args, Files>> {
+    let labels = models::shown_labels(data.files, &data.diagnostic.labels, data.config);
+    let source_lines: Vec<_> = labels
+        .iter()
+        .map(|label| models::SourceLine::new(data.files, label, data.config))
+        .collect();
+
+    // The file and last line shown by the previous label
+    let mut previous: Option<(String, usize)> = None;
+    let mut labelled_lines = vec![];
+
+    for (i, label) in labels.into_iter().enumerate() {
+        let source_line = source_lines[i].clone();
+        let filename = source_line.filename();
+
+        let previous_line = match &previous {
+            Some((file, line)) if *file == filename => Some(*line),
+            _ => None,
+        };
+        let next_line = source_lines
+            .get(i + 1)
+            .filter(|next| next.filename() == filename)
+            .map(|next| next.location().line);
+
+        let labelled_line =
+            models::LabelledLine::new(source_line, label).with_neighbors(previous_line, next_line);
+
+        previous = Some((filename, labelled_line.last_line()));
+        labelled_lines.push(labelled_line);
+    }
+
+    labelled_lines
+}
+
 pub(crate) fn SourceCodeLocation(
-    source_line: models::SourceLine<impl ReportingFiles>,
+    (source_line, indent): (models::SourceLine<impl ReportingFiles>, usize),
     into: Document,
 ) -> Document {
-    let Location { line, column } = source_line.location();
+    let position = source_line.display_position();
     let filename = source_line.filename().to_string();
 
     into.add(tree! {
         <Section name="source-code-location" as {
             <Line as {
                 // - <test>:3:9
-                "- " {filename} ":" {line + 1}
-                ":" {column}
+                {repeat(" ", indent)} {source_line.location_prefix()} {filename} ":" {position}
             }>
         }>
     })
@@ -73,44 +358,367 @@ pub(crate) fn SourceCodeLocation(
 
 pub(crate) fn SourceCodeLine<'args>(
     model: models::LabelledLine<'args, impl ReportingFiles>,
-    into: Document,
+    mut into: Document,
 ) -> Document {
     let source_line = model.source_line();
+    let visible = source_line.visible();
+    let gutter_width = model.gutter_width();
+
+    into = into.add(tree! {
+        // 1 | (define test 123)
+        <Each items={model.context_before()} as |context| {
+            <ContextLine args={(context, gutter_width, model.indent())}>
+        }>
 
-    into.add(tree! {
         <Line as {
+            {repeat(" ", model.indent())}
+
             <Section name="gutter" as {
                 {source_line.line_number()}
-                " | "
+                {repeat(" ", gutter_width - source_line.line_number_len())}
+                {source_line.gutter_separator()}
             }>
 
             <Section name="before-marked" as {
-                {source_line.before_marked()}
+                {visible.before()}
             }>
 
             <Section name={model.style()} as {
-                {model.source_line().marked()}
+                {visible.marked()}
             }>
 
             <Section name="after-marked" as {
-                {source_line.after_marked()}
+                {visible.after()}
             }>
+
+            {IfSome(model.inline_message(), |message| tree! {
+                <Section name={model.style()} as { " " {message} }>
+            })}
+        }>
+    });
+
+    if model.show_underline() {
+        into = into.add(tree! {
+            <Underline args={model.clone()}>
+        });
+    }
+
+    into.add(tree! {
+        // 3 | ()
+        <Each items={model.context_after()} as |context| {
+            <ContextLine args={(context, gutter_width, model.indent())}>
         }>
+    })
+}
 
+pub(crate) fn Underline<'args>(
+    model: models::LabelledLine<'args, impl ReportingFiles>,
+    into: Document,
+) -> Document {
+    let visible = model.source_line().visible();
+    let separator = model.source_line().gutter_separator();
+
+    // Wrapped lines of the message are indented to line up with the first
+    // line
+    let message_indent = visible.before_width() + visible.underline_width() + 1;
+    let gutter_width = model.gutter_width() + models::display_width(&separator);
+    let mut lines = model
+        .message_lines(model.indent() + gutter_width + message_indent)
+        .into_iter();
+    let first_line = lines.next();
+
+    into.add(tree! {
+        //   |         ^^ Expected integer but got string
         <Line as {
+            {repeat(" ", model.indent())}
+
             <Section name="underline" as {
                 <Section name="gutter" as {
-                    {repeat(" ", model.source_line().line_number_len())}
-                    " | "
+                    {repeat(" ", model.gutter_width())}
+                    {&separator}
                 }>
 
-                {repeat(" ", model.source_line().before_marked().len())}
+                {repeat(" ", visible.before_width())}
 
                 <Section name={model.style()} as {
-                    {repeat(model.mark(), model.source_line().marked().len())}
-                    {IfSome(model.message(), |message| tree!({" "} {message}))}
+                    {model.underline(visible.underline_width())}
+                    {IfSome(&first_line, |message| tree!({" "} {message}))}
                 }>
             }>
         }>
+
+        //   |            but got string
+        <Each items={lines} as |line| {
+            <Line as {
+                {repeat(" ", model.indent())}
+
+                <Section name="underline" as {
+                    <Section name="gutter" as {
+                        {repeat(" ", model.gutter_width())}
+                        {&separator}
+                    }>
+
+                    {repeat(" ", message_indent)}
+                    <Section name={model.style()} as { {line} }>
+                }>
+            }>
+        }>
+    })
+}
+
+pub(crate) fn ContextLine<'args>(
+    (context, gutter_width, indent): (models::ContextLine<'args>, usize, usize),
+    into: Document,
+) -> Document {
+    let line_number_len = context.line_number().to_string().len();
+
+    into.add(tree! {
+        <Section name="context" as {
+            <Line as {
+                {repeat(" ", indent)}
+
+                <Section name="gutter" as {
+                    {context.line_number()}
+                    {repeat(" ", gutter_width - line_number_len)}
+                    {context.gutter_separator()}
+                }>
+
+                {context.source()}
+            }>
+        }>
+    })
+}
+
+/// The body of a diagnostic in the rustc-style layout, selected with
+/// `Config::rustc_style`:
+///
+/// ```text
+///   --> test:9:2
+///    |
+///  9 | (line 9)
+///    |  ^^^^
+///    |
+/// ```
+///
+/// Line numbers are right-aligned to the widest line number shown by any of
+/// the diagnostic's labels, so that the gutters of all the snippets line up.
+/// The blank gutter line after the location line can be turned off with
+/// `Config::leading_gutter_line`.
+pub(crate) fn RustcBody<'args>(data: DiagnosticData<'args, impl ReportingFiles>, mut into: Document) -> Document {
+    let labelled_lines = labelled_lines(&data);
+    let gutter_width = gutter_width(&labelled_lines);
+    let separator = data.config.gutter_separator();
+
+    for labelled_line in labelled_lines {
+        into = into.add(tree! {
+            //  --> <test>:2:9
+            <RustcSourceCodeLocation args={(labelled_line.source_line().clone(), gutter_width)}>
+        });
+
+        if data.config.leading_gutter_line() {
+            into = into.add(tree! {
+                <RustcBlankGutter args={(gutter_width, &separator)}>
+            });
+        }
+
+        into = into.add(tree! {
+            // 2 | (+ test "")
+            //   |         ^^
+            <RustcSourceCodeLine args={(labelled_line, gutter_width)}>
+            <RustcBlankGutter args={(gutter_width, &separator)}>
+        });
+    }
+
+    into
+}
+
+/// The width of the widest line number shown by any of the labels.
+fn gutter_width(labelled_lines: &[models::LabelledLine<impl ReportingFiles>]) -> usize {
+    labelled_lines
+        .iter()
+        .map(|labelled_line| labelled_line.gutter_width())
+        .max()
+        .unwrap_or(0)
+}
+
+pub(crate) fn RustcSourceCodeLocation(
+    (source_line, gutter_width): (models::SourceLine<impl ReportingFiles>, usize),
+    into: Document,
+) -> Document {
+    let position = source_line.display_position();
+    let filename = source_line.filename().to_string();
+
+    into.add(tree! {
+        <Section name="source-code-location" as {
+            <Line as {
+                //  --> <test>:3:9
+                {repeat(" ", gutter_width)} "--> " {filename} ":" {position}
+            }>
+        }>
+    })
+}
+
+pub(crate) fn RustcBlankGutter(
+    (gutter_width, separator): (usize, &str),
+    into: Document,
+) -> Document {
+    into.add(tree! {
+        <Line as {
+            <Section name="gutter" as {
+                {repeat(" ", gutter_width)} {separator.trim_end()}
+            }>
+        }>
+    })
+}
+
+pub(crate) fn RustcGutter(
+    (line_number, gutter_width, separator): (Option<usize>, usize, String),
+    into: Document,
+) -> Document {
+    let line_number = match line_number {
+        Some(line_number) => line_number.to_string(),
+        None => String::new(),
+    };
+
+    into.add(tree! {
+        <Section name="gutter" as {
+            {repeat(" ", gutter_width - line_number.len())}
+            {line_number}
+            {separator}
+        }>
     })
 }
+
+pub(crate) fn RustcSourceCodeLine<'args>(
+    (model, gutter_width): (models::LabelledLine<'args, impl ReportingFiles>, usize),
+    mut into: Document,
+) -> Document {
+    let source_line = model.source_line();
+    let visible = source_line.visible();
+
+    into = into.add(tree! {
+        // 1 | (define test 123)
+        <Each items={model.context_before()} as |context| {
+            <RustcContextLine args={(context, gutter_width)}>
+        }>
+
+        <Line as {
+            <RustcGutter args={(
+                Some(source_line.line_number()),
+                gutter_width,
+                source_line.gutter_separator(),
+            )}>
+
+            <Section name="before-marked" as {
+                {visible.before()}
+            }>
+
+            <Section name={model.style()} as {
+                {visible.marked()}
+            }>
+
+            <Section name="after-marked" as {
+                {visible.after()}
+            }>
+
+            {IfSome(model.inline_message(), |message| tree! {
+                <Section name={model.style()} as { " " {message} }>
+            })}
+        }>
+    });
+
+    if model.show_underline() {
+        let message_indent = visible.before_width() + visible.underline_width() + 1;
+        let separator = source_line.gutter_separator();
+        let mut lines = model
+            .message_lines(gutter_width + models::display_width(&separator) + message_indent)
+            .into_iter();
+        let first_line = lines.next();
+
+        into = into.add(tree! {
+            //   |         ^^ Expected integer but got string
+            <Line as {
+                <Section name="underline" as {
+                    <RustcGutter args={(None, gutter_width, separator.clone())}>
+
+                    {repeat(" ", visible.before_width())}
+
+                    <Section name={model.style()} as {
+                        {model.underline(visible.underline_width())}
+                        {IfSome(&first_line, |message| tree!({" "} {message}))}
+                    }>
+                }>
+            }>
+
+            //   |            but got string
+            <Each items={lines} as |line| {
+                <Line as {
+                    <Section name="underline" as {
+                        <RustcGutter args={(None, gutter_width, separator.clone())}>
+
+                        {repeat(" ", message_indent)}
+                        <Section name={model.style()} as { {line} }>
+                    }>
+                }>
+            }>
+        });
+    }
+
+    into.add(tree! {
+        // 3 | ()
+        <Each items={model.context_after()} as |context| {
+            <RustcContextLine args={(context, gutter_width)}>
+        }>
+    })
+}
+
+pub(crate) fn RustcContextLine<'args>(
+    (context, gutter_width): (models::ContextLine<'args>, usize),
+    into: Document,
+) -> Document {
+    into.add(tree! {
+        <Section name="context" as {
+            <Line as {
+                <RustcGutter args={(
+                    Some(context.line_number()),
+                    gutter_width,
+                    context.gutter_separator(),
+                )}>
+
+                {context.source()}
+            }>
+        }>
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::diagnostic::Diagnostic;
+    use crate::models;
+    use crate::render_tree::prelude::*;
+    use crate::simple::SimpleSpan;
+    use render_tree::stylesheet::ColorAccumulator;
+    use render_tree::Stylesheet;
+
+    #[test]
+    fn test_styled_separator() -> std::io::Result<()> {
+        let diagnostic: Diagnostic<SimpleSpan> =
+            Diagnostic::new_error("Unexpected type in `+` application");
+        let header = models::Header::new(&diagnostic, &crate::DefaultConfig);
+
+        let document = Component(super::Header, header).into_fragment();
+        let styles = Stylesheet::new()
+            .add("header primary", "fg: red")
+            .add("header separator", "fg: magenta");
+
+        let mut writer = ColorAccumulator::new();
+        document.write_with(&mut writer, &styles)?;
+
+        assert_eq!(
+            writer.to_string(),
+            "{fg:Red}error{fg:Magenta}: {/}Unexpected type in `+` application\n"
+        );
+
+        Ok(())
+    }
+}
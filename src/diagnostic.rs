@@ -1,8 +1,12 @@
+use crate::models::severity_section;
 use crate::{ReportingSpan, Severity};
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::iter::FromIterator;
 use serde_derive::{Serialize, Deserialize};
 
 /// A style for the label
-#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
 pub enum LabelStyle {
     /// The main focus of the diagnostic
     Primary,
@@ -11,7 +15,7 @@ pub enum LabelStyle {
 }
 
 /// A label describing an underlined region of code associated with a diagnostic
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Label<Span: ReportingSpan> {
     /// The span we are going to include in the final snippet.
     pub span: Span,
@@ -46,10 +50,106 @@ impl<Span: ReportingSpan> Label<Span> {
     pub fn message(&self) -> &Option<String> {
         &self.message
     }
+
+    /// Convert the label's span, keeping its message and style.
+    pub fn map_span<NewSpan: ReportingSpan>(self, f: impl FnOnce(Span) -> NewSpan) -> Label<NewSpan> {
+        Label {
+            span: f(self.span),
+            message: self.message,
+            style: self.style,
+        }
+    }
+}
+
+/// The kind of a note attached to a diagnostic
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub enum NoteStyle {
+    /// Additional context about the diagnostic
+    Note,
+    /// A suggestion for how to fix the diagnostic
+    Help,
+}
+
+/// A free-text note shown below a diagnostic's source code, like rustc's
+/// `= help: ...`
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Note {
+    /// Whether this is a note or a help message.
+    pub style: NoteStyle,
+    /// The text of the note.
+    pub message: String,
+}
+
+impl Note {
+    pub fn new<S: Into<String>>(style: NoteStyle, message: S) -> Note {
+        Note {
+            style,
+            message: message.into(),
+        }
+    }
+}
+
+/// A suggested edit to the source, shown below a diagnostic with the
+/// replacement substituted into the line.
+///
+/// An empty span is an insertion, and an empty replacement is a deletion.
+/// Suggestions are shown on the line that the span starts on, so the span and
+/// replacement should be on a single line.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Suggestion<Span: ReportingSpan> {
+    /// The span of source to replace.
+    pub span: Span,
+    /// The text to replace the span with.
+    pub replacement: String,
+    /// A message explaining the suggestion.
+    pub message: String,
+}
+
+impl<Span: ReportingSpan> Suggestion<Span> {
+    pub fn new<R: Into<String>, M: Into<String>>(
+        span: Span,
+        replacement: R,
+        message: M,
+    ) -> Suggestion<Span> {
+        Suggestion {
+            span,
+            replacement: replacement.into(),
+            message: message.into(),
+        }
+    }
+
+    /// Convert the suggestion's span, keeping its replacement and message.
+    pub fn map_span<NewSpan: ReportingSpan>(
+        self,
+        f: impl FnOnce(Span) -> NewSpan,
+    ) -> Suggestion<NewSpan> {
+        Suggestion {
+            span: f(self.span),
+            replacement: self.replacement,
+            message: self.message,
+        }
+    }
+}
+
+/// Rewrites spans, like a compiler mapping spans in macro-expanded code back
+/// to the macro's call site. See `Diagnostic::remap`.
+///
+/// Any `Fn(Span) -> Span` is a `SpanMapper`.
+pub trait SpanMapper<Span: ReportingSpan> {
+    fn remap(&self, span: Span) -> Span;
+}
+
+impl<Span: ReportingSpan, F: Fn(Span) -> Span> SpanMapper<Span> for F {
+    fn remap(&self, span: Span) -> Span {
+        self(span)
+    }
 }
 
 /// Represents a diagnostic message and associated child messages.
-#[derive(Clone, Debug, Deserialize, Serialize)]
+///
+/// Diagnostics are equal, and hash the same, if everything about them is,
+/// including their spans, when their span type supports it.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
 pub struct Diagnostic<Span: ReportingSpan> {
     /// The overall severity of the diagnostic
     pub severity: Severity,
@@ -60,6 +160,12 @@ pub struct Diagnostic<Span: ReportingSpan> {
     /// The labelled spans marking the regions of code that cause this
     /// diagnostic to be raised
     pub labels: Vec<Label<Span>>,
+    /// Notes and help messages shown below the source code
+    #[serde(default)]
+    pub notes: Vec<Note>,
+    /// Suggested edits shown below the notes
+    #[serde(default)]
+    pub suggestions: Vec<Suggestion<Span>>,
 }
 
 impl<Span: ReportingSpan> Diagnostic<Span> {
@@ -69,6 +175,8 @@ impl<Span: ReportingSpan> Diagnostic<Span> {
             code: None,
             message: message.into(),
             labels: Vec::new(),
+            notes: Vec::new(),
+            suggestions: Vec::new(),
         }
     }
 
@@ -102,6 +210,51 @@ impl<Span: ReportingSpan> Diagnostic<Span> {
         self
     }
 
+    pub fn with_note<S: Into<String>>(mut self, message: S) -> Diagnostic<Span> {
+        self.notes.push(Note::new(NoteStyle::Note, message));
+        self
+    }
+
+    pub fn with_help<S: Into<String>>(mut self, message: S) -> Diagnostic<Span> {
+        self.notes.push(Note::new(NoteStyle::Help, message));
+        self
+    }
+
+    pub fn with_suggestion(mut self, suggestion: Suggestion<Span>) -> Diagnostic<Span> {
+        self.suggestions.push(suggestion);
+        self
+    }
+
+    /// Convert the spans of every label and suggestion, for example to a
+    /// different `ReportingFiles`'s span type.
+    pub fn map_spans<NewSpan: ReportingSpan>(
+        self,
+        mut f: impl FnMut(Span) -> NewSpan,
+    ) -> Diagnostic<NewSpan> {
+        Diagnostic {
+            severity: self.severity,
+            code: self.code,
+            message: self.message,
+            labels: self
+                .labels
+                .into_iter()
+                .map(|label| label.map_span(&mut f))
+                .collect(),
+            notes: self.notes,
+            suggestions: self
+                .suggestions
+                .into_iter()
+                .map(|suggestion| suggestion.map_span(&mut f))
+                .collect(),
+        }
+    }
+
+    /// Rewrite the spans of every label and suggestion through `mapper`, so
+    /// the diagnostic is reported at the original source positions.
+    pub fn remap(self, mapper: &impl SpanMapper<Span>) -> Diagnostic<Span> {
+        self.map_spans(|span| mapper.remap(span))
+    }
+
     pub fn with_labels<Labels: IntoIterator<Item = Label<Span>>>(
         mut self,
         labels: Labels,
@@ -110,3 +263,114 @@ impl<Span: ReportingSpan> Diagnostic<Span> {
         self
     }
 }
+
+/// A batch of diagnostics with the duplicates removed, for front-ends that
+/// report the same diagnostic many times, like once for each expansion of a
+/// macro or each instantiation of a generic function.
+///
+/// Diagnostics are kept in the order they were first inserted, and each
+/// remembers how many times it was inserted.
+///
+/// ```
+/// use language_reporting::{Diagnostic, DiagnosticSet, SimpleSpan};
+///
+/// let mut set = DiagnosticSet::new();
+///
+/// for _ in 0..3 {
+///     set.insert(Diagnostic::<SimpleSpan>::new_error("Unbound variable `x`"));
+/// }
+///
+/// let diagnostics = set.into_diagnostics();
+/// assert_eq!(diagnostics.len(), 1);
+/// assert_eq!(diagnostics[0].notes[0].message, "this error occurs 3 times");
+/// ```
+#[derive(Clone, Debug)]
+pub struct DiagnosticSet<Span: ReportingSpan + Eq + Hash> {
+    diagnostics: Vec<(Diagnostic<Span>, usize)>,
+    indices: HashMap<Diagnostic<Span>, usize>,
+}
+
+impl<Span: ReportingSpan + Eq + Hash> DiagnosticSet<Span> {
+    pub fn new() -> DiagnosticSet<Span> {
+        DiagnosticSet {
+            diagnostics: vec![],
+            indices: HashMap::new(),
+        }
+    }
+
+    /// Add a diagnostic to the set. Returns `false` if an equal diagnostic
+    /// was already inserted, in which case only its count goes up.
+    pub fn insert(&mut self, diagnostic: Diagnostic<Span>) -> bool {
+        if let Some(&index) = self.indices.get(&diagnostic) {
+            self.diagnostics[index].1 += 1;
+            return false;
+        }
+
+        self.indices.insert(diagnostic.clone(), self.diagnostics.len());
+        self.diagnostics.push((diagnostic, 1));
+        true
+    }
+
+    /// The number of distinct diagnostics.
+    pub fn len(&self) -> usize {
+        self.diagnostics.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.diagnostics.is_empty()
+    }
+
+    /// How many times `diagnostic` was inserted.
+    pub fn count(&self, diagnostic: &Diagnostic<Span>) -> usize {
+        match self.indices.get(diagnostic) {
+            Some(&index) => self.diagnostics[index].1,
+            None => 0,
+        }
+    }
+
+    /// The distinct diagnostics, in the order they were first inserted, with
+    /// the number of times each was inserted.
+    pub fn iter(&self) -> impl Iterator<Item = (&Diagnostic<Span>, usize)> {
+        self.diagnostics
+            .iter()
+            .map(|(diagnostic, count)| (diagnostic, *count))
+    }
+
+    /// The distinct diagnostics, ready for `emit_all`. A diagnostic that was
+    /// inserted more than once gets a note like `this error occurs 12 times`.
+    pub fn into_diagnostics(self) -> Vec<Diagnostic<Span>> {
+        self.diagnostics
+            .into_iter()
+            .map(|(diagnostic, count)| {
+                if count == 1 {
+                    return diagnostic;
+                }
+
+                let severity = severity_section(diagnostic.severity);
+                diagnostic.with_note(format!("this {} occurs {} times", severity, count))
+            })
+            .collect()
+    }
+}
+
+impl<Span: ReportingSpan + Eq + Hash> Default for DiagnosticSet<Span> {
+    fn default() -> DiagnosticSet<Span> {
+        DiagnosticSet::new()
+    }
+}
+
+impl<Span: ReportingSpan + Eq + Hash> Extend<Diagnostic<Span>> for DiagnosticSet<Span> {
+    fn extend<I: IntoIterator<Item = Diagnostic<Span>>>(&mut self, diagnostics: I) {
+        for diagnostic in diagnostics {
+            self.insert(diagnostic);
+        }
+    }
+}
+
+impl<Span: ReportingSpan + Eq + Hash> FromIterator<Diagnostic<Span>> for DiagnosticSet<Span> {
+    fn from_iter<I: IntoIterator<Item = Diagnostic<Span>>>(diagnostics: I) -> DiagnosticSet<Span> {
+        let mut set = DiagnosticSet::new();
+        set.extend(diagnostics);
+        set
+    }
+}
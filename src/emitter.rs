@@ -1,12 +1,18 @@
 use crate::components;
-use crate::diagnostic::Diagnostic;
-use crate::span::ReportingFiles;
+use crate::diagnostic::{Diagnostic, Label, NoteStyle};
+use crate::models;
+use crate::simple::{SimpleReportingFiles, SimpleSpan};
+use crate::span::{ReportingFiles, ReportingSpan};
+use crate::{LabelStyle, Severity, SeverityPolicy};
 
 use log;
 use render_tree::{Component, Render, Stylesheet};
+use serde_derive::Serialize;
+use std::borrow::Cow;
+use std::ops::Range;
 use std::path::Path;
 use std::{fmt, io};
-use termcolor::WriteColor;
+use termcolor::{Buffer, ColorChoice, ColorSpec, StandardStream, WriteColor};
 
 pub fn emit<'doc, W, Files: ReportingFiles>(
     writer: W,
@@ -17,145 +23,1156 @@ pub fn emit<'doc, W, Files: ReportingFiles>(
 where
     W: WriteColor,
 {
-    DiagnosticWriter { writer }.emit(DiagnosticData {
+    write_diagnostic(writer, false, files, diagnostic, config)
+}
+
+/// Emit a diagnostic. `ansi` is whether `writer` is known to write ANSI
+/// escapes, which lets it write styles that `ColorSpec` can't express.
+fn write_diagnostic<'doc, W, Files: ReportingFiles>(
+    writer: W,
+    ansi: bool,
+    files: &'doc Files,
+    diagnostic: &'doc Diagnostic<Files::Span>,
+    config: &'doc dyn Config,
+) -> io::Result<()>
+where
+    W: WriteColor,
+{
+    if diagnostic.severity < config.min_severity() {
+        return Ok(());
+    }
+
+    DiagnosticWriter { writer, ansi }.emit(DiagnosticData {
         files,
         diagnostic,
         config,
     })
 }
 
-struct DiagnosticWriter<W> {
-    writer: W,
-}
-
-impl<W> DiagnosticWriter<W>
+/// Emit a batch of diagnostics, separated by blank lines, followed by a
+/// summary like `error: aborting due to 2 previous errors; 1 warning emitted`.
+///
+/// Bugs count as errors in the summary. Diagnostics that are less severe than
+/// `Config::min_severity` are skipped, and aren't counted, but the summary
+/// itself is always shown. If there are no errors or warnings, no summary is
+/// emitted.
+pub fn emit_all<'doc, W, Files: ReportingFiles>(
+    mut writer: W,
+    files: &'doc Files,
+    diagnostics: &'doc [Diagnostic<Files::Span>],
+    config: &'doc dyn Config,
+) -> io::Result<()>
 where
     W: WriteColor,
 {
-    fn emit<'doc>(mut self, data: DiagnosticData<'doc, impl ReportingFiles>) -> io::Result<()> {
-        let document = Component(components::Diagnostic, data).into_fragment();
+    let severities: Vec<Severity> = diagnostics
+        .iter()
+        .map(|diagnostic| diagnostic.severity)
+        .filter(|&severity| severity >= config.min_severity())
+        .collect();
+
+    for (i, diagnostic) in diagnostics
+        .iter()
+        .filter(|diagnostic| diagnostic.severity >= config.min_severity())
+        .enumerate()
+    {
+        if i > 0 {
+            writeln!(writer)?;
+        }
 
-        let styles = Stylesheet::new()
-            .add("** header **", "weight: bold")
-            .add("bug ** primary", "fg: red")
-            .add("error ** primary", "fg: red")
-            .add("warning ** primary", "fg: yellow")
-            .add("note ** primary", "fg: green")
-            .add("help ** primary", "fg: cyan")
-            .add("** secondary", "fg: blue")
-            .add("** gutter", "fg: blue");
+        emit(&mut writer, files, diagnostic, config)?;
+    }
 
-        if log::log_enabled!(log::Level::Debug) {
-            document.debug_write(&mut self.writer, &styles)?;
+    // There's always at least one diagnostic above a summary
+    if let Some((severity, message)) = summary(&severities) {
+        writeln!(writer)?;
+
+        let summary: Diagnostic<Files::Span> = Diagnostic::new(severity, message);
+
+        // The summary is shown even if `min_severity` would skip it
+        let writer = DiagnosticWriter {
+            writer: &mut writer,
+            ansi: false,
+        };
+
+        writer.emit(DiagnosticData {
+            files,
+            diagnostic: &summary,
+            config,
+        })?;
+    }
+
+    Ok(())
+}
+
+/// The number of diagnostics of each severity that an `Emitter` has emitted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SeverityCounts {
+    pub bugs: usize,
+    pub errors: usize,
+    pub warnings: usize,
+    pub notes: usize,
+    pub helps: usize,
+}
+
+impl SeverityCounts {
+    /// The number of diagnostics with `severity`.
+    pub fn get(&self, severity: Severity) -> usize {
+        match severity {
+            Severity::Bug => self.bugs,
+            Severity::Error => self.errors,
+            Severity::Warning => self.warnings,
+            Severity::Note => self.notes,
+            Severity::Help => self.helps,
+        }
+    }
+
+    /// The number of diagnostics of any severity.
+    pub fn total(&self) -> usize {
+        self.bugs + self.errors + self.warnings + self.notes + self.helps
+    }
+
+    /// The most severe severity that was counted, if anything was.
+    pub fn worst(&self) -> Option<Severity> {
+        [
+            Severity::Bug,
+            Severity::Error,
+            Severity::Warning,
+            Severity::Note,
+            Severity::Help,
+        ]
+        .iter()
+        .cloned()
+        .find(|&severity| self.get(severity) > 0)
+    }
+
+    /// The process exit code for the counted diagnostics: `1` if any of them
+    /// is at least as severe as `Config::exit_code_threshold`, and `0`
+    /// otherwise.
+    pub fn exit_code(&self, config: &dyn Config) -> i32 {
+        match self.worst() {
+            None => 0,
+            Some(worst) => worst.exit_code_with_threshold(config.exit_code_threshold()),
+        }
+    }
+
+    fn increment(&mut self, severity: Severity) {
+        match severity {
+            Severity::Bug => self.bugs += 1,
+            Severity::Error => self.errors += 1,
+            Severity::Warning => self.warnings += 1,
+            Severity::Note => self.notes += 1,
+            Severity::Help => self.helps += 1,
+        }
+    }
+}
+
+/// Emits diagnostics one at a time, counting them by severity, so that a
+/// compiler can check whether a pass had any errors without keeping track
+/// of that itself.
+///
+/// Like `emit`, diagnostics that are less severe than `Config::min_severity`
+/// are skipped, and aren't counted. After `max_errors` errors, further errors
+/// are counted but not shown, and `finish` notes how many were hidden.
+///
+/// ```rust
+/// use language_reporting::*;
+/// use termcolor::Buffer;
+///
+/// let files = SimpleReportingFiles::default();
+/// let mut emitter = Emitter::new(Buffer::no_color(), &files, &DefaultConfig).max_errors(1);
+///
+/// emitter.emit(&Diagnostic::new_error("First")).unwrap();
+/// emitter.emit(&Diagnostic::new_error("Second")).unwrap();
+///
+/// assert!(emitter.has_errors());
+/// assert_eq!(emitter.counts().errors, 2);
+///
+/// let output = emitter.finish().unwrap().into_inner();
+/// assert_eq!(
+///     String::from_utf8(output).unwrap(),
+///     "error: First\nnote: 1 error not shown\n"
+/// );
+/// ```
+pub struct Emitter<'doc, W: WriteColor, Files: ReportingFiles> {
+    writer: W,
+    files: &'doc Files,
+    config: &'doc dyn Config,
+    counts: SeverityCounts,
+    max_errors: Option<usize>,
+    hidden_errors: usize,
+    severity_policy: SeverityPolicy,
+}
+
+impl<'doc, W: WriteColor, Files: ReportingFiles> Emitter<'doc, W, Files> {
+    pub fn new(writer: W, files: &'doc Files, config: &'doc dyn Config) -> Self {
+        Emitter {
+            writer,
+            files,
+            config,
+            counts: SeverityCounts::default(),
+            max_errors: None,
+            hidden_errors: 0,
+            severity_policy: SeverityPolicy::new(),
+        }
+    }
+
+    /// Only show the first `max_errors` errors. Bugs count as errors.
+    pub fn max_errors(self, max_errors: usize) -> Self {
+        Emitter {
+            max_errors: Some(max_errors),
+            ..self
+        }
+    }
+
+    /// Change the severity of diagnostics with `policy` before they're
+    /// shown and counted. Diagnostics that the policy allows are skipped, and
+    /// aren't counted.
+    pub fn severity_policy(self, policy: SeverityPolicy) -> Self {
+        Emitter {
+            severity_policy: policy,
+            ..self
+        }
+    }
+
+    /// Emit `diagnostic`, unless it's an error past `max_errors`, and count
+    /// it.
+    pub fn emit(&mut self, diagnostic: &Diagnostic<Files::Span>) -> io::Result<()> {
+        let severity = match self.severity_policy.severity(diagnostic) {
+            None => return Ok(()),
+            Some(severity) => severity,
+        };
+
+        if severity < self.config.min_severity() {
+            return Ok(());
+        }
+
+        let is_error = severity >= Severity::Error;
+        let shown_errors = self.error_count() - self.hidden_errors;
+
+        self.counts.increment(severity);
+
+        if is_error && self.max_errors.is_some_and(|max| shown_errors >= max) {
+            self.hidden_errors += 1;
+            return Ok(());
+        }
+
+        if severity == diagnostic.severity {
+            emit(&mut self.writer, self.files, diagnostic, self.config)
+        } else {
+            let diagnostic = Diagnostic {
+                severity,
+                ..diagnostic.clone()
+            };
+
+            emit(&mut self.writer, self.files, &diagnostic, self.config)
+        }
+    }
+
+    pub fn counts(&self) -> &SeverityCounts {
+        &self.counts
+    }
+
+    /// The number of errors emitted so far, including bugs and errors that
+    /// weren't shown.
+    pub fn error_count(&self) -> usize {
+        self.counts.bugs + self.counts.errors
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.error_count() > 0
+    }
+
+    /// The process exit code for the diagnostics emitted so far, from
+    /// `Config::exit_code_threshold`. See `SeverityCounts::exit_code`.
+    pub fn exit_code(&self) -> i32 {
+        self.counts.exit_code(self.config)
+    }
+
+    /// The number of errors that weren't shown because of `max_errors`.
+    pub fn hidden_errors(&self) -> usize {
+        self.hidden_errors
+    }
+
+    /// Note how many errors weren't shown, if any, and return the writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        if self.hidden_errors > 0 {
+            let message = format!(
+                "{} error{} not shown",
+                self.hidden_errors,
+                plural(self.hidden_errors)
+            );
+            let note: Diagnostic<Files::Span> = Diagnostic::new(Severity::Note, message);
+
+            // The note is shown even if `min_severity` would skip notes
+            let writer = DiagnosticWriter {
+                writer: &mut self.writer,
+                ansi: false,
+            };
+
+            writer.emit(DiagnosticData {
+                files: self.files,
+                diagnostic: &note,
+                config: self.config,
+            })?;
         }
 
-        document.write_with(&mut self.writer, &styles)?;
+        Ok(self.writer)
+    }
+
+    /// Return the writer, without noting how many errors weren't shown.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// The severity and message of the summary that `emit_all` prints after a
+/// batch of diagnostics.
+fn summary(severities: &[Severity]) -> Option<(Severity, String)> {
+    let count = |matches: fn(Severity) -> bool| {
+        severities
+            .iter()
+            .filter(|&&severity| matches(severity))
+            .count()
+    };
+
+    let errors = count(|severity| severity >= Severity::Error);
+    let warnings = count(|severity| severity == Severity::Warning);
+
+    let warnings_emitted = format!("{} warning{} emitted", warnings, plural(warnings));
 
+    match (errors, warnings) {
+        (0, 0) => None,
+        (0, _) => Some((Severity::Warning, warnings_emitted)),
+        (_, 0) => Some((
+            Severity::Error,
+            format!("aborting due to {} previous error{}", errors, plural(errors)),
+        )),
+        (_, _) => Some((
+            Severity::Error,
+            format!(
+                "aborting due to {} previous error{}; {}",
+                errors,
+                plural(errors),
+                warnings_emitted
+            ),
+        )),
+    }
+}
+
+fn plural(count: usize) -> &'static str {
+    if count == 1 {
+        ""
+    } else {
+        "s"
+    }
+}
+
+/// Emit a diagnostic into a `String`, without any color.
+///
+/// This allocates a buffer for the whole diagnostic, and is intended for
+/// testing and logging rather than for writing to a terminal. Writing to the
+/// buffer can't fail, so there's no `io::Result` to unwrap.
+///
+/// ```rust
+/// use language_reporting::*;
+///
+/// let mut files = SimpleReportingFiles::default();
+/// let file = files.add("test", "(+ test \"\")\n");
+///
+/// let error = Diagnostic::new_error("Unexpected string")
+///     .with_label(Label::new_primary(SimpleSpan::new(file, 8, 10)));
+///
+/// assert_eq!(
+///     emit_to_string(&files, &error, &DefaultConfig),
+///     "error: Unexpected string\n- test:1:9\n1 | (+ test \"\")\n  |         ^^\n"
+/// );
+/// ```
+pub fn emit_to_string<'doc, Files: ReportingFiles>(
+    files: &'doc Files,
+    diagnostic: &'doc Diagnostic<Files::Span>,
+    config: &'doc dyn Config,
+) -> String {
+    emit_to_buffer(Buffer::no_color(), false, files, diagnostic, config)
+}
+
+/// Emit a diagnostic into a `String`, colored with ANSI escape sequences.
+///
+/// Like `emit_to_string`, this allocates and is intended for testing and
+/// logging.
+pub fn emit_ansi_string<'doc, Files: ReportingFiles>(
+    files: &'doc Files,
+    diagnostic: &'doc Diagnostic<Files::Span>,
+    config: &'doc dyn Config,
+) -> String {
+    emit_to_buffer(Buffer::ansi(), true, files, diagnostic, config)
+}
+
+/// Report a diagnostic with a single primary label to stderr, in one call.
+///
+/// This is the smallest possible entry point, for scripts and quick tools
+/// that only have one source file: it adds `source` to a
+/// `SimpleReportingFiles` under `file_name`, labels the bytes in `span`, and
+/// emits the diagnostic with `DefaultConfig`. Color is used when stderr is a
+/// terminal.
+///
+/// ```rust
+/// use language_reporting::{report, Severity};
+///
+/// report(Severity::Error, "Unexpected string", "test", "(+ test \"\")\n", 8..10).unwrap();
+/// ```
+pub fn report(
+    severity: Severity,
+    message: impl Into<String>,
+    file_name: impl Into<String>,
+    source: impl Into<String>,
+    span: Range<usize>,
+) -> io::Result<()> {
+    let mut files = SimpleReportingFiles::default();
+    let file = files.add(file_name, source);
+
+    let diagnostic = Diagnostic::new(severity, message)
+        .with_label(Label::new_primary(SimpleSpan::new(file, span.start, span.end)));
+
+    let mut writer = StandardStream::stderr(ColorChoice::Auto);
+    emit(&mut writer, &files, &diagnostic, &DefaultConfig)
+}
+
+/// Emit a diagnostic without color into a `fmt::Write`, like the
+/// `fmt::Formatter` in a `Display` impl, so that an error type can show its
+/// diagnostic without allocating a buffer.
+///
+/// ```rust
+/// use language_reporting::*;
+/// use std::fmt;
+///
+/// struct MyError {
+///     files: SimpleReportingFiles,
+///     diagnostic: Diagnostic<SimpleSpan>,
+/// }
+///
+/// impl fmt::Display for MyError {
+///     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+///         emit_fmt(f, &self.files, &self.diagnostic, &DefaultConfig)
+///     }
+/// }
+///
+/// let mut files = SimpleReportingFiles::default();
+/// let file = files.add("test", "(+ test \"\")\n");
+///
+/// let diagnostic = Diagnostic::new_error("Unexpected string")
+///     .with_label(Label::new_primary(SimpleSpan::new(file, 8, 10)));
+///
+/// let error = MyError { files, diagnostic };
+///
+/// assert_eq!(
+///     error.to_string(),
+///     "error: Unexpected string\n- test:1:9\n1 | (+ test \"\")\n  |         ^^\n"
+/// );
+/// ```
+pub fn emit_fmt<'doc, Files: ReportingFiles>(
+    f: &mut dyn fmt::Write,
+    files: &'doc Files,
+    diagnostic: &'doc Diagnostic<Files::Span>,
+    config: &'doc dyn Config,
+) -> fmt::Result {
+    let mut writer = FmtWriter {
+        inner: f,
+        pending: vec![],
+    };
+
+    write_diagnostic(&mut writer, false, files, diagnostic, config).map_err(|_| fmt::Error)?;
+
+    // The diagnostic is always valid UTF-8, so nothing is left over
+    if writer.pending.is_empty() {
         Ok(())
+    } else {
+        Err(fmt::Error)
     }
 }
 
-pub trait Config: std::fmt::Debug {
-    fn filename(&self, path: &Path) -> String;
+/// Emit a diagnostic into a `String`, without color. Unlike
+/// `emit_to_string`, the text isn't round-tripped through bytes.
+pub fn format_diagnostic<'doc, Files: ReportingFiles>(
+    files: &'doc Files,
+    diagnostic: &'doc Diagnostic<Files::Span>,
+    config: &'doc dyn Config,
+) -> String {
+    let mut out = String::new();
+    emit_fmt(&mut out, files, diagnostic, config).expect("writing to a String can't fail");
+    out
 }
 
-#[derive(Debug)]
-pub struct DefaultConfig;
+/// A `WriteColor` over a `fmt::Write`, which ignores colors. Writes that end
+/// partway through a UTF-8 character are held in `pending` until the rest of
+/// the character is written.
+struct FmtWriter<'a> {
+    inner: &'a mut dyn fmt::Write,
+    pending: Vec<u8>,
+}
 
-impl Config for DefaultConfig {
-    fn filename(&self, path: &Path) -> String {
-        format!("{}", path.display())
+impl<'a> io::Write for FmtWriter<'a> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+
+        let valid = match std::str::from_utf8(&self.pending) {
+            Ok(text) => text.len(),
+            Err(err) if err.error_len().is_none() => err.valid_up_to(),
+            Err(err) => return Err(io::Error::new(io::ErrorKind::InvalidData, err)),
+        };
+
+        let text = std::str::from_utf8(&self.pending[..valid]).unwrap();
+
+        self.inner
+            .write_str(text)
+            .map_err(io::Error::other)?;
+        self.pending.drain(..valid);
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
     }
 }
 
-#[derive(Debug)]
-pub(crate) struct DiagnosticData<'doc, Files: ReportingFiles> {
-    pub(crate) files: &'doc Files,
-    pub(crate) diagnostic: &'doc Diagnostic<Files::Span>,
-    pub(crate) config: &'doc dyn Config,
+impl<'a> WriteColor for FmtWriter<'a> {
+    fn supports_color(&self) -> bool {
+        false
+    }
+
+    fn set_color(&mut self, _spec: &ColorSpec) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn reset(&mut self) -> io::Result<()> {
+        Ok(())
+    }
 }
 
-pub fn format(f: impl Fn(&mut fmt::Formatter) -> fmt::Result) -> impl fmt::Display {
-    struct Display<F>(F);
+fn emit_to_buffer<'doc, Files: ReportingFiles>(
+    mut buffer: Buffer,
+    ansi: bool,
+    files: &'doc Files,
+    diagnostic: &'doc Diagnostic<Files::Span>,
+    config: &'doc dyn Config,
+) -> String {
+    write_diagnostic(&mut buffer, ansi, files, diagnostic, config).expect("writing to a Buffer can't fail");
 
-    impl<F> fmt::Display for Display<F>
-    where
-        F: Fn(&mut fmt::Formatter) -> fmt::Result,
-    {
-        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-            (self.0)(f)
-        }
+    String::from_utf8_lossy(buffer.as_slice()).into_owned()
+}
+
+/// Emit a diagnostic as a single line of JSON, similar to rustc's
+/// `--error-format=json`.
+///
+/// Each label is resolved against `files`, so the output includes the file
+/// name, the one-based line and column, and the marked source. Notes and help
+/// messages are included with their style, and suggestions with their
+/// resolved location and replacement. If a label or suggestion can't be
+/// resolved, an `InvalidData` error is returned and nothing is written.
+pub fn emit_json<'doc, W, Files: ReportingFiles>(
+    mut writer: W,
+    files: &'doc Files,
+    diagnostic: &'doc Diagnostic<Files::Span>,
+    config: &'doc dyn Config,
+) -> io::Result<()>
+where
+    W: io::Write,
+{
+    let json = JsonDiagnostic::new(files, diagnostic, config)?;
+
+    serde_json::to_writer(&mut writer, &json).map_err(io::Error::other)?;
+
+    writeln!(writer)
+}
+
+#[derive(Debug, Serialize)]
+struct JsonDiagnostic<'doc> {
+    severity: &'static str,
+    code: Option<&'doc str>,
+    message: &'doc str,
+    labels: Vec<JsonLabel<'doc>>,
+    notes: Vec<JsonNote<'doc>>,
+    suggestions: Vec<JsonSuggestion<'doc>>,
+}
+
+impl<'doc> JsonDiagnostic<'doc> {
+    fn new<Files: ReportingFiles>(
+        files: &'doc Files,
+        diagnostic: &'doc Diagnostic<Files::Span>,
+        config: &'doc dyn Config,
+    ) -> io::Result<JsonDiagnostic<'doc>> {
+        let labels = diagnostic
+            .labels
+            .iter()
+            .map(|label| {
+                let source_line = resolved_line(files, &label.span, config, "label")?;
+                let location = source_line.location();
+
+                Ok(JsonLabel {
+                    style: match label.style {
+                        LabelStyle::Primary => "primary",
+                        LabelStyle::Secondary => "secondary",
+                    },
+                    message: label.message.as_ref().map(|m| &m[..]),
+                    file: source_line.filename(),
+                    line: location.line + 1,
+                    column: location.column + 1,
+                    byte_start: label.span.start(),
+                    byte_end: label.span.end(),
+                    source: source_line.marked(),
+                })
+            })
+            .collect::<io::Result<_>>()?;
+
+        let suggestions = diagnostic
+            .suggestions
+            .iter()
+            .map(|suggestion| {
+                let source_line = resolved_line(files, &suggestion.span, config, "suggestion")?;
+                let location = source_line.location();
+
+                Ok(JsonSuggestion {
+                    message: &suggestion.message,
+                    replacement: &suggestion.replacement,
+                    file: source_line.filename(),
+                    line: location.line + 1,
+                    column: location.column + 1,
+                    byte_start: suggestion.span.start(),
+                    byte_end: suggestion.span.end(),
+                })
+            })
+            .collect::<io::Result<_>>()?;
+
+        Ok(JsonDiagnostic {
+            severity: models::severity(diagnostic),
+            code: diagnostic.code.as_ref().map(|c| &c[..]),
+            message: &diagnostic.message,
+            labels,
+            notes: diagnostic
+                .notes
+                .iter()
+                .map(|note| JsonNote {
+                    style: match note.style {
+                        NoteStyle::Note => "note",
+                        NoteStyle::Help => "help",
+                    },
+                    message: &note.message,
+                })
+                .collect(),
+            suggestions,
+        })
     }
-    Display(f)
 }
 
-#[cfg(test)]
-mod default_emit_smoke_tests {
-    use super::*;
-    use crate::diagnostic::{Diagnostic, Label};
-    use crate::simple::*;
-    use crate::termcolor::Buffer;
-    use crate::Severity;
+/// The line of a label's or suggestion's span, or an `InvalidData` error if
+/// `files` can't resolve the span.
+fn resolved_line<'doc, Files: ReportingFiles>(
+    files: &'doc Files,
+    span: &'doc Files::Span,
+    config: &'doc dyn Config,
+    description: &str,
+) -> io::Result<models::SourceLine<'doc, Files>> {
+    let source_line = models::SourceLine::for_span(files, span, config);
 
-    use regex;
-    use render_tree::stylesheet::ColorAccumulator;
-    use unindent::unindent;
+    if source_line.resolves() {
+        Ok(source_line)
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "Couldn't resolve the {} at {}..{} in {}",
+                description,
+                span.start(),
+                span.end(),
+                source_line.filename()
+            ),
+        ))
+    }
+}
 
-    fn emit_with_writer<W: WriteColor>(mut writer: W) -> W {
-        let mut files = SimpleReportingFiles::default();
+#[derive(Debug, Serialize)]
+struct JsonLabel<'doc> {
+    style: &'static str,
+    message: Option<&'doc str>,
+    file: String,
+    line: usize,
+    column: usize,
+    byte_start: usize,
+    byte_end: usize,
+    source: Cow<'doc, str>,
+}
 
-        let source = unindent(
-            r##"
-                (define test 123)
-                (+ test "")
-                ()
-            "##,
-        );
+#[derive(Debug, Serialize)]
+struct JsonNote<'doc> {
+    style: &'static str,
+    message: &'doc str,
+}
 
-        let file = files.add("test", source);
+#[derive(Debug, Serialize)]
+struct JsonSuggestion<'doc> {
+    message: &'doc str,
+    replacement: &'doc str,
+    file: String,
+    line: usize,
+    column: usize,
+    byte_start: usize,
+    byte_end: usize,
+}
 
-        let str_start = files.byte_index(file, 1, 8).unwrap();
-        let error = Diagnostic::new(Severity::Error, "Unexpected type in `+` application")
-            .with_label(
-                Label::new_primary(SimpleSpan::new(file, str_start, str_start + 2))
-                    .with_message("Expected integer but got string"),
-            )
-            .with_label(
-                Label::new_secondary(SimpleSpan::new(file, str_start, str_start + 2))
-                    .with_message("Expected integer but got string"),
-            )
-            .with_code("E0001");
+struct DiagnosticWriter<W> {
+    writer: W,
+    /// Whether `writer` is known to write ANSI escapes, so that styles
+    /// `ColorSpec` can't express, like `strikethrough`, can be written.
+    ansi: bool,
+}
 
-        let line_start = files.byte_index(file, 1, 0).unwrap();
-        let warning = Diagnostic::new(
-            Severity::Warning,
-            "`+` function has no effect unless its result is used",
-        )
-        .with_label(Label::new_primary(SimpleSpan::new(
-            file,
-            line_start,
-            line_start + 11,
-        )));
+impl<W> DiagnosticWriter<W>
+where
+    W: WriteColor,
+{
+    fn emit<'doc>(mut self, data: DiagnosticData<'doc, impl ReportingFiles>) -> io::Result<()> {
+        let styles = data.config.stylesheet();
+        let document = Component(components::Diagnostic, data).into_fragment();
 
-        let diagnostics = [error, warning];
+        if log::log_enabled!(log::Level::Debug) {
+            document.debug_write(&mut self.writer, &styles)?;
+        }
 
-        for diagnostic in &diagnostics {
-            emit(&mut writer, &files, &diagnostic, &super::DefaultConfig).unwrap();
+        if self.ansi {
+            document.write_ansi_with(&mut self.writer, &styles)?;
+        } else {
+            document.write_with(&mut self.writer, &styles)?;
         }
 
-        writer
+        Ok(())
     }
+}
 
-    #[test]
-    fn test_no_color() {
-        assert_eq!(
-            String::from_utf8_lossy(&emit_with_writer(Buffer::no_color()).into_inner()),
-            unindent(&format!(
-                r##"
-                    error[E0001]: Unexpected type in `+` application
-                    - test:2:9
-                    2 | (+ test "")
-                      |         ^^ Expected integer but got string
+pub trait Config: std::fmt::Debug {
+    fn filename(&self, path: &Path) -> String;
+
+    /// The color theme for diagnostics, which `stylesheet` uses by default.
+    fn theme(&self) -> Theme {
+        Theme::Dark
+    }
+
+    /// The stylesheet that diagnostics are colored with. The default is the
+    /// stylesheet of `theme`, which can be extended with more rules.
+    ///
+    /// These sections are a stable contract for stylesheets to target:
+    ///
+    /// - `bug`, `error`, `warning`, `note` or `help`: the whole diagnostic,
+    ///   named after its severity
+    /// - `header`: the first line, containing `primary` (the severity and
+    ///   code) and `separator` (the `: ` before the message)
+    /// - `source-code-location`: the `- file:line:column` line
+    /// - `gutter`: the line numbers and `|` to the left of source code
+    /// - `before-marked` and `after-marked`: the source around a label
+    /// - `primary` or `secondary`: the marked source, underline and message of
+    ///   a label, named after its style
+    /// - `underline`: the line under a labelled line
+    /// - `context`: an unlabelled line shown by `context_lines`
+    /// - `note-prefix`: the `note: ` or `help: ` before a note or suggestion
+    /// - `cross-file-note`: the indented `note:` block that holds secondary
+    ///   labels in another file than the primary label
+    /// - `suggestion`: a suggested edit, containing `inserted` (a replacement
+    ///   or inserted text) or `removed` (deleted text)
+    fn stylesheet(&self) -> Stylesheet {
+        self.theme().stylesheet()
+    }
+
+    /// The name of a severity, as displayed in a diagnostic's header.
+    ///
+    /// Override this to rename or localize severities. Stylesheets still
+    /// target the original severity, so renaming doesn't change colors.
+    fn severity_name(&self, severity: Severity) -> String {
+        models::severity_section(severity).to_string()
+    }
+
+    /// A diagnostic's code, as displayed after the severity in its header,
+    /// like the `[E0001]` in `error[E0001]: `.
+    fn code_display(&self, code: &str) -> String {
+        format!("[{}]", code)
+    }
+
+    /// The separator between the line numbers in the gutter and the source,
+    /// like the ` | ` in `2 | (+ test "")`. Rows without a line number, like
+    /// underlines, are padded to line up with it.
+    fn gutter_separator(&self) -> String {
+        " | ".to_string()
+    }
+
+    /// The prefix of a label's location line, like the `- ` in
+    /// `- test:2:9`. The rustc-style layout always uses ` --> `.
+    fn location_prefix(&self) -> String {
+        "- ".to_string()
+    }
+
+    /// The number of lines of unlabelled source to show before and after
+    /// each labelled line.
+    fn context_lines(&self) -> usize {
+        0
+    }
+
+    /// The width that diagnostic messages and label messages are
+    /// word-wrapped to, or `None` to leave them unwrapped. Wrapped lines are
+    /// indented to line up with the start of the message. Source lines are
+    /// never wrapped.
+    fn wrap_width(&self) -> Option<usize> {
+        None
+    }
+
+    /// Whether to draw the row of carets under each labelled span. When this
+    /// is `false`, label messages are shown at the end of the source line.
+    fn show_underline(&self) -> bool {
+        true
+    }
+
+    /// The maximum number of columns of source to show for a labelled line,
+    /// not counting the gutter. Longer lines are elided with `…` around the
+    /// marked region, or `None` to always show the whole line.
+    fn max_line_width(&self) -> Option<usize> {
+        None
+    }
+
+    /// The character, or characters, that underline the source marked by a
+    /// primary label. A mark that's several characters or columns wide is
+    /// repeated to fill the marked columns, and cut off at the end of them.
+    fn primary_mark(&self) -> String {
+        "^".to_string()
+    }
+
+    /// Like `primary_mark`, but for secondary labels.
+    fn secondary_mark(&self) -> String {
+        "-".to_string()
+    }
+
+    /// The prefix of a note's message, shown after the `=` connector below
+    /// the source code.
+    fn note_prefix(&self) -> String {
+        "note: ".to_string()
+    }
+
+    /// The prefix of a help message, shown after the `=` connector below the
+    /// source code.
+    fn help_prefix(&self) -> String {
+        "help: ".to_string()
+    }
+
+    /// Whether to assume that source is displayed left-to-right.
+    ///
+    /// Underlines are placed by the logical (stored) order of the source, but
+    /// terminals may display runs of right-to-left text, like Hebrew or
+    /// Arabic, in a different order, so the underline can appear under the
+    /// wrong characters. When this is `false`, diagnostics whose labelled
+    /// lines contain right-to-left text get a note that says so.
+    fn assume_ltr(&self) -> bool {
+        true
+    }
+
+    /// Whether labels are rendered in order of their position, grouped by
+    /// file, starting with the file of the primary label. When this is
+    /// `false`, labels are rendered in the order they were added to the
+    /// diagnostic, for front-ends that use that order to tell a story.
+    /// Either way, secondary labels in another file than the primary label
+    /// are shown under a `note:`.
+    fn sort_labels(&self) -> bool {
+        true
+    }
+
+    /// Whether the rustc-style layout shows a blank `  |` gutter line between
+    /// the ` --> ` location line and the source.
+    fn leading_gutter_line(&self) -> bool {
+        true
+    }
+
+    /// The number that the first line and column of a file are counted from
+    /// in a label's location, like the `2:9` in `- test:2:9`. This is `1` by
+    /// default, like editors count, but tools that count from zero can use
+    /// `0`. Line numbers in the gutter always count from one.
+    fn location_base(&self) -> usize {
+        1
+    }
+
+    /// Whether a label's location shows where its span ends as well as where
+    /// it starts, like `- test:2:9-2:11`, for editors that select the whole
+    /// range. Both ends are counted from `location_base`, and the end is
+    /// exclusive, like the span's.
+    fn location_range(&self) -> bool {
+        false
+    }
+
+    /// How much of a diagnostic's secondary labels to show, for `--quiet`
+    /// and `--verbose` modes. Primary labels are always shown.
+    fn label_verbosity(&self) -> LabelVerbosity {
+        LabelVerbosity::All
+    }
+
+    /// The least severe diagnostic that should be emitted. Less severe
+    /// diagnostics are skipped, which is useful for a `--quiet` mode.
+    fn min_severity(&self) -> Severity {
+        Severity::Help
+    }
+
+    /// Whether to lay out source snippets like rustc does, with a ` --> `
+    /// location line, blank gutter lines around each snippet, and line
+    /// numbers right-aligned across the whole diagnostic.
+    fn rustc_style(&self) -> bool {
+        false
+    }
+
+    /// The least severe diagnostic that should cause a non-zero process exit
+    /// code, which `Emitter::exit_code` and `SeverityCounts::exit_code` use.
+    /// See `Severity::exit_code_with_threshold`.
+    fn exit_code_threshold(&self) -> Severity {
+        Severity::Error
+    }
+
+    /// The error parser that diagnostic messages should be escaped for, or
+    /// `None` to show messages as they are.
+    ///
+    /// Build tools like `make` scan their output for `file:line: message`
+    /// lines, so a message containing a newline or a colon can confuse them.
+    /// When this is set, the header's message is escaped with
+    /// `ParserDialect::escape`, and isn't word-wrapped even if `wrap_width`
+    /// is set.
+    fn escape_messages_for(&self) -> Option<ParserDialect> {
+        None
+    }
+}
+
+/// How secondary labels are shown, selected with `Config::label_verbosity`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LabelVerbosity {
+    /// Secondary labels are shown with their source, like primary labels.
+    All,
+    /// Secondary labels aren't shown at all.
+    PrimaryOnly,
+    /// Secondary labels' messages are shown as notes below the source, and
+    /// their source isn't shown. Secondary labels without a message aren't
+    /// shown.
+    MessagesOnly,
+}
+
+/// A family of tools that parse diagnostics out of build output, selected
+/// with `Config::escape_messages_for`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ParserDialect {
+    /// GNU make, and tools that parse its output the same way, like ninja
+    /// and editors' compilation modes. Backslashes and colons are escaped
+    /// with a backslash, line feeds are written as `\n` and carriage returns
+    /// are dropped, so the message stays on the header's line.
+    GnuMake,
+}
+
+impl ParserDialect {
+    /// Escape `message` so that it can't break this dialect's line parsing.
+    pub fn escape(self, message: &str) -> Cow<'_, str> {
+        match self {
+            ParserDialect::GnuMake => {
+                if !message.contains(['\\', ':', '\n', '\r']) {
+                    return Cow::Borrowed(message);
+                }
+
+                let mut escaped = String::with_capacity(message.len());
+
+                for c in message.chars() {
+                    match c {
+                        '\\' => escaped.push_str("\\\\"),
+                        ':' => escaped.push_str("\\:"),
+                        '\n' => escaped.push_str("\\n"),
+                        '\r' => {}
+                        c => escaped.push(c),
+                    }
+                }
+
+                Cow::Owned(escaped)
+            }
+        }
+    }
+}
+
+/// The stylesheet that `Config::stylesheet` returns by default, which is
+/// `Theme::Dark`'s.
+pub fn default_stylesheet() -> Stylesheet {
+    Theme::Dark.stylesheet()
+}
+
+/// A preset stylesheet for diagnostics, selected with `Config::theme`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Theme {
+    /// Bold headers, primary labels colored by severity, and blue secondary
+    /// labels and gutters, for dark terminal backgrounds.
+    Dark,
+    /// Like `Dark`, but with darker colors that are readable on light
+    /// terminal backgrounds.
+    Light,
+    /// No colors, only bold and underlines.
+    Plain,
+}
+
+impl Theme {
+    pub fn stylesheet(self) -> Stylesheet {
+        match self {
+            Theme::Dark => Stylesheet::new()
+                .add("** header **", "weight: bold")
+                .add("bug ** primary", "fg: red")
+                .add("error ** primary", "fg: red")
+                .add("warning ** primary", "fg: yellow")
+                .add("note ** primary", "fg: green")
+                .add("help ** primary", "fg: cyan")
+                .add("** secondary", "fg: blue")
+                .add("** gutter", "fg: blue")
+                .add("** context gutter", "weight: dim")
+                .add("** note-prefix", "weight: bold")
+                .add("** inserted", "fg: green")
+                .add("** removed", "fg: red; strikethrough: true"),
+            Theme::Light => Stylesheet::new()
+                .add("** header **", "weight: bold")
+                .add("bug ** primary", "fg: ansi(124)")
+                .add("error ** primary", "fg: ansi(124)")
+                .add("warning ** primary", "fg: ansi(130)")
+                .add("note ** primary", "fg: ansi(28)")
+                .add("help ** primary", "fg: ansi(30)")
+                .add("** secondary", "fg: ansi(25)")
+                .add("** gutter", "fg: ansi(240)")
+                .add("** context gutter", "weight: dim")
+                .add("** note-prefix", "weight: bold")
+                .add("** inserted", "fg: ansi(28)")
+                .add("** removed", "fg: ansi(124); strikethrough: true"),
+            Theme::Plain => Stylesheet::new()
+                .add("** header **", "weight: bold")
+                .add("** primary", "weight: bold")
+                .add("** secondary", "underline: true")
+                .add("** note-prefix", "weight: bold")
+                .add("** inserted", "underline: true")
+                .add("** removed", "strikethrough: true"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct DefaultConfig;
+
+impl Config for DefaultConfig {
+    fn filename(&self, path: &Path) -> String {
+        format!("{}", path.display())
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct DiagnosticData<'doc, Files: ReportingFiles> {
+    pub(crate) files: &'doc Files,
+    pub(crate) diagnostic: &'doc Diagnostic<Files::Span>,
+    pub(crate) config: &'doc dyn Config,
+}
+
+pub fn format(f: impl Fn(&mut fmt::Formatter) -> fmt::Result) -> impl fmt::Display {
+    struct Display<F>(F);
+
+    impl<F> fmt::Display for Display<F>
+    where
+        F: Fn(&mut fmt::Formatter) -> fmt::Result,
+    {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            (self.0)(f)
+        }
+    }
+    Display(f)
+}
+
+#[cfg(test)]
+mod default_emit_smoke_tests {
+    use super::*;
+    use crate::diagnostic::{Diagnostic, Label};
+    use crate::simple::*;
+    use crate::termcolor::Buffer;
+    use crate::Severity;
+
+    use regex;
+    use render_tree::stylesheet::{ColorAccumulator, Event, RecordingWriter};
+    use unindent::unindent;
+
+    fn emit_with_writer<W: WriteColor>(writer: W) -> W {
+        emit_with_writer_and_config(writer, &super::DefaultConfig)
+    }
+
+    fn emit_with_writer_and_config<W: WriteColor>(mut writer: W, config: &dyn Config) -> W {
+        let (files, diagnostics) = smoke_test_diagnostics();
+
+        for diagnostic in &diagnostics {
+            emit(&mut writer, &files, &diagnostic, config).unwrap();
+        }
+
+        writer
+    }
+
+    fn smoke_test_diagnostics() -> (SimpleReportingFiles, [Diagnostic<SimpleSpan>; 2]) {
+        let mut files = SimpleReportingFiles::default();
+
+        let source = unindent(
+            r##"
+                (define test 123)
+                (+ test "")
+                ()
+            "##,
+        );
+
+        let file = files.add("test", source);
+
+        let str_start = files.byte_index(file, 1, 8).unwrap();
+        let error = Diagnostic::new(Severity::Error, "Unexpected type in `+` application")
+            .with_label(
+                Label::new_primary(SimpleSpan::new(file, str_start, str_start + 2))
+                    .with_message("Expected integer but got string"),
+            )
+            .with_label(
+                Label::new_secondary(SimpleSpan::new(file, str_start, str_start + 2))
+                    .with_message("Expected integer but got string"),
+            )
+            .with_code("E0001");
+
+        let line_start = files.byte_index(file, 1, 0).unwrap();
+        let warning = Diagnostic::new(
+            Severity::Warning,
+            "`+` function has no effect unless its result is used",
+        )
+        .with_label(Label::new_primary(SimpleSpan::new(
+            file,
+            line_start,
+            line_start + 11,
+        )));
+
+        (files, [error, warning])
+    }
+
+    #[test]
+    fn test_plain_string_matches_to_string() {
+        let (files, diagnostics) = smoke_test_diagnostics();
+
+        for diagnostic in &diagnostics {
+            let data = DiagnosticData {
+                files: &files,
+                diagnostic,
+                config: &super::DefaultConfig,
+            };
+            let document = Component(components::Diagnostic, data).into_fragment();
+
+            assert_eq!(document.to_plain_string(), document.clone().to_string().unwrap());
+        }
+    }
+
+    #[test]
+    fn test_no_color() {
+        assert_eq!(
+            String::from_utf8_lossy(&emit_with_writer(Buffer::no_color()).into_inner()),
+            unindent(&format!(
+                r##"
+                    error[E0001]: Unexpected type in `+` application
+                    - test:2:9
+                    2 | (+ test "")
+                      |         ^^ Expected integer but got string
                     - test:2:9
                     2 | (+ test "")
                       |         -- Expected integer but got string
@@ -168,6 +1185,2497 @@ mod default_emit_smoke_tests {
         );
     }
 
+    #[test]
+    fn test_emit_fmt_in_display() {
+        struct WrappedError {
+            files: SimpleReportingFiles,
+            diagnostics: Vec<Diagnostic<SimpleSpan>>,
+        }
+
+        impl fmt::Display for WrappedError {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                for diagnostic in &self.diagnostics {
+                    emit_fmt(f, &self.files, diagnostic, &DefaultConfig)?;
+                }
+
+                Ok(())
+            }
+        }
+
+        let mut files = SimpleReportingFiles::default();
+        let file = files.add("test", "(+ tést \"ü\")\n(é)\n");
+
+        let diagnostics = vec![
+            Diagnostic::new(Severity::Error, "Unexpected «string»")
+                .with_label(Label::new_primary(SimpleSpan::new(file, 10, 14)).with_message("ü"))
+                .with_label(Label::new_secondary(SimpleSpan::new(file, 3, 8))),
+            Diagnostic::new(Severity::Warning, "Unused")
+                .with_label(Label::new_primary(SimpleSpan::new(file, 16, 20))),
+        ];
+
+        let expected: String = diagnostics
+            .iter()
+            .map(|diagnostic| emit_to_string(&files, diagnostic, &DefaultConfig))
+            .collect();
+
+        assert_eq!(
+            format_diagnostic(&files, &diagnostics[0], &DefaultConfig),
+            emit_to_string(&files, &diagnostics[0], &DefaultConfig)
+        );
+        assert_eq!(WrappedError { files, diagnostics }.to_string(), expected);
+    }
+
+    #[test]
+    fn test_emit_fmt_propagates_errors() {
+        struct Full(usize);
+
+        impl fmt::Write for Full {
+            fn write_str(&mut self, s: &str) -> fmt::Result {
+                if s.len() > self.0 {
+                    return Err(fmt::Error);
+                }
+
+                self.0 -= s.len();
+                Ok(())
+            }
+        }
+
+        let mut files = SimpleReportingFiles::default();
+        let file = files.add("test", "(+ test \"\")\n");
+
+        let error = Diagnostic::new(Severity::Error, "Unexpected string")
+            .with_label(Label::new_primary(SimpleSpan::new(file, 8, 10)));
+
+        assert_eq!(emit_fmt(&mut Full(10), &files, &error, &DefaultConfig), Err(fmt::Error));
+        assert_eq!(emit_fmt(&mut Full(1000), &files, &error, &DefaultConfig), Ok(()));
+    }
+
+    #[test]
+    fn test_fmt_writer_joins_split_characters() {
+        use std::io::Write;
+
+        let mut out = String::new();
+        let mut writer = FmtWriter {
+            inner: &mut out,
+            pending: vec![],
+        };
+
+        let bytes = "aé€".as_bytes();
+        writer.write_all(&bytes[..2]).unwrap();
+        writer.write_all(&bytes[2..4]).unwrap();
+        writer.write_all(&bytes[4..]).unwrap();
+        assert!(writer.pending.is_empty());
+
+        assert!(writer.write_all(&[0xff]).is_err());
+        assert_eq!(out, "aé€");
+    }
+
+    #[derive(Debug)]
+    struct VerbosityConfig(LabelVerbosity);
+
+    impl Config for VerbosityConfig {
+        fn filename(&self, path: &Path) -> String {
+            DefaultConfig.filename(path)
+        }
+
+        fn label_verbosity(&self) -> LabelVerbosity {
+            self.0
+        }
+    }
+
+    fn emit_with_verbosity(verbosity: LabelVerbosity) -> String {
+        let writer = emit_with_writer_and_config(Buffer::no_color(), &VerbosityConfig(verbosity));
+        String::from_utf8_lossy(&writer.into_inner()).into_owned()
+    }
+
+    #[test]
+    fn test_label_verbosity_all() {
+        assert_eq!(DefaultConfig.label_verbosity(), LabelVerbosity::All);
+        assert_eq!(
+            emit_with_verbosity(LabelVerbosity::All),
+            String::from_utf8_lossy(&emit_with_writer(Buffer::no_color()).into_inner())
+        );
+    }
+
+    #[test]
+    fn test_label_verbosity_primary_only() {
+        assert_eq!(
+            emit_with_verbosity(LabelVerbosity::PrimaryOnly),
+            unindent(
+                r##"
+                    error[E0001]: Unexpected type in `+` application
+                    - test:2:9
+                    2 | (+ test "")
+                      |         ^^ Expected integer but got string
+                    warning: `+` function has no effect unless its result is used
+                    - test:2:1
+                    2 | (+ test "")
+                      | ^^^^^^^^^^^
+                "##,
+            ),
+        );
+    }
+
+    #[test]
+    fn test_label_verbosity_messages_only() {
+        assert_eq!(
+            emit_with_verbosity(LabelVerbosity::MessagesOnly),
+            unindent(
+                r##"
+                    error[E0001]: Unexpected type in `+` application
+                    - test:2:9
+                    2 | (+ test "")
+                      |         ^^ Expected integer but got string
+                      = note: Expected integer but got string
+                    warning: `+` function has no effect unless its result is used
+                    - test:2:1
+                    2 | (+ test "")
+                      | ^^^^^^^^^^^
+                "##,
+            ),
+        );
+    }
+
+    #[test]
+    fn test_no_color_rustc_style() {
+        let writer = emit_with_writer_and_config(Buffer::no_color(), &RustcConfig(0));
+
+        assert_eq!(
+            String::from_utf8_lossy(&writer.into_inner()),
+            unindent(
+                r##"
+                    error[E0001]: Unexpected type in `+` application
+                     --> test:2:9
+                      |
+                    2 | (+ test "")
+                      |         ^^ Expected integer but got string
+                      |
+                     --> test:2:9
+                      |
+                    2 | (+ test "")
+                      |         -- Expected integer but got string
+                      |
+                    warning: `+` function has no effect unless its result is used
+                     --> test:2:1
+                      |
+                    2 | (+ test "")
+                      | ^^^^^^^^^^^
+                      |
+                "##,
+            ),
+        );
+    }
+
+    fn emit_all_to_string(severities: &[Severity]) -> String {
+        let mut files = SimpleReportingFiles::default();
+        let file = files.add("test", "(+ test \"\")\n");
+
+        let diagnostics: Vec<_> = severities
+            .iter()
+            .map(|&severity| {
+                Diagnostic::new(severity, "Unexpected string")
+                    .with_label(Label::new_primary(SimpleSpan::new(file, 8, 10)))
+            })
+            .collect();
+
+        let mut buffer = Buffer::no_color();
+        emit_all(&mut buffer, &files, &diagnostics, &DefaultConfig).unwrap();
+
+        String::from_utf8_lossy(buffer.as_slice()).into_owned()
+    }
+
+    #[test]
+    fn test_emit_all() {
+        assert_eq!(
+            emit_all_to_string(&[Severity::Error, Severity::Warning, Severity::Bug]),
+            unindent(
+                r##"
+                    error: Unexpected string
+                    - test:1:9
+                    1 | (+ test "")
+                      |         ^^
+
+                    warning: Unexpected string
+                    - test:1:9
+                    1 | (+ test "")
+                      |         ^^
+
+                    bug: Unexpected string
+                    - test:1:9
+                    1 | (+ test "")
+                      |         ^^
+
+                    error: aborting due to 2 previous errors; 1 warning emitted
+                "##,
+            ),
+        );
+    }
+
+    #[test]
+    fn test_emit_all_summary() {
+        let summary = |severities: &[Severity]| {
+            emit_all_to_string(severities)
+                .lines()
+                .last()
+                .map(str::to_string)
+        };
+
+        assert_eq!(
+            summary(&[Severity::Error]).as_deref(),
+            Some("error: aborting due to 1 previous error")
+        );
+        assert_eq!(
+            summary(&[Severity::Error, Severity::Warning, Severity::Warning]).as_deref(),
+            Some("error: aborting due to 1 previous error; 2 warnings emitted")
+        );
+        assert_eq!(
+            summary(&[Severity::Warning, Severity::Note]).as_deref(),
+            Some("warning: 1 warning emitted")
+        );
+        assert_eq!(
+            summary(&[Severity::Warning, Severity::Warning]).as_deref(),
+            Some("warning: 2 warnings emitted")
+        );
+
+        // Notes and help messages on their own don't get a summary
+        assert_eq!(
+            summary(&[Severity::Note, Severity::Help]).as_deref(),
+            Some("  |         ^^")
+        );
+        assert_eq!(emit_all_to_string(&[]), "");
+    }
+
+    #[test]
+    fn test_emit_all_deduplicated() {
+        use crate::DiagnosticSet;
+
+        let mut files = SimpleReportingFiles::default();
+        let file = files.add("test", "(+ test \"\")\n");
+        let error = Diagnostic::new_error("Unexpected string")
+            .with_label(Label::new_primary(SimpleSpan::new(file, 8, 10)));
+        let warning = Diagnostic::new_warning("Unexpected string")
+            .with_label(Label::new_primary(SimpleSpan::new(file, 8, 10)));
+
+        let mut set = DiagnosticSet::new();
+        assert!(set.insert(error.clone()));
+        assert!(set.insert(warning.clone()));
+
+        for _ in 0..4 {
+            assert!(!set.insert(error.clone()));
+        }
+
+        assert_eq!(set.len(), 2);
+        assert_eq!(set.count(&error), 5);
+        assert_eq!(set.count(&warning), 1);
+
+        let mut buffer = Buffer::no_color();
+        emit_all(&mut buffer, &files, &set.into_diagnostics(), &DefaultConfig).unwrap();
+
+        assert_eq!(
+            String::from_utf8_lossy(buffer.as_slice()),
+            unindent(
+                r##"
+                    error: Unexpected string
+                    - test:1:9
+                    1 | (+ test "")
+                      |         ^^
+                      = note: this error occurs 5 times
+
+                    warning: Unexpected string
+                    - test:1:9
+                    1 | (+ test "")
+                      |         ^^
+
+                    error: aborting due to 1 previous error; 1 warning emitted
+                "##,
+            ),
+        );
+    }
+
+    #[derive(Debug)]
+    struct QuietConfig;
+
+    impl Config for QuietConfig {
+        fn filename(&self, path: &Path) -> String {
+            DefaultConfig.filename(path)
+        }
+
+        fn min_severity(&self) -> Severity {
+            Severity::Error
+        }
+    }
+
+    #[test]
+    fn test_emitter_counts() {
+        let files = SimpleReportingFiles::default();
+        let mut emitter = Emitter::new(Buffer::no_color(), &files, &DefaultConfig);
+
+        for severity in &[Severity::Warning, Severity::Note, Severity::Warning, Severity::Help] {
+            emitter.emit(&Diagnostic::new(*severity, "Unused")).unwrap();
+        }
+
+        assert!(!emitter.has_errors());
+
+        emitter.emit(&Diagnostic::new_error("Unbound")).unwrap();
+        emitter.emit(&Diagnostic::new(Severity::Bug, "Crashed")).unwrap();
+
+        assert!(emitter.has_errors());
+        assert_eq!(emitter.error_count(), 2);
+        assert_eq!(
+            *emitter.counts(),
+            SeverityCounts {
+                bugs: 1,
+                errors: 1,
+                warnings: 2,
+                notes: 1,
+                helps: 1,
+            }
+        );
+        assert_eq!(emitter.counts().get(Severity::Warning), 2);
+        assert_eq!(emitter.counts().total(), 6);
+
+        let output = emitter.finish().unwrap().into_inner();
+        assert_eq!(
+            String::from_utf8_lossy(&output),
+            "warning: Unused\nnote: Unused\nwarning: Unused\nhelp: Unused\n\
+             error: Unbound\nbug: Crashed\n"
+        );
+    }
+
+    #[test]
+    fn test_emitter_exit_code() {
+        #[derive(Debug)]
+        struct FailOnWarnings;
+
+        impl Config for FailOnWarnings {
+            fn filename(&self, path: &Path) -> String {
+                DefaultConfig.filename(path)
+            }
+
+            fn exit_code_threshold(&self) -> Severity {
+                Severity::Warning
+            }
+        }
+
+        let files = SimpleReportingFiles::default();
+        let warning: Diagnostic<SimpleSpan> = Diagnostic::new_warning("Unused");
+        let error: Diagnostic<SimpleSpan> = Diagnostic::new_error("Unbound");
+
+        for config in &[&DefaultConfig as &dyn Config, &FailOnWarnings] {
+            let emitter = Emitter::new(Buffer::no_color(), &files, *config);
+            assert_eq!(emitter.counts().worst(), None);
+            assert_eq!(emitter.exit_code(), 0);
+        }
+
+        let mut default = Emitter::new(Buffer::no_color(), &files, &DefaultConfig);
+        let mut strict = Emitter::new(Buffer::no_color(), &files, &FailOnWarnings);
+
+        for emitter in &mut [&mut default, &mut strict] {
+            emitter.emit(&Diagnostic::new_note("Defined here")).unwrap();
+            emitter.emit(&warning).unwrap();
+        }
+
+        assert_eq!(default.counts().worst(), Some(Severity::Warning));
+        assert_eq!(default.exit_code(), 0);
+        assert_eq!(strict.exit_code(), 1);
+
+        default.emit(&error).unwrap();
+        assert_eq!(default.exit_code(), 1);
+        assert_eq!(default.counts().exit_code(&FailOnWarnings), 1);
+    }
+
+    #[test]
+    fn test_emitter_severity_policy() {
+        let files = SimpleReportingFiles::default();
+        let policy = SeverityPolicy::new().deny_warnings().allow("W0002");
+        let mut emitter =
+            Emitter::new(ColorAccumulator::new(), &files, &DefaultConfig).severity_policy(policy);
+
+        let diagnostics: Vec<Diagnostic<SimpleSpan>> = vec![
+            Diagnostic::new_warning("Unused").with_code("W0001"),
+            Diagnostic::new_warning("Shadowed").with_code("W0002"),
+            Diagnostic::new_note("Defined here"),
+        ];
+
+        for diagnostic in &diagnostics {
+            emitter.emit(diagnostic).unwrap();
+        }
+
+        assert!(emitter.has_errors());
+        assert_eq!(
+            *emitter.counts(),
+            SeverityCounts {
+                errors: 1,
+                notes: 1,
+                ..SeverityCounts::default()
+            }
+        );
+
+        // The denied warning is styled as an error
+        assert_eq!(
+            emitter.finish().unwrap().to_string(),
+            "{fg:Red bold bright}error[W0001]{bold bright}: Unused{/}\n\
+             {fg:Green bold bright}note{bold bright}: Defined here{/}\n"
+        );
+    }
+
+    #[test]
+    fn test_emit_all_with_severity_policy() {
+        let files = SimpleReportingFiles::default();
+        let diagnostics: Vec<Diagnostic<SimpleSpan>> = vec![
+            Diagnostic::new_warning("Unused").with_code("W0001"),
+            Diagnostic::new_warning("Shadowed").with_code("W0002"),
+            Diagnostic::new_warning("Unreachable").with_code("W0003"),
+        ];
+
+        let summary = |policy: SeverityPolicy| {
+            let diagnostics: Vec<_> = diagnostics
+                .iter()
+                .cloned()
+                .filter_map(|diagnostic| policy.apply(diagnostic))
+                .collect();
+
+            let mut buffer = Buffer::no_color();
+            emit_all(&mut buffer, &files, &diagnostics, &DefaultConfig).unwrap();
+
+            let output = String::from_utf8_lossy(buffer.as_slice()).into_owned();
+            output.lines().last().map(str::to_string)
+        };
+
+        assert_eq!(
+            summary(SeverityPolicy::new()).as_deref(),
+            Some("warning: 3 warnings emitted")
+        );
+        assert_eq!(
+            summary(SeverityPolicy::new().deny("W0001")).as_deref(),
+            Some("error: aborting due to 1 previous error; 2 warnings emitted")
+        );
+        assert_eq!(
+            summary(SeverityPolicy::new().deny_warnings().allow("W0002")).as_deref(),
+            Some("error: aborting due to 2 previous errors")
+        );
+        assert_eq!(
+            summary(SeverityPolicy::new().allow("W0001").allow("W0002").allow("W0003")),
+            None
+        );
+    }
+
+    #[test]
+    fn test_emitter_max_errors() {
+        let files = SimpleReportingFiles::default();
+        let mut emitter = Emitter::new(Buffer::no_color(), &files, &DefaultConfig).max_errors(2);
+
+        let diagnostics: Vec<Diagnostic<SimpleSpan>> = vec![
+            Diagnostic::new_error("First"),
+            Diagnostic::new_warning("Unused"),
+            Diagnostic::new_error("Second"),
+            Diagnostic::new_error("Third"),
+            Diagnostic::new_warning("Still shown"),
+            Diagnostic::new(Severity::Bug, "Crashed"),
+        ];
+
+        for diagnostic in &diagnostics {
+            emitter.emit(diagnostic).unwrap();
+        }
+
+        assert_eq!(emitter.error_count(), 4);
+        assert_eq!(emitter.hidden_errors(), 2);
+        assert_eq!(emitter.counts().warnings, 2);
+
+        let output = emitter.finish().unwrap().into_inner();
+        assert_eq!(
+            String::from_utf8_lossy(&output),
+            "error: First\nwarning: Unused\nerror: Second\nwarning: Still shown\n\
+             note: 2 errors not shown\n"
+        );
+    }
+
+    #[test]
+    fn test_emitter_min_severity() {
+        let files = SimpleReportingFiles::default();
+        let mut emitter = Emitter::new(Buffer::no_color(), &files, &QuietConfig).max_errors(0);
+
+        emitter.emit(&Diagnostic::new_warning("Unused")).unwrap();
+        emitter.emit(&Diagnostic::new_error("Unbound")).unwrap();
+
+        assert_eq!(emitter.counts().warnings, 0);
+        assert_eq!(emitter.counts().errors, 1);
+
+        // The note about hidden errors isn't skipped, even though it's less
+        // severe than `min_severity`
+        let output = emitter.finish().unwrap().into_inner();
+        assert_eq!(String::from_utf8_lossy(&output), "note: 1 error not shown\n");
+
+        let mut emitter = Emitter::new(Buffer::no_color(), &files, &DefaultConfig).max_errors(0);
+        emitter.emit(&Diagnostic::new_error("Unbound")).unwrap();
+        assert_eq!(emitter.into_inner().into_inner(), b"");
+    }
+
+    #[test]
+    fn test_min_severity() {
+        let files = SimpleReportingFiles::default();
+        let warning: Diagnostic<SimpleSpan> = Diagnostic::new_warning("Unused variable `x`");
+        let error: Diagnostic<SimpleSpan> = Diagnostic::new_error("Unbound variable `y`");
+
+        assert_eq!(emit_to_string(&files, &warning, &QuietConfig), "");
+        assert_eq!(
+            emit_to_string(&files, &error, &QuietConfig),
+            "error: Unbound variable `y`\n"
+        );
+
+        let diagnostics = [warning.clone(), error.clone(), warning];
+        let mut buffer = Buffer::no_color();
+        emit_all(&mut buffer, &files, &diagnostics, &QuietConfig).unwrap();
+
+        assert_eq!(
+            String::from_utf8_lossy(buffer.as_slice()),
+            unindent(
+                r##"
+                    error: Unbound variable `y`
+
+                    error: aborting due to 1 previous error
+                "##,
+            ),
+        );
+    }
+
+    #[test]
+    fn test_emit_all_summary_below_min_severity() {
+        #[derive(Debug)]
+        struct BugsOnlyConfig;
+
+        impl Config for BugsOnlyConfig {
+            fn filename(&self, path: &Path) -> String {
+                DefaultConfig.filename(path)
+            }
+
+            fn min_severity(&self) -> Severity {
+                Severity::Bug
+            }
+        }
+
+        let files = SimpleReportingFiles::default();
+        let diagnostics: Vec<Diagnostic<SimpleSpan>> = vec![
+            Diagnostic::new(Severity::Bug, "ice"),
+            Diagnostic::new_error("Unbound variable `y`"),
+            Diagnostic::new(Severity::Bug, "another ice"),
+        ];
+
+        let mut buffer = Buffer::no_color();
+        emit_all(&mut buffer, &files, &diagnostics, &BugsOnlyConfig).unwrap();
+
+        assert_eq!(
+            String::from_utf8_lossy(buffer.as_slice()),
+            unindent(
+                r##"
+                    bug: ice
+
+                    bug: another ice
+
+                    error: aborting due to 2 previous errors
+                "##,
+            ),
+        );
+    }
+
+    #[test]
+    fn test_labels_are_sorted_by_position() {
+        let mut files = SimpleReportingFiles::default();
+        let file = files.add("test", "(define test 123)\n(+ test \"\")\n");
+        let other = files.add("other", "(+ 1 2)\n");
+
+        let span = |file, start, end| SimpleSpan::new(file, start, end);
+        let error = Diagnostic::new(Severity::Error, "Unexpected type in `+` application")
+            .with_label(Label::new_primary(span(file, 26, 28)).with_message("second"))
+            .with_label(Label::new_secondary(span(other, 1, 2)).with_message("fourth"))
+            .with_label(Label::new_secondary(span(file, 26, 28)).with_message("third"))
+            .with_label(Label::new_secondary(span(file, 8, 12)).with_message("first"));
+
+        assert_eq!(
+            emit_to_string(&files, &error, &DefaultConfig),
+            unindent(
+                r##"
+                    error: Unexpected type in `+` application
+                    - test:1:9
+                    1 | (define test 123)
+                      |         ---- first
+                    - test:2:9
+                    2 | (+ test "")
+                      |         ^^ second
+                    - test:2:9
+                    2 | (+ test "")
+                      |         -- third
+                    note:
+                      - other:1:2
+                      1 | (+ 1 2)
+                        |  - fourth
+                "##,
+            ),
+        );
+    }
+
+    #[test]
+    fn test_labels_in_insertion_order() {
+        #[derive(Debug)]
+        struct UnsortedConfig;
+
+        impl Config for UnsortedConfig {
+            fn filename(&self, path: &Path) -> String {
+                DefaultConfig.filename(path)
+            }
+
+            fn sort_labels(&self) -> bool {
+                false
+            }
+        }
+
+        let mut files = SimpleReportingFiles::default();
+        let file = files.add("test", "(define test 123)\n(+ test \"\")\n");
+
+        let span = |start, end| SimpleSpan::new(file, start, end);
+        let error = Diagnostic::new(Severity::Error, "Unexpected type in `+` application")
+            .with_label(Label::new_primary(span(26, 28)).with_message("used here"))
+            .with_label(Label::new_secondary(span(8, 12)).with_message("defined here"))
+            .with_label(Label::new_secondary(span(14, 17)).with_message("as an integer"));
+
+        assert_eq!(
+            emit_to_string(&files, &error, &DefaultConfig),
+            unindent(
+                r##"
+                    error: Unexpected type in `+` application
+                    - test:1:9
+                    1 | (define test 123)
+                      |         ---- defined here
+                    - test:1:15
+                    1 | (define test 123)
+                      |               --- as an integer
+                    - test:2:9
+                    2 | (+ test "")
+                      |         ^^ used here
+                "##,
+            ),
+        );
+
+        assert_eq!(
+            emit_to_string(&files, &error, &UnsortedConfig),
+            unindent(
+                r##"
+                    error: Unexpected type in `+` application
+                    - test:2:9
+                    2 | (+ test "")
+                      |         ^^ used here
+                    - test:1:9
+                    1 | (define test 123)
+                      |         ---- defined here
+                    - test:1:15
+                    1 | (define test 123)
+                      |               --- as an integer
+                "##,
+            ),
+        );
+    }
+
+    fn emit_cross_file(labels: &[(&str, bool, usize, usize, &str)]) -> String {
+        let mut files = SimpleReportingFiles::default();
+        let test = files.add("test", "(define test 123)\n(+ test \"\")\n");
+        let other = files.add("other", "(+ 1 2)\n");
+        let third = files.add("third", "(define one 1)\n(define two 2)\n");
+
+        let mut error = Diagnostic::new(Severity::Error, "Unexpected type in `+` application");
+
+        for &(name, primary, start, end, message) in labels {
+            let file = match name {
+                "test" => test,
+                "other" => other,
+                _ => third,
+            };
+
+            let span = SimpleSpan::new(file, start, end);
+            let label = if primary {
+                Label::new_primary(span)
+            } else {
+                Label::new_secondary(span)
+            };
+
+            error = error.with_label(label.with_message(message));
+        }
+
+        emit_to_string(&files, &error, &DefaultConfig)
+    }
+
+    #[test]
+    fn test_secondary_labels_in_the_same_file_are_not_grouped() {
+        assert_eq!(
+            emit_cross_file(&[
+                ("test", true, 26, 28, "expected integer"),
+                ("test", false, 8, 12, "defined here"),
+            ]),
+            unindent(
+                r##"
+                    error: Unexpected type in `+` application
+                    - test:1:9
+                    1 | (define test 123)
+                      |         ---- defined here
+                    - test:2:9
+                    2 | (+ test "")
+                      |         ^^ expected integer
+                "##,
+            ),
+        );
+    }
+
+    #[test]
+    fn test_secondary_labels_in_another_file_are_grouped_under_a_note() {
+        assert_eq!(
+            emit_cross_file(&[
+                ("other", false, 1, 2, "called here"),
+                ("test", true, 26, 28, "expected integer"),
+            ]),
+            unindent(
+                r##"
+                    error: Unexpected type in `+` application
+                    - test:2:9
+                    2 | (+ test "")
+                      |         ^^ expected integer
+                    note:
+                      - other:1:2
+                      1 | (+ 1 2)
+                        |  - called here
+                "##,
+            ),
+        );
+    }
+
+    #[test]
+    fn test_secondary_labels_in_a_third_file_share_a_note() {
+        assert_eq!(
+            emit_cross_file(&[
+                ("test", true, 26, 28, "expected integer"),
+                ("third", false, 23, 26, "second"),
+                ("other", false, 1, 2, "called here"),
+                ("third", false, 8, 11, "first"),
+            ]),
+            unindent(
+                r##"
+                    error: Unexpected type in `+` application
+                    - test:2:9
+                    2 | (+ test "")
+                      |         ^^ expected integer
+                    note:
+                      - third:1:9
+                      1 | (define one 1)
+                        |         --- first
+                      - third:2:9
+                      2 | (define two 2)
+                        |         --- second
+                    note:
+                      - other:1:2
+                      1 | (+ 1 2)
+                        |  - called here
+                "##,
+            ),
+        );
+    }
+
+    #[test]
+    fn test_label_on_unterminated_last_line() {
+        let mut files = SimpleReportingFiles::default();
+        let source = "(define test 123)\n(+ test \"\")";
+        let file = files.add("test", source);
+
+        let str_start = source.find("\"\"").unwrap();
+        let error = Diagnostic::new(Severity::Error, "Unexpected type in `+` application")
+            .with_label(
+                Label::new_primary(SimpleSpan::new(file, str_start, str_start + 2))
+                    .with_message("Expected integer but got string"),
+            );
+
+        assert_eq!(
+            emit_to_string(&files, &error, &super::DefaultConfig),
+            unindent(
+                r##"
+                    error: Unexpected type in `+` application
+                    - test:2:9
+                    2 | (+ test "")
+                      |         ^^ Expected integer but got string
+                "##,
+            ),
+        );
+    }
+
+    #[test]
+    fn test_json() {
+        let mut files = SimpleReportingFiles::default();
+
+        let source = unindent(
+            r##"
+                (define test 123)
+                (+ test "")
+                ()
+            "##,
+        );
+
+        let file = files.add("test", source);
+
+        let str_start = files.byte_index(file, 1, 8).unwrap();
+        let error = Diagnostic::new(Severity::Error, "Unexpected type in `+` application")
+            .with_label(
+                Label::new_primary(SimpleSpan::new(file, str_start, str_start + 2))
+                    .with_message("Expected integer but got string"),
+            )
+            .with_label(Label::new_secondary(SimpleSpan::new(
+                file,
+                str_start,
+                str_start + 2,
+            )))
+            .with_code("E0001")
+            .with_note("`+` only adds integers")
+            .with_help("convert the string with `parse`")
+            .with_suggestion(crate::Suggestion::new(
+                SimpleSpan::new(file, str_start, str_start + 2),
+                "0",
+                "use an integer",
+            ));
+
+        let mut writer = vec![];
+        emit_json(&mut writer, &files, &error, &super::DefaultConfig).unwrap();
+
+        let output = String::from_utf8(writer).unwrap();
+        assert!(output.ends_with('\n'));
+
+        let value: serde_json::Value = serde_json::from_str(&output).unwrap();
+
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "severity": "error",
+                "code": "E0001",
+                "message": "Unexpected type in `+` application",
+                "labels": [
+                    {
+                        "style": "primary",
+                        "message": "Expected integer but got string",
+                        "file": "test",
+                        "line": 2,
+                        "column": 9,
+                        "byte_start": 26,
+                        "byte_end": 28,
+                        "source": "\"\"",
+                    },
+                    {
+                        "style": "secondary",
+                        "message": null,
+                        "file": "test",
+                        "line": 2,
+                        "column": 9,
+                        "byte_start": 26,
+                        "byte_end": 28,
+                        "source": "\"\"",
+                    }
+                ],
+                "notes": [
+                    { "style": "note", "message": "`+` only adds integers" },
+                    { "style": "help", "message": "convert the string with `parse`" },
+                ],
+                "suggestions": [
+                    {
+                        "message": "use an integer",
+                        "replacement": "0",
+                        "file": "test",
+                        "line": 2,
+                        "column": 9,
+                        "byte_start": 26,
+                        "byte_end": 28,
+                    }
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn test_json_with_unresolved_spans() {
+        let mut files = SimpleReportingFiles::default();
+        let file = files.add("test", "abc\n");
+
+        let span = SimpleSpan::new(file, 10, 12);
+        let label = Diagnostic::new_error("Out of range").with_label(Label::new_primary(span));
+        let suggestion = Diagnostic::new_error("Out of range")
+            .with_suggestion(crate::Suggestion::new(span, "0", "use an integer"));
+
+        for (diagnostic, message) in &[
+            (label, "Couldn't resolve the label at 10..12 in test"),
+            (suggestion, "Couldn't resolve the suggestion at 10..12 in test"),
+        ] {
+            let mut writer = vec![];
+            let error = emit_json(&mut writer, &files, diagnostic, &super::DefaultConfig)
+                .unwrap_err();
+
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+            assert_eq!(error.to_string(), *message);
+            assert!(writer.is_empty());
+        }
+    }
+
+    fn emit_single_label(source: &str, marked: &str) -> String {
+        let mut files = SimpleReportingFiles::default();
+        let file = files.add("test", source);
+
+        let start = source.find(marked).unwrap();
+        let error = Diagnostic::new(Severity::Error, "Unexpected string").with_label(
+            Label::new_primary(SimpleSpan::new(file, start, start + marked.len())),
+        );
+
+        emit_to_string(&files, &error, &super::DefaultConfig)
+    }
+
+    fn diagnostic_with_notes(files: &mut SimpleReportingFiles) -> Diagnostic<SimpleSpan> {
+        let file = files.add("test", "(define test 123)\n(+ test \"\")\n");
+
+        let start = files.byte_index(file, 1, 8).unwrap();
+
+        Diagnostic::new(Severity::Error, "Unexpected type in `+` application")
+            .with_label(
+                Label::new_primary(SimpleSpan::new(file, start, start + 2))
+                    .with_message("Expected integer but got string"),
+            )
+            .with_note("`+` only adds integers")
+            .with_help("convert the string with `parse`")
+    }
+
+    #[test]
+    fn test_notes() {
+        let mut files = SimpleReportingFiles::default();
+        let error = diagnostic_with_notes(&mut files);
+
+        assert_eq!(
+            emit_to_string(&files, &error, &DefaultConfig),
+            unindent(
+                r##"
+                    error: Unexpected type in `+` application
+                    - test:2:9
+                    2 | (+ test "")
+                      |         ^^ Expected integer but got string
+                      = note: `+` only adds integers
+                      = help: convert the string with `parse`
+                "##,
+            ),
+        );
+
+        assert_eq!(
+            emit_to_string(&files, &error, &RustcConfig(0)),
+            unindent(
+                r##"
+                    error: Unexpected type in `+` application
+                     --> test:2:9
+                      |
+                    2 | (+ test "")
+                      |         ^^ Expected integer but got string
+                      |
+                      = note: `+` only adds integers
+                      = help: convert the string with `parse`
+                "##,
+            ),
+        );
+    }
+
+    #[test]
+    fn test_note_prefixes() {
+        #[derive(Debug)]
+        struct PrefixConfig;
+
+        impl Config for PrefixConfig {
+            fn filename(&self, path: &Path) -> String {
+                DefaultConfig.filename(path)
+            }
+
+            fn note_prefix(&self) -> String {
+                "info: ".to_string()
+            }
+
+            fn help_prefix(&self) -> String {
+                "hint: ".to_string()
+            }
+        }
+
+        let mut files = SimpleReportingFiles::default();
+        let error = diagnostic_with_notes(&mut files);
+
+        assert_eq!(
+            emit_to_string(&files, &error, &PrefixConfig),
+            unindent(
+                r##"
+                    error: Unexpected type in `+` application
+                    - test:2:9
+                    2 | (+ test "")
+                      |         ^^ Expected integer but got string
+                      = info: `+` only adds integers
+                      = hint: convert the string with `parse`
+                "##,
+            ),
+        );
+
+        let warning: Diagnostic<SimpleSpan> =
+            Diagnostic::new_warning("Unused variable `x`").with_help("remove it");
+
+        assert_eq!(
+            emit_to_string(&files, &warning, &DefaultConfig),
+            "warning: Unused variable `x`\n = help: remove it\n"
+        );
+    }
+
+    #[test]
+    fn test_header_events() {
+        let writer = emit_with_writer(RecordingWriter::new());
+
+        let end = writer
+            .events()
+            .iter()
+            .position(|event| *event == Event::Write("\n".to_string()))
+            .unwrap();
+
+        let mut red = termcolor::ColorSpec::new();
+        red.set_fg(Some(termcolor::Color::Red))
+            .set_bold(true)
+            .set_intense(true);
+
+        let mut bold = termcolor::ColorSpec::new();
+        bold.set_bold(true).set_intense(true);
+
+        let write = |text: &str| Event::Write(text.to_string());
+
+        assert_eq!(
+            &writer.events()[..=end],
+            &[
+                Event::Reset,
+                Event::SetColor(red.clone()),
+                write("error"),
+                Event::SetColor(red),
+                write("[E0001]"),
+                Event::SetColor(bold.clone()),
+                write(": "),
+                Event::SetColor(bold),
+                write("Unexpected type in `+` application"),
+                Event::Reset,
+                write("\n"),
+            ]
+        );
+    }
+
+    #[derive(Debug)]
+    struct ThemeConfig(Theme);
+
+    impl Config for ThemeConfig {
+        fn filename(&self, path: &Path) -> String {
+            DefaultConfig.filename(path)
+        }
+
+        fn theme(&self) -> Theme {
+            self.0
+        }
+    }
+
+    fn emit_with_theme(theme: Theme) -> String {
+        emit_themed(&ThemeConfig(theme))
+    }
+
+    fn emit_themed(config: &dyn Config) -> String {
+        let mut files = SimpleReportingFiles::default();
+        let file = files.add("test", "(+ test \"\")\n");
+
+        let warning = Diagnostic::new(Severity::Warning, "Unused value")
+            .with_label(Label::new_primary(SimpleSpan::new(file, 8, 10)))
+            .with_label(Label::new_secondary(SimpleSpan::new(file, 0, 2)).with_message("here"))
+            .with_help("remove it");
+
+        let mut writer = ColorAccumulator::new();
+        emit(&mut writer, &files, &warning, config).unwrap();
+        writer.to_string()
+    }
+
+    #[test]
+    fn test_dark_theme_is_the_default() {
+        assert_eq!(DefaultConfig.theme(), Theme::Dark);
+        assert_eq!(emit_with_theme(Theme::Dark), emit_themed(&DefaultConfig));
+    }
+
+    #[test]
+    fn test_light_theme() {
+        assert_eq!(
+            emit_with_theme(Theme::Light),
+            unindent(
+                r##"
+                    {fg:Ansi256(130) bold bright}warning{bold bright}: Unused value{/}
+                    - test:1:1
+                    {fg:Ansi256(240)}1 | {fg:Ansi256(25)}(+{/} test "")
+                    {fg:Ansi256(240)}  | {fg:Ansi256(25)}-- here{/}
+                    - test:1:9
+                    {fg:Ansi256(240)}1 | {/}(+ test {fg:Ansi256(130)}""{/})
+                    {fg:Ansi256(240)}  | {/}        {fg:Ansi256(130)}^^{/}
+                    {fg:Ansi256(240)}  ={/} {bold bright}help: {/}remove it
+                "##,
+            ),
+        );
+    }
+
+    #[test]
+    fn test_plain_theme() {
+        assert_eq!(
+            emit_with_theme(Theme::Plain),
+            unindent(
+                r##"
+                    {bold bright}warning: Unused value{/}
+                    - test:1:1
+                    1 | {underline}(+{/} test "")
+                      | {underline}-- here{/}
+                    - test:1:9
+                    1 | (+ test {bold bright}""{/})
+                      |         {bold bright}^^{/}
+                      = {bold bright}help: {/}remove it
+                "##,
+            ),
+        );
+    }
+
+    #[test]
+    fn test_custom_stylesheet() {
+        #[derive(Debug)]
+        struct ThemeConfig;
+
+        impl Config for ThemeConfig {
+            fn filename(&self, path: &Path) -> String {
+                DefaultConfig.filename(path)
+            }
+
+            fn stylesheet(&self) -> Stylesheet {
+                Stylesheet::new()
+                    .add("error ** primary", "fg: magenta")
+                    .add("** gutter", "fg: green")
+            }
+        }
+
+        let mut files = SimpleReportingFiles::default();
+        let file = files.add("test", "(+ test \"\")\n");
+
+        let error = Diagnostic::new(Severity::Error, "Unexpected type in `+` application")
+            .with_label(Label::new_primary(SimpleSpan::new(file, 8, 10)));
+
+        let mut writer = ColorAccumulator::new();
+        emit(&mut writer, &files, &error, &ThemeConfig).unwrap();
+
+        assert_eq!(
+            writer.to_string(),
+            unindent(
+                r##"
+                    {fg:Magenta}error{/}: Unexpected type in `+` application
+                    - test:1:9
+                    {fg:Green}1 | {/}(+ test {fg:Magenta}""{/})
+                    {fg:Green}  | {/}        {fg:Magenta}^^{/}
+                "##,
+            ),
+        );
+
+        assert_eq!(
+            emit_to_string(&files, &error, &ThemeConfig),
+            emit_to_string(&files, &error, &DefaultConfig)
+        );
+    }
+
+    fn emit_suggestions(suggestions: Vec<crate::Suggestion<SimpleSpan>>) -> String {
+        emit_suggestions_with(suggestions, &DefaultConfig)
+    }
+
+    fn emit_suggestions_with(
+        suggestions: Vec<crate::Suggestion<SimpleSpan>>,
+        config: &dyn Config,
+    ) -> String {
+        let mut files = SimpleReportingFiles::default();
+        let file = files.add("test", "(+ test \"\")\n");
+
+        let error = Diagnostic::new(Severity::Error, "Unexpected type in `+` application")
+            .with_label(Label::new_primary(SimpleSpan::new(file, 8, 10)));
+
+        let error = suggestions
+            .into_iter()
+            .fold(error, |error, suggestion| error.with_suggestion(suggestion));
+
+        emit_to_string(&files, &error, config)
+    }
+
+    #[test]
+    fn test_suggestions() {
+        use crate::Suggestion;
+
+        assert_eq!(
+            emit_suggestions(vec![
+                Suggestion::new(SimpleSpan::new(0, 8, 10), "0", "use an integer"),
+                Suggestion::new(SimpleSpan::new(0, 3, 3), "1 ", "add another integer"),
+                Suggestion::new(SimpleSpan::new(0, 7, 10), "", "remove the string"),
+            ]),
+            unindent(
+                r##"
+                    error: Unexpected type in `+` application
+                    - test:1:9
+                    1 | (+ test "")
+                      |         ^^
+                    help: use an integer
+                    - test:1:9
+                    1 | (+ test 0)
+                      |         ~
+                    help: add another integer
+                    - test:1:4
+                    1 | (+ 1 test "")
+                      |    ++
+                    help: remove the string
+                    - test:1:8
+                    1 | (+ test "")
+                      |        ---
+                "##,
+            ),
+        );
+    }
+
+    #[test]
+    fn test_suggestions_rustc_style() {
+        use crate::Suggestion;
+
+        assert_eq!(
+            emit_suggestions_with(
+                vec![Suggestion::new(SimpleSpan::new(0, 8, 10), "0", "use an integer")],
+                &RustcConfig(0),
+            ),
+            unindent(
+                r##"
+                    error: Unexpected type in `+` application
+                     --> test:1:9
+                      |
+                    1 | (+ test "")
+                      |         ^^
+                      |
+                    help: use an integer
+                     --> test:1:9
+                      |
+                    1 | (+ test 0)
+                      |         ~
+                      |
+                "##,
+            ),
+        );
+    }
+
+    #[test]
+    fn test_suggestion_gutter_matches_the_diagnostic() {
+        use crate::Suggestion;
+
+        let mut files = SimpleReportingFiles::default();
+        let source = "(+ test \"\")\n".repeat(10);
+        let file = files.add("test", source.clone());
+        let last_line = files.byte_index(file, 9, 8).unwrap();
+
+        let error = Diagnostic::new(Severity::Error, "Unexpected type in `+` application")
+            .with_label(Label::new_primary(SimpleSpan::new(file, last_line, last_line + 2)))
+            .with_suggestion(Suggestion::new(SimpleSpan::new(file, 8, 10), "0", "use an integer"));
+
+        assert_eq!(
+            emit_to_string(&files, &error, &DefaultConfig),
+            unindent(
+                r##"
+                    error: Unexpected type in `+` application
+                    - test:10:9
+                    10 | (+ test "")
+                       |         ^^
+                    help: use an integer
+                    - test:1:9
+                    1  | (+ test 0)
+                       |         ~
+                "##,
+            ),
+        );
+
+        assert_eq!(
+            emit_to_string(&files, &error, &RustcConfig(0)),
+            unindent(
+                r##"
+                    error: Unexpected type in `+` application
+                      --> test:10:9
+                       |
+                    10 | (+ test "")
+                       |         ^^
+                       |
+                    help: use an integer
+                      --> test:1:9
+                       |
+                     1 | (+ test 0)
+                       |         ~
+                       |
+                "##,
+            ),
+        );
+    }
+
+    #[test]
+    fn test_deletion_suggestions_are_struck_through() {
+        let mut files = SimpleReportingFiles::default();
+        let file = files.add("test", "(+ test \"\")\n");
+
+        let error: Diagnostic<SimpleSpan> = Diagnostic::new_error("Unexpected string")
+            .with_suggestion(crate::Suggestion::new(
+                SimpleSpan::new(file, 7, 10),
+                "",
+                "remove the string",
+            ));
+
+        let ansi = emit_ansi_string(&files, &error, &DefaultConfig);
+
+        assert!(ansi.contains("\x1b[9m \"\""));
+        assert!(!emit_to_string(&files, &error, &DefaultConfig).contains('\x1b'));
+    }
+
+    #[test]
+    fn test_location_base() {
+        #[derive(Debug)]
+        struct ZeroBasedConfig;
+
+        impl Config for ZeroBasedConfig {
+            fn filename(&self, path: &Path) -> String {
+                DefaultConfig.filename(path)
+            }
+
+            fn location_base(&self) -> usize {
+                0
+            }
+        }
+
+        let mut files = SimpleReportingFiles::default();
+        let file = files.add("test", "(define test 123)\n(+ test \"\")\n");
+
+        let start = files.byte_index(file, 1, 8).unwrap();
+        let error = Diagnostic::new(Severity::Error, "Unexpected string")
+            .with_label(Label::new_primary(SimpleSpan::new(file, start, start + 2)));
+
+        assert_eq!(
+            emit_to_string(&files, &error, &DefaultConfig),
+            unindent(
+                r##"
+                    error: Unexpected string
+                    - test:2:9
+                    2 | (+ test "")
+                      |         ^^
+                "##,
+            ),
+        );
+
+        assert_eq!(
+            emit_to_string(&files, &error, &ZeroBasedConfig),
+            unindent(
+                r##"
+                    error: Unexpected string
+                    - test:1:8
+                    2 | (+ test "")
+                      |         ^^
+                "##,
+            ),
+        );
+    }
+
+    #[derive(Debug)]
+    struct RangeConfig;
+
+    impl Config for RangeConfig {
+        fn filename(&self, path: &Path) -> String {
+            DefaultConfig.filename(path)
+        }
+
+        fn location_range(&self) -> bool {
+            true
+        }
+    }
+
+    fn emit_range(start: (usize, usize), end: (usize, usize)) -> String {
+        let mut files = SimpleReportingFiles::default();
+        let file = files.add("test", "(define test 123)\n(+ test \"\")\n()\n(ok)\n");
+
+        let start = files.byte_index(file, start.0, start.1).unwrap();
+        let end = files.byte_index(file, end.0, end.1).unwrap();
+        let error = Diagnostic::new(Severity::Error, "Unexpected string")
+            .with_label(Label::new_primary(SimpleSpan::new(file, start, end)));
+
+        // The location line
+        emit_to_string(&files, &error, &RangeConfig)
+            .lines()
+            .nth(1)
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn test_location_range() {
+        assert!(!DefaultConfig.location_range());
+
+        // On one line
+        assert_eq!(emit_range((1, 8), (1, 10)), "- test:2:9-2:11");
+
+        // Across lines
+        assert_eq!(emit_range((1, 8), (3, 2)), "- test:2:9-4:3");
+
+        // Up to the newline, and through it
+        assert_eq!(emit_range((1, 8), (1, 11)), "- test:2:9-2:12");
+        assert_eq!(emit_range((1, 8), (2, 0)), "- test:2:9-3:1");
+    }
+
+    #[test]
+    fn test_location_range_rustc_style() {
+        #[derive(Debug)]
+        struct RustcRangeConfig;
+
+        impl Config for RustcRangeConfig {
+            fn filename(&self, path: &Path) -> String {
+                DefaultConfig.filename(path)
+            }
+
+            fn location_range(&self) -> bool {
+                true
+            }
+
+            fn rustc_style(&self) -> bool {
+                true
+            }
+        }
+
+        let mut files = SimpleReportingFiles::default();
+        let file = files.add("test", "(define test 123)\n(+ test \"\")\n");
+
+        let start = files.byte_index(file, 1, 8).unwrap();
+        let error = Diagnostic::new(Severity::Error, "Unexpected string")
+            .with_label(Label::new_primary(SimpleSpan::new(file, start, start + 2)));
+
+        let output = emit_to_string(&files, &error, &RustcRangeConfig);
+
+        assert!(output.contains(" --> test:2:9-2:11\n"));
+    }
+
+    #[test]
+    fn test_custom_severity_name() {
+        #[derive(Debug)]
+        struct LintConfig;
+
+        impl Config for LintConfig {
+            fn filename(&self, path: &Path) -> String {
+                DefaultConfig.filename(path)
+            }
+
+            fn severity_name(&self, severity: Severity) -> String {
+                match severity {
+                    Severity::Warning => "lint".to_string(),
+                    other => DefaultConfig.severity_name(other),
+                }
+            }
+        }
+
+        let files = SimpleReportingFiles::default();
+        let warning: Diagnostic<SimpleSpan> = Diagnostic::new_warning("Unused variable `x`");
+        let error: Diagnostic<SimpleSpan> = Diagnostic::new_error("Unbound variable `y`");
+
+        assert_eq!(
+            emit_to_string(&files, &warning, &LintConfig),
+            "lint: Unused variable `x`\n"
+        );
+        assert_eq!(
+            emit_to_string(&files, &error, &LintConfig),
+            "error: Unbound variable `y`\n"
+        );
+
+        let mut writer = ColorAccumulator::new();
+        emit(&mut writer, &files, &warning, &LintConfig).unwrap();
+
+        assert_eq!(
+            writer.to_string(),
+            "{fg:Yellow bold bright}lint{bold bright}: Unused variable `x`{/}\n"
+        );
+    }
+
+    #[derive(Debug)]
+    struct GermanConfig(bool);
+
+    impl Config for GermanConfig {
+        fn filename(&self, path: &Path) -> String {
+            DefaultConfig.filename(path)
+        }
+
+        fn severity_name(&self, severity: Severity) -> String {
+            match severity {
+                Severity::Bug => "Programmfehler",
+                Severity::Error => "Fehler",
+                Severity::Warning => "Warnung",
+                Severity::Note => "Hinweis",
+                Severity::Help => "Hilfe",
+            }
+            .to_string()
+        }
+
+        fn code_display(&self, code: &str) -> String {
+            format!(" ({})", code)
+        }
+
+        fn gutter_separator(&self) -> String {
+            " │ ".to_string()
+        }
+
+        fn location_prefix(&self) -> String {
+            "→ ".to_string()
+        }
+
+        fn rustc_style(&self) -> bool {
+            self.0
+        }
+    }
+
+    #[test]
+    fn test_localized_config() {
+        let mut files = SimpleReportingFiles::default();
+        let file = files.add("test", "(+ test \"\")\n");
+        let diagnostic = Diagnostic::new_error("Unerwarteter Typ")
+            .with_code("E0001")
+            .with_label(
+                Label::new_primary(SimpleSpan::new(file, 8, 10))
+                    .with_message("Ganzzahl erwartet"),
+            );
+
+        assert_eq!(
+            emit_to_string(&files, &diagnostic, &GermanConfig(false)),
+            unindent(
+                r##"
+                    Fehler (E0001): Unerwarteter Typ
+                    → test:1:9
+                    1 │ (+ test "")
+                      │         ^^ Ganzzahl erwartet
+                "##,
+            ),
+        );
+
+        assert_eq!(
+            emit_to_string(&files, &diagnostic, &GermanConfig(true)),
+            unindent(
+                r##"
+                    Fehler (E0001): Unerwarteter Typ
+                     --> test:1:9
+                      │
+                    1 │ (+ test "")
+                      │         ^^ Ganzzahl erwartet
+                      │
+                "##,
+            ),
+        );
+    }
+
+    #[derive(Debug)]
+    struct ContextConfig(usize);
+
+    impl Config for ContextConfig {
+        fn filename(&self, path: &Path) -> String {
+            DefaultConfig.filename(path)
+        }
+
+        fn context_lines(&self) -> usize {
+            self.0
+        }
+    }
+
+    fn emit_with_context(context_lines: usize, line: usize) -> String {
+        let mut files = SimpleReportingFiles::default();
+
+        let source: String = (1..=10).map(|n| format!("(line {})\n", n)).collect();
+        let file = files.add("test", source);
+
+        let start = files.byte_index(file, line, 1).unwrap();
+        let error = Diagnostic::new(Severity::Error, "Unexpected line")
+            .with_label(Label::new_primary(SimpleSpan::new(file, start, start + 4)));
+
+        emit_to_string(&files, &error, &ContextConfig(context_lines))
+    }
+
+    #[test]
+    fn test_context_lines() {
+        assert_eq!(
+            emit_with_context(1, 4),
+            unindent(
+                r##"
+                    error: Unexpected line
+                    - test:5:2
+                    4 | (line 4)
+                    5 | (line 5)
+                      |  ^^^^
+                    6 | (line 6)
+                "##,
+            ),
+        );
+    }
+
+    #[test]
+    fn test_context_lines_are_omitted_outside_the_file() {
+        assert_eq!(
+            emit_with_context(2, 0),
+            unindent(
+                r##"
+                    error: Unexpected line
+                    - test:1:2
+                    1 | (line 1)
+                      |  ^^^^
+                    2 | (line 2)
+                    3 | (line 3)
+                "##,
+            ),
+        );
+
+        assert_eq!(
+            emit_with_context(2, 9),
+            unindent(
+                r##"
+                    error: Unexpected line
+                    - test:10:2
+                    8  | (line 8)
+                    9  | (line 9)
+                    10 | (line 10)
+                       |  ^^^^
+                "##,
+            ),
+        );
+    }
+
+    #[test]
+    fn test_context_lines_widen_the_gutter() {
+        assert_eq!(
+            emit_with_context(1, 8),
+            unindent(
+                r##"
+                    error: Unexpected line
+                    - test:9:2
+                    8  | (line 8)
+                    9  | (line 9)
+                       |  ^^^^
+                    10 | (line 10)
+                "##,
+            ),
+        );
+    }
+
+    #[test]
+    fn test_context_lines_are_not_repeated_for_adjacent_labels() {
+        let mut files = SimpleReportingFiles::default();
+
+        let source: String = (1..=10).map(|n| format!("(line {})\n", n)).collect();
+        let file = files.add("test", source);
+
+        let label = |line| {
+            let start = files.byte_index(file, line, 1).unwrap();
+            Label::new_primary(SimpleSpan::new(file, start, start + 4))
+        };
+
+        let error = Diagnostic::new(Severity::Error, "Unexpected lines")
+            .with_label(label(3))
+            .with_label(label(4));
+
+        assert_eq!(
+            emit_to_string(&files, &error, &ContextConfig(1)),
+            unindent(
+                r##"
+                    error: Unexpected lines
+                    - test:4:2
+                    3 | (line 3)
+                    4 | (line 4)
+                      |  ^^^^
+                    - test:5:2
+                    5 | (line 5)
+                      |  ^^^^
+                    6 | (line 6)
+                "##,
+            ),
+        );
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_context_lines_in_color() {
+        let mut files = SimpleReportingFiles::default();
+        let file = files.add("test", "(define test 123)\n(+ test \"\")\n");
+
+        let error = Diagnostic::new(Severity::Error, "Unexpected type")
+            .with_label(Label::new_primary(SimpleSpan::new(file, 26, 28)));
+
+        let mut writer = ColorAccumulator::new();
+        emit(&mut writer, &files, &error, &ContextConfig(1)).unwrap();
+
+        assert_eq!(
+            writer.to_string(),
+            normalize(
+                r#"
+                   {fg:Red bold bright} $$error{bold bright}: Unexpected type{/}
+                                        $$- test:2:9
+                              {fg:Blue} $$1 | {/}(define test 123)
+                              {fg:Blue} $$2 | {/}(+ test {fg:Red}""{/})
+                              {fg:Blue} $$  | {/}        {fg:Red}^^{/}
+                "#
+            )
+        );
+    }
+
+    #[test]
+    fn test_messages_escaped_for_gnu_make() {
+        #[derive(Debug)]
+        struct MakeConfig;
+
+        impl Config for MakeConfig {
+            fn filename(&self, path: &Path) -> String {
+                DefaultConfig.filename(path)
+            }
+
+            fn escape_messages_for(&self) -> Option<ParserDialect> {
+                Some(ParserDialect::GnuMake)
+            }
+
+            fn wrap_width(&self) -> Option<usize> {
+                Some(20)
+            }
+        }
+
+        let mut files = SimpleReportingFiles::default();
+        let file = files.add("test", "(+ test \"\")\n");
+
+        let error = Diagnostic::new(Severity::Error, "expected: integer\r\nfound: C:\\string")
+            .with_label(Label::new_primary(SimpleSpan::new(file, 8, 10)));
+
+        assert_eq!(
+            emit_to_string(&files, &error, &MakeConfig),
+            unindent(
+                r##"
+                    error: expected\: integer\nfound\: C\:\\string
+                    - test:1:9
+                    1 | (+ test "")
+                      |         ^^
+                "##,
+            ),
+        );
+    }
+
+    #[test]
+    fn test_gnu_make_escape_borrows_safe_messages() {
+        let escaped = ParserDialect::GnuMake.escape("Unexpected type in `+` application");
+
+        assert!(matches!(escaped, Cow::Borrowed(_)));
+        assert_eq!(ParserDialect::GnuMake.escape("a:b\nc"), "a\\:b\\nc");
+    }
+
+    #[test]
+    fn test_wrapped_header() {
+        #[derive(Debug)]
+        struct NarrowConfig;
+
+        impl Config for NarrowConfig {
+            fn filename(&self, path: &Path) -> String {
+                DefaultConfig.filename(path)
+            }
+
+            fn wrap_width(&self) -> Option<usize> {
+                Some(40)
+            }
+        }
+
+        let files = SimpleReportingFiles::default();
+        let error: Diagnostic<SimpleSpan> = Diagnostic::new_error(
+            "`+` function has no effect unless its result is used, so you probably meant to bind it",
+        )
+        .with_code("E0002");
+
+        assert_eq!(
+            emit_to_string(&files, &error, &NarrowConfig),
+            unindent(
+                r##"
+                    error[E0002]: `+` function has no effect
+                                  unless its result is used,
+                                  so you probably meant to
+                                  bind it
+                "##,
+            ),
+        );
+    }
+
+    #[test]
+    fn test_wrapped_label_messages() {
+        #[derive(Debug)]
+        struct NarrowConfig(bool);
+
+        impl Config for NarrowConfig {
+            fn filename(&self, path: &Path) -> String {
+                DefaultConfig.filename(path)
+            }
+
+            fn wrap_width(&self) -> Option<usize> {
+                Some(40)
+            }
+
+            fn rustc_style(&self) -> bool {
+                self.0
+            }
+        }
+
+        let mut files = SimpleReportingFiles::default();
+        let file = files.add("test", "(define test 123)\n(+ test \"\")\n");
+
+        let str_start = files.byte_index(file, 1, 8).unwrap();
+        let error = Diagnostic::new_error("Unexpected type in `+`")
+            .with_label(
+                Label::new_primary(SimpleSpan::new(file, str_start, str_start + 2))
+                    .with_message("Expected integer but got string, which can't be added"),
+            )
+            .with_label(
+                Label::new_secondary(SimpleSpan::new(file, 8, 12))
+                    .with_message("test is defined here"),
+            );
+
+        assert_eq!(
+            emit_to_string(&files, &error, &NarrowConfig(false)),
+            unindent(
+                r##"
+                    error: Unexpected type in `+`
+                    - test:1:9
+                    1 | (define test 123)
+                      |         ---- test is defined here
+                    - test:2:9
+                    2 | (+ test "")
+                      |         ^^ Expected integer but got
+                      |            string, which can't be
+                      |            added
+                "##,
+            ),
+        );
+
+        assert_eq!(
+            emit_to_string(&files, &error, &NarrowConfig(true)),
+            unindent(
+                r##"
+                    error: Unexpected type in `+`
+                     --> test:1:9
+                      |
+                    1 | (define test 123)
+                      |         ---- test is defined here
+                      |
+                     --> test:2:9
+                      |
+                    2 | (+ test "")
+                      |         ^^ Expected integer but got
+                      |            string, which can't be
+                      |            added
+                      |
+                "##,
+            ),
+        );
+    }
+
+    fn emit_with_line_ending(line_ending: &str) -> String {
+        let mut files = SimpleReportingFiles::default();
+
+        let source = ["(define test 123)", "(+ test \"\")", "()", "(done)", ""].join(line_ending);
+        let file = files.add("test", source);
+
+        let str_start = files.byte_index(file, 1, 8).unwrap();
+        let line_start = files.byte_index(file, 2, 0).unwrap();
+        let line_end = files.byte_index(file, 0, 17).unwrap();
+
+        let error = Diagnostic::new(Severity::Error, "Unexpected type in `+` application")
+            .with_label(
+                Label::new_primary(SimpleSpan::new(file, str_start, str_start + 2))
+                    .with_message("Expected integer but got string"),
+            )
+            .with_label(
+                Label::new_secondary(SimpleSpan::new(file, line_start, line_start + 2))
+                    .with_message("Starts a line"),
+            )
+            .with_label(
+                Label::new_secondary(SimpleSpan::new(
+                    file,
+                    line_end - 1,
+                    line_end + line_ending.len(),
+                ))
+                .with_message("Reaches the end of a line"),
+            );
+
+        emit_to_string(&files, &error, &ContextConfig(1))
+    }
+
+    #[test]
+    fn test_crlf_line_endings() {
+        assert_eq!(emit_with_line_ending("\r\n"), emit_with_line_ending("\n"));
+        assert_eq!(
+            emit_with_line_ending("\r\n"),
+            unindent(
+                r##"
+                    error: Unexpected type in `+` application
+                    - test:1:17
+                    1 | (define test 123)
+                      |                 - Reaches the end of a line
+                    - test:2:9
+                    2 | (+ test "")
+                      |         ^^ Expected integer but got string
+                    - test:3:1
+                    3 | ()
+                      | -- Starts a line
+                    4 | (done)
+                "##,
+            ),
+        );
+    }
+
+    #[derive(Debug)]
+    struct UnderlineConfig(bool);
+
+    impl Config for UnderlineConfig {
+        fn filename(&self, path: &Path) -> String {
+            DefaultConfig.filename(path)
+        }
+
+        fn show_underline(&self) -> bool {
+            self.0
+        }
+    }
+
+    #[test]
+    fn test_show_underline() {
+        let mut files = SimpleReportingFiles::default();
+        let file = files.add("test", "(define test 123)\n(+ test \"\")\n");
+
+        let error = Diagnostic::new(Severity::Error, "Unexpected type in `+` application")
+            .with_label(
+                Label::new_primary(SimpleSpan::new(file, 26, 28))
+                    .with_message("Expected integer but got string"),
+            )
+            .with_label(Label::new_secondary(SimpleSpan::new(file, 18, 19)));
+
+        assert_eq!(
+            emit_to_string(&files, &error, &UnderlineConfig(true)),
+            unindent(
+                r##"
+                    error: Unexpected type in `+` application
+                    - test:2:1
+                    2 | (+ test "")
+                      | -
+                    - test:2:9
+                    2 | (+ test "")
+                      |         ^^ Expected integer but got string
+                "##,
+            ),
+        );
+
+        assert_eq!(
+            emit_to_string(&files, &error, &UnderlineConfig(false)),
+            unindent(
+                r##"
+                    error: Unexpected type in `+` application
+                    - test:2:1
+                    2 | (+ test "")
+                    - test:2:9
+                    2 | (+ test "") Expected integer but got string
+                "##,
+            ),
+        );
+    }
+
+    #[derive(Debug)]
+    struct MaxWidthConfig(usize);
+
+    impl Config for MaxWidthConfig {
+        fn filename(&self, path: &Path) -> String {
+            DefaultConfig.filename(path)
+        }
+
+        fn context_lines(&self) -> usize {
+            1
+        }
+
+        fn max_line_width(&self) -> Option<usize> {
+            Some(self.0)
+        }
+    }
+
+    fn emit_long_line(start: usize, end: usize) -> String {
+        let mut files = SimpleReportingFiles::default();
+
+        let numbers: Vec<String> = (10..60).map(|n| n.to_string()).collect();
+        let line = format!("[{}]", numbers.join(", "));
+        assert_eq!(line.len(), 200);
+
+        let file = files.add("test", format!("{}\n{}\n", line, line));
+        let error = Diagnostic::new(Severity::Error, "Unexpected number")
+            .with_label(Label::new_primary(SimpleSpan::new(file, start, end)));
+
+        emit_to_string(&files, &error, &MaxWidthConfig(40))
+    }
+
+    #[test]
+    fn test_max_line_width() {
+        // The `35` in the middle of the line
+        assert_eq!(
+            emit_long_line(101, 103),
+            unindent(
+                r##"
+                    error: Unexpected number
+                    - test:1:102
+                    1 | …, 31, 32, 33, 34, 35, 36, 37, 38, 39, …
+                      |                    ^^
+                    2 | [10, 11, 12, 13, 14, 15, 16, 17, 18, 19…
+                "##,
+            ),
+        );
+
+        // The `10` at the start of the line
+        assert_eq!(
+            emit_long_line(1, 3),
+            unindent(
+                r##"
+                    error: Unexpected number
+                    - test:1:2
+                    1 | [10, 11, 12, 13, 14, 15, 16, 17, 18, 19…
+                      |  ^^
+                    2 | [10, 11, 12, 13, 14, 15, 16, 17, 18, 19…
+                "##,
+            ),
+        );
+    }
+
+    #[test]
+    fn test_max_line_width_with_a_wide_label() {
+        // Everything from `20` to `49`, which is wider than the line
+        assert_eq!(
+            emit_long_line(41, 159),
+            unindent(
+                r##"
+                    error: Unexpected number
+                    - test:1:42
+                    1 | …20, 21, 22, 23, 24,…45, 46, 47, 48, 49…
+                      |  ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^
+                    2 | [10, 11, 12, 13, 14, 15, 16, 17, 18, 19…
+                "##,
+            ),
+        );
+    }
+
+    #[test]
+    fn test_max_line_width_with_a_label_at_the_end() {
+        let mut files = SimpleReportingFiles::default();
+
+        let line = format!("{}oops", "x".repeat(496));
+        assert_eq!(line.len(), 500);
+
+        let file = files.add("test", format!("{}\n", line));
+        let error = Diagnostic::new(Severity::Error, "Unexpected oops").with_label(
+            Label::new_primary(SimpleSpan::new(file, 496, 500)).with_message("here"),
+        );
+
+        assert_eq!(
+            emit_to_string(&files, &error, &MaxWidthConfig(20)),
+            unindent(
+                r##"
+                    error: Unexpected oops
+                    - test:1:497
+                    1 | …xxxxxxxxxxxxxxxoops
+                      |                 ^^^^ here
+                "##,
+            ),
+        );
+    }
+
+    #[test]
+    fn test_emit_ansi_string() {
+        let mut files = SimpleReportingFiles::default();
+        let file = files.add("test", "(+ test \"\")\n");
+
+        let error = Diagnostic::new(Severity::Error, "Unexpected type in `+` application")
+            .with_label(Label::new_primary(SimpleSpan::new(file, 8, 10)));
+
+        let plain = emit_to_string(&files, &error, &super::DefaultConfig);
+        let ansi = emit_ansi_string(&files, &error, &super::DefaultConfig);
+
+        assert!(!plain.contains('\x1b'));
+        assert!(ansi.contains("\x1b["));
+        assert_eq!(
+            regex::Regex::new("\x1b\\[[0-9;]*m")
+                .unwrap()
+                .replace_all(&ansi, ""),
+            plain
+        );
+    }
+
+    #[derive(Debug)]
+    struct MarkConfig(&'static str, &'static str);
+
+    impl Config for MarkConfig {
+        fn filename(&self, path: &Path) -> String {
+            DefaultConfig.filename(path)
+        }
+
+        fn primary_mark(&self) -> String {
+            self.0.to_string()
+        }
+
+        fn secondary_mark(&self) -> String {
+            self.1.to_string()
+        }
+    }
+
+    fn emit_with_marks(config: &MarkConfig) -> String {
+        let mut files = SimpleReportingFiles::default();
+        let file = files.add("test", "(+ test \"\")\n");
+
+        let error = Diagnostic::new(Severity::Error, "Unexpected type in `+` application")
+            .with_label(Label::new_primary(SimpleSpan::new(file, 8, 10)).with_message("string"))
+            .with_label(Label::new_secondary(SimpleSpan::new(file, 3, 7)).with_message("name"))
+            .with_label(Label::new_secondary(SimpleSpan::new(file, 1, 1)).with_message("empty"));
+
+        emit_to_string(&files, &error, config)
+    }
+
+    #[test]
+    fn test_custom_marks() {
+        assert_eq!(
+            emit_with_marks(&MarkConfig("^", "~")),
+            unindent(
+                r##"
+                    error: Unexpected type in `+` application
+                    - test:1:2
+                    1 | (+ test "")
+                      |  ~ empty
+                    - test:1:4
+                    1 | (+ test "")
+                      |    ~~~~ name
+                    - test:1:9
+                    1 | (+ test "")
+                      |         ^^ string
+                "##,
+            ),
+        );
+    }
+
+    #[test]
+    fn test_multi_byte_and_multi_column_marks() {
+        assert_eq!(
+            emit_with_marks(&MarkConfig("\u{2594}", "~-")),
+            unindent(
+                r##"
+                    error: Unexpected type in `+` application
+                    - test:1:2
+                    1 | (+ test "")
+                      |  ~ empty
+                    - test:1:4
+                    1 | (+ test "")
+                      |    ~-~- name
+                    - test:1:9
+                    1 | (+ test "")
+                      |         ▔▔ string
+                "##,
+            ),
+        );
+    }
+
+    #[test]
+    fn test_zero_width_labels() {
+        let mut files = SimpleReportingFiles::default();
+        let file = files.add("test", "let x = 1\nlet y = 2\n");
+
+        let error = Diagnostic::new(Severity::Error, "Expected `;`")
+            .with_label(
+                Label::new_primary(SimpleSpan::new(file, 9, 9)).with_message("insert `;` here"),
+            )
+            .with_label(
+                Label::new_secondary(SimpleSpan::new(file, 14, 14)).with_message("before `y`"),
+            );
+
+        assert_eq!(
+            emit_to_string(&files, &error, &DefaultConfig),
+            unindent(
+                r##"
+                    error: Expected `;`
+                    - test:1:10
+                    1 | let x = 1
+                      |          ^ insert `;` here
+                    - test:2:5
+                    2 | let y = 2
+                      |     - before `y`
+                "##,
+            ),
+        );
+    }
+
+    #[test]
+    fn test_remap() {
+        use crate::{ReportingSpan, Suggestion};
+
+        let mut files = SimpleReportingFiles::default();
+        let file = files.add("test", "(define test 123)\n(+ test \"\")\n");
+
+        // As if the diagnostic was raised in code that was expanded from the
+        // second line
+        let error = Diagnostic::new(Severity::Error, "Unexpected type in `+` application")
+            .with_label(Label::new_primary(SimpleSpan::new(file, 8, 10)).with_message("string"))
+            .with_suggestion(Suggestion::new(SimpleSpan::new(file, 8, 10), "0", "use an integer"));
+
+        let shift = |span: SimpleSpan| span.with_end(span.end() + 18).with_start(span.start() + 18);
+
+        assert_eq!(
+            emit_to_string(&files, &error.remap(&shift), &DefaultConfig),
+            unindent(
+                r##"
+                    error: Unexpected type in `+` application
+                    - test:2:9
+                    2 | (+ test "")
+                      |         ^^ string
+                    help: use an integer
+                    - test:2:9
+                    2 | (+ test 0)
+                      |         ~
+                "##,
+            ),
+        );
+    }
+
+    #[test]
+    fn test_zero_width_label_at_end_of_file() {
+        let mut files = SimpleReportingFiles::default();
+        let file = files.add("test", "let x = 1");
+
+        let error = Diagnostic::new(Severity::Error, "Expected `;`")
+            .with_label(Label::new_primary(SimpleSpan::new(file, 9, 9)).with_message("here"));
+
+        assert_eq!(
+            emit_to_string(&files, &error, &DefaultConfig),
+            unindent(
+                r##"
+                    error: Expected `;`
+                    - test:1:10
+                    1 | let x = 1
+                      |          ^ here
+                "##,
+            ),
+        );
+    }
+
+    #[derive(Debug)]
+    struct RustcConfig(usize);
+
+    impl Config for RustcConfig {
+        fn filename(&self, path: &Path) -> String {
+            DefaultConfig.filename(path)
+        }
+
+        fn context_lines(&self) -> usize {
+            self.0
+        }
+
+        fn rustc_style(&self) -> bool {
+            true
+        }
+    }
+
+    fn emit_rustc_style(context_lines: usize, lines: &[usize]) -> String {
+        let mut files = SimpleReportingFiles::default();
+
+        let source: String = (1..=12).map(|n| format!("(line {})\n", n)).collect();
+        let file = files.add("test", source);
+
+        let error = lines.iter().fold(
+            Diagnostic::new(Severity::Error, "Unexpected lines").with_code("E0001"),
+            |error, &line| {
+                let start = files.byte_index(file, line, 1).unwrap();
+                error.with_label(
+                    Label::new_primary(SimpleSpan::new(file, start, start + 4))
+                        .with_message("unexpected"),
+                )
+            },
+        );
+
+        emit_to_string(&files, &error, &RustcConfig(context_lines))
+    }
+
+    #[test]
+    fn test_rustc_style() {
+        assert_eq!(
+            emit_rustc_style(0, &[1]),
+            unindent(
+                r##"
+                    error[E0001]: Unexpected lines
+                     --> test:2:2
+                      |
+                    2 | (line 2)
+                      |  ^^^^ unexpected
+                      |
+                "##,
+            ),
+        );
+    }
+
+    #[test]
+    fn test_rustc_style_without_leading_gutter_line() {
+        #[derive(Debug)]
+        struct CompactConfig;
+
+        impl Config for CompactConfig {
+            fn filename(&self, path: &Path) -> String {
+                DefaultConfig.filename(path)
+            }
+
+            fn rustc_style(&self) -> bool {
+                true
+            }
+
+            fn leading_gutter_line(&self) -> bool {
+                false
+            }
+        }
+
+        let mut files = SimpleReportingFiles::default();
+        let file = files.add("test", "(+ test \"\")\n");
+
+        let error = Diagnostic::new(Severity::Error, "Unexpected string")
+            .with_label(Label::new_primary(SimpleSpan::new(file, 8, 10)));
+
+        assert_eq!(
+            emit_to_string(&files, &error, &CompactConfig),
+            unindent(
+                r##"
+                    error: Unexpected string
+                     --> test:1:9
+                    1 | (+ test "")
+                      |         ^^
+                      |
+                "##,
+            ),
+        );
+    }
+
+    #[test]
+    fn test_rustc_style_aligns_line_numbers_across_labels() {
+        assert_eq!(
+            emit_rustc_style(0, &[8, 11]),
+            unindent(
+                r##"
+                    error[E0001]: Unexpected lines
+                      --> test:9:2
+                       |
+                     9 | (line 9)
+                       |  ^^^^ unexpected
+                       |
+                      --> test:12:2
+                       |
+                    12 | (line 12)
+                       |  ^^^^ unexpected
+                       |
+                "##,
+            ),
+        );
+    }
+
+    #[test]
+    fn test_rustc_style_with_context_lines() {
+        assert_eq!(
+            emit_rustc_style(1, &[8]),
+            unindent(
+                r##"
+                    error[E0001]: Unexpected lines
+                      --> test:9:2
+                       |
+                     8 | (line 8)
+                     9 | (line 9)
+                       |  ^^^^ unexpected
+                    10 | (line 10)
+                       |
+                "##,
+            ),
+        );
+    }
+
+    #[test]
+    fn test_underline_right_to_left_text() {
+        #[derive(Debug)]
+        struct RtlConfig;
+
+        impl Config for RtlConfig {
+            fn filename(&self, path: &Path) -> String {
+                DefaultConfig.filename(path)
+            }
+
+            fn assume_ltr(&self) -> bool {
+                false
+            }
+        }
+
+        let source = "(greet \"שלום\")\n";
+        let marked = "\"שלום\"";
+
+        let mut files = SimpleReportingFiles::default();
+        let file = files.add("test", source);
+
+        let start = source.find(marked).unwrap();
+        let error = Diagnostic::new(Severity::Error, "Unexpected string").with_label(
+            Label::new_primary(SimpleSpan::new(file, start, start + marked.len())),
+        );
+
+        let ltr = emit_to_string(&files, &error, &DefaultConfig);
+        let carets = ltr.lines().nth(3).unwrap().matches('^').count();
+
+        assert_eq!(carets, marked.chars().count());
+        assert_eq!(
+            ltr,
+            unindent(
+                r##"
+                    error: Unexpected string
+                    - test:1:8
+                    1 | (greet "שלום")
+                      |        ^^^^^^
+                "##,
+            ),
+        );
+
+        assert_eq!(
+            emit_to_string(&files, &error, &RtlConfig),
+            unindent(
+                r##"
+                    error: Unexpected string
+                    - test:1:8
+                    1 | (greet "שלום")
+                      |        ^^^^^^
+                      = note: underlines follow the logical order of right-to-left text, which may be displayed in a different order
+                "##,
+            ),
+        );
+    }
+
+    #[test]
+    fn test_underline_accented_text() {
+        assert_eq!(
+            emit_single_label("(+ \"café\" \"crème\")\n", "\"crème\""),
+            unindent(
+                r##"
+                    error: Unexpected string
+                    - test:1:11
+                    1 | (+ "café" "crème")
+                      |           ^^^^^^^
+                "##,
+            ),
+        );
+    }
+
+    #[test]
+    fn test_underline_double_width_text() {
+        assert_eq!(
+            emit_single_label("(+ \"日本\" \"語\")\n", "\"語\""),
+            unindent(
+                r##"
+                    error: Unexpected string
+                    - test:1:9
+                    1 | (+ "日本" "語")
+                      |           ^^^^
+                "##,
+            ),
+        );
+    }
+
     #[cfg(windows)]
     #[test]
     fn test_color() {
@@ -0,0 +1,220 @@
+use crate::simple::{SimpleFile, SimpleSpan};
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+/// A `ReportingFiles` implementation that reads its files from disk.
+///
+/// Paths are registered up front with `add_path`, but a file is only read
+/// (and its lines indexed) the first time a span in it needs to be resolved.
+/// If a file can't be read, `location`, `line_span` and `source` return
+/// `None` for it rather than panicking, and diagnostics with labels in it are
+/// emitted without their source.
+#[derive(Debug, Clone, Default)]
+pub struct FsReportingFiles {
+    files: Vec<FsFile>,
+}
+
+#[derive(Debug, Clone)]
+struct FsFile {
+    path: PathBuf,
+    loaded: RefCell<Option<Rc<SimpleFile>>>,
+}
+
+impl FsReportingFiles {
+    pub fn add_path(&mut self, path: impl AsRef<Path>) -> usize {
+        self.files.push(FsFile {
+            path: path.as_ref().to_path_buf(),
+            loaded: RefCell::new(None),
+        });
+
+        self.files.len() - 1
+    }
+
+    /// Drop the cached contents of a file. The file will be read again the
+    /// next time a span in it needs to be resolved.
+    pub fn forget(&mut self, file: usize) {
+        if let Some(file) = self.files.get(file) {
+            file.loaded.replace(None);
+        }
+    }
+
+    fn load(&self, file: usize) -> Option<Rc<SimpleFile>> {
+        let file = self.files.get(file)?;
+        let mut loaded = file.loaded.borrow_mut();
+
+        if loaded.is_none() {
+            let contents = fs::read_to_string(&file.path).ok()?;
+            let name = file.path.display().to_string();
+
+            *loaded = Some(Rc::new(SimpleFile::new(name, contents)));
+        }
+
+        loaded.clone()
+    }
+}
+
+impl crate::ReportingFiles for FsReportingFiles {
+    type Span = SimpleSpan;
+    type FileId = usize;
+
+    fn file_id(&self, span: SimpleSpan) -> usize {
+        span.file_id
+    }
+
+    fn file_name(&self, id: usize) -> crate::FileName {
+        crate::FileName::Real(self.files[id].path.clone())
+    }
+
+    fn byte_span(&self, file: usize, from_index: usize, to_index: usize) -> Option<Self::Span> {
+        let len = self.load(file)?.contents().len();
+
+        if from_index <= to_index && to_index <= len {
+            Some(SimpleSpan::new(file, from_index, to_index))
+        } else {
+            None
+        }
+    }
+
+    fn byte_index(&self, file: usize, line: usize, column: usize) -> Option<usize> {
+        self.load(file)?.byte_index(line, column)
+    }
+
+    fn location(&self, file: usize, index: usize) -> Option<crate::Location> {
+        self.load(file)?.location(index)
+    }
+
+    fn line_span(&self, file: usize, line: usize) -> Option<Self::Span> {
+        let (start, end) = self.load(file)?.line_range(line)?;
+
+        Some(SimpleSpan::new(file, start, end))
+    }
+
+    fn source(&self, span: SimpleSpan) -> Option<Cow<'_, str>> {
+        let file = self.load(span.file_id)?;
+        let source = file.contents().get(span.start..span.end)?;
+
+        Some(Cow::Owned(source.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{emit_to_string, Diagnostic, FileName, Label, Location, ReportingFiles, Suggestion};
+    use unindent::unindent;
+
+    /// A file in the temp directory that is removed when the test is done.
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str, contents: &str) -> TempFile {
+            let path = std::env::temp_dir().join(format!(
+                "language-reporting-{}-{}",
+                std::process::id(),
+                name
+            ));
+
+            fs::write(&path, contents).unwrap();
+
+            TempFile(path)
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            fs::remove_file(&self.0).ok();
+        }
+    }
+
+    #[test]
+    fn test_emit_from_disk() {
+        let contents = unindent(
+            r##"
+                (define test 123)
+                (+ test "")
+                ()
+            "##,
+        );
+        let temp = TempFile::new("emit.lisp", &contents);
+
+        let mut files = FsReportingFiles::default();
+        let file = files.add_path(&temp.0);
+
+        let str_start = files.byte_index(file, 1, 8).unwrap();
+        let span = SimpleSpan::new(file, str_start, str_start + 2);
+        let error = Diagnostic::new_error("Unexpected type in `+` application")
+            .with_label(Label::new_primary(span).with_message("Expected integer but got string"));
+
+        let line = contents.lines().nth(1).unwrap();
+        assert_eq!(
+            files.source(files.line_span(file, 1).unwrap()).as_deref(),
+            Some(line)
+        );
+
+        assert_eq!(
+            emit_to_string(&files, &error, &crate::DefaultConfig),
+            format!(
+                "error: Unexpected type in `+` application\n\
+                 - {}:2:9\n\
+                 2 | {}\n\
+                 \x20 |         ^^ Expected integer but got string\n",
+                temp.0.display(),
+                line
+            )
+        );
+
+        match files.file_name(file) {
+            FileName::Real(path) => assert_eq!(path, temp.0),
+            other => panic!("expected a real file name, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_missing_file() {
+        let mut files = FsReportingFiles::default();
+        let file = files.add_path(std::env::temp_dir().join("language-reporting-missing-file"));
+
+        assert_eq!(files.location(file, 0), None);
+        assert!(files.line_span(file, 0).is_none());
+        assert!(files.source(SimpleSpan::new(file, 0, 0)).is_none());
+    }
+
+    #[test]
+    fn test_emit_for_missing_file() {
+        let mut files = FsReportingFiles::default();
+        let file = files.add_path(std::env::temp_dir().join("language-reporting-missing-file"));
+
+        let span = SimpleSpan::new(file, 8, 10);
+        let error = Diagnostic::new_error("Unexpected type in `+` application")
+            .with_label(Label::new_primary(span).with_message("Expected integer but got string"))
+            .with_suggestion(Suggestion::new(span, "0", "use an integer"));
+
+        // The header is still emitted, without the source it can't show
+        assert_eq!(
+            emit_to_string(&files, &error, &crate::DefaultConfig),
+            "error: Unexpected type in `+` application\n"
+        );
+    }
+
+    #[test]
+    fn test_forget() {
+        let temp = TempFile::new("forget.txt", "abc\ndef");
+
+        let mut files = FsReportingFiles::default();
+        let file = files.add_path(&temp.0);
+
+        assert_eq!(files.location(file, 5), Some(Location::new(1, 1)));
+
+        fs::write(&temp.0, "abcdef").unwrap();
+
+        // The contents are cached until they're forgotten
+        assert_eq!(files.location(file, 5), Some(Location::new(1, 1)));
+
+        files.forget(file);
+
+        assert_eq!(files.location(file, 5), Some(Location::new(0, 5)));
+    }
+}
@@ -23,19 +23,46 @@ use std::str::FromStr;
 use termcolor::ColorChoice;
 use serde_derive::{Serialize, Deserialize};
 
+mod callback;
+#[cfg(feature = "codespan-interop")]
+mod codespan_interop;
 mod components;
 mod diagnostic;
 mod emitter;
+mod fs;
+#[cfg(feature = "lsp")]
+mod lsp;
 mod models;
+mod policy;
+#[cfg(feature = "sarif")]
+mod sarif;
 mod simple;
 mod span;
 
-pub use self::diagnostic::{Diagnostic, Label, LabelStyle};
-pub use self::emitter::{emit, format, Config, DefaultConfig};
+pub use self::callback::CallbackFiles;
+#[cfg(feature = "codespan-interop")]
+pub use self::codespan_interop::{CodespanFiles, CodespanSpan};
+pub use self::diagnostic::{
+    Diagnostic, DiagnosticSet, Label, LabelStyle, Note, NoteStyle, SpanMapper, Suggestion,
+};
+pub use self::emitter::{
+    default_stylesheet, emit, emit_all, emit_ansi_string, emit_fmt, emit_json, emit_to_string,
+    format, format_diagnostic, report, Config, DefaultConfig, Emitter, LabelVerbosity,
+    ParserDialect, SeverityCounts, Theme,
+};
+pub use self::fs::FsReportingFiles;
+#[cfg(feature = "lsp")]
+pub use self::lsp::{
+    lsp_byte_index, lsp_position, lsp_severity, to_lsp, to_lsp_with_encoding, LspDiagnostic,
+    LspLocation, LspPosition, LspRange, LspRelatedInformation, PositionEncoding,
+};
 pub use self::render_tree::prelude::*;
-pub use self::render_tree::stylesheet::{Style, Stylesheet};
+pub use self::render_tree::stylesheet::{Color, ColorAccumulator, Style, Stylesheet};
+#[cfg(feature = "sarif")]
+pub use self::sarif::emit_sarif;
+pub use self::policy::SeverityPolicy;
 pub use self::simple::{SimpleFile, SimpleReportingFiles, SimpleSpan};
-pub use self::span::{FileName, Location, ReportingFiles, ReportingSpan};
+pub use self::span::{FileName, Location, RenderSpan, ReportingFiles, ReportingSpan};
 pub use render_tree::macros::*;
 
 /// A severity level for diagnostic messages
@@ -50,7 +77,7 @@ pub use render_tree::macros::*;
 /// assert!(Severity::Warning > Severity::Note);
 /// assert!(Severity::Note > Severity::Help);
 /// ```
-#[derive(Copy, Clone, PartialEq, Hash, Debug, Serialize, Deserialize)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
 pub enum Severity {
     /// An unexpected bug.
     Bug,
@@ -83,6 +110,36 @@ impl PartialOrd for Severity {
     }
 }
 
+impl Severity {
+    /// The process exit code for a diagnostic of this severity.
+    ///
+    /// Bugs and errors map to `1`, while warnings, notes and help messages map
+    /// to `0`.
+    ///
+    /// ```rust
+    /// use language_reporting::Severity;
+    ///
+    /// assert_eq!(Severity::Error.exit_code(), 1);
+    /// assert_eq!(Severity::Warning.exit_code(), 0);
+    /// ```
+    pub fn exit_code(self) -> i32 {
+        self.exit_code_with_threshold(Severity::Error)
+    }
+
+    /// The process exit code for a diagnostic of this severity, treating
+    /// `threshold` and anything more severe than it as a failure.
+    ///
+    /// This is usually driven by `Config::exit_code_threshold`, so that a CLI
+    /// can (for example) fail on warnings.
+    pub fn exit_code_with_threshold(self, threshold: Severity) -> i32 {
+        if self >= threshold {
+            1
+        } else {
+            0
+        }
+    }
+}
+
 impl fmt::Display for Severity {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         self.to_str().fmt(f)
@@ -165,3 +222,64 @@ impl Into<ColorChoice> for ColorArg {
         self.0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exit_code() {
+        assert_eq!(Severity::Bug.exit_code(), 1);
+        assert_eq!(Severity::Error.exit_code(), 1);
+        assert_eq!(Severity::Warning.exit_code(), 0);
+        assert_eq!(Severity::Note.exit_code(), 0);
+        assert_eq!(Severity::Help.exit_code(), 0);
+    }
+
+    #[test]
+    fn test_exit_code_with_warning_threshold() {
+        assert_eq!(Severity::Bug.exit_code_with_threshold(Severity::Warning), 1);
+        assert_eq!(Severity::Error.exit_code_with_threshold(Severity::Warning), 1);
+        assert_eq!(Severity::Warning.exit_code_with_threshold(Severity::Warning), 1);
+        assert_eq!(Severity::Note.exit_code_with_threshold(Severity::Warning), 0);
+        assert_eq!(Severity::Help.exit_code_with_threshold(Severity::Warning), 0);
+    }
+
+    #[test]
+    fn test_exit_code_with_bug_threshold() {
+        assert_eq!(Severity::Bug.exit_code_with_threshold(Severity::Bug), 1);
+        assert_eq!(Severity::Error.exit_code_with_threshold(Severity::Bug), 0);
+        assert_eq!(Severity::Warning.exit_code_with_threshold(Severity::Bug), 0);
+    }
+
+    #[test]
+    fn test_exit_code_with_config_threshold() {
+        #[derive(Debug)]
+        struct DenyWarnings;
+
+        impl Config for DenyWarnings {
+            fn filename(&self, path: &std::path::Path) -> String {
+                DefaultConfig.filename(path)
+            }
+
+            fn exit_code_threshold(&self) -> Severity {
+                Severity::Warning
+            }
+        }
+
+        assert_eq!(DefaultConfig.exit_code_threshold(), Severity::Error);
+
+        let severities = [Severity::Note, Severity::Warning];
+
+        let exit_code = |config: &dyn Config| {
+            severities
+                .iter()
+                .map(|severity| severity.exit_code_with_threshold(config.exit_code_threshold()))
+                .max()
+                .unwrap_or(0)
+        };
+
+        assert_eq!(exit_code(&DefaultConfig), 0);
+        assert_eq!(exit_code(&DenyWarnings), 1);
+    }
+}
@@ -0,0 +1,396 @@
+//! Converting diagnostics to the Language Server Protocol's `Diagnostic`, for
+//! language servers. The types here mirror the LSP's wire format, so they
+//! serialize to the JSON that clients expect without depending on an LSP
+//! crate.
+
+use crate::diagnostic::Diagnostic;
+use crate::span::{FileName, ReportingFiles, ReportingSpan};
+use crate::{LabelStyle, Severity};
+use serde_derive::Serialize;
+
+/// The unit that LSP positions count characters in, which the client and
+/// server agree on with `positionEncoding`. The LSP default is UTF-16.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum PositionEncoding {
+    /// Bytes
+    Utf8,
+    /// UTF-16 code units, so a character outside the Basic Multilingual
+    /// Plane, like most emoji, counts as two
+    #[default]
+    Utf16,
+    /// Characters (Unicode scalar values), like `Location::column`
+    Utf32,
+}
+
+impl PositionEncoding {
+    fn char_len(self, ch: char) -> usize {
+        match self {
+            PositionEncoding::Utf8 => ch.len_utf8(),
+            PositionEncoding::Utf16 => ch.len_utf16(),
+            PositionEncoding::Utf32 => 1,
+        }
+    }
+
+    fn len(self, text: &str) -> usize {
+        text.chars().map(|ch| self.char_len(ch)).sum()
+    }
+}
+
+/// A zero-based line, and a zero-based character offset into the line in
+/// some `PositionEncoding`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct LspPosition {
+    pub line: u32,
+    pub character: u32,
+}
+
+impl LspPosition {
+    pub fn new(line: u32, character: u32) -> LspPosition {
+        LspPosition { line, character }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct LspRange {
+    pub start: LspPosition,
+    pub end: LspPosition,
+}
+
+/// A range in a file. The `uri` is the file's name from
+/// `ReportingFiles::file_name`, which a language server may need to turn
+/// into the URI that the client knows the file by.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LspLocation {
+    pub uri: String,
+    pub range: LspRange,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LspRelatedInformation {
+    pub location: LspLocation,
+    pub message: String,
+}
+
+/// The LSP's `Diagnostic`. `severity` is `1` for errors, `2` for warnings,
+/// `3` for information and `4` for hints.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LspDiagnostic {
+    pub range: LspRange,
+    pub severity: u8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    pub message: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub related_information: Vec<LspRelatedInformation>,
+}
+
+/// Convert a diagnostic to the LSP's `Diagnostic`, with UTF-16 positions.
+///
+/// The first primary label is the diagnostic's range, and its message is
+/// added to the diagnostic's message. The other labels become related
+/// information. A diagnostic without a primary label, or whose primary
+/// label can't be resolved in `files`, has an empty range at the start of
+/// the file.
+///
+/// ```rust
+/// use language_reporting::*;
+///
+/// let mut files = SimpleReportingFiles::default();
+/// let file = files.add("test", "let 🦀 = \"\";\n");
+///
+/// let diagnostic = Diagnostic::new_error("Unexpected string")
+///     .with_label(Label::new_primary(SimpleSpan::new(file, 11, 13)));
+///
+/// let lsp = to_lsp(&diagnostic, &files);
+///
+/// assert_eq!(lsp.severity, 1);
+/// assert_eq!(lsp.range.start, LspPosition::new(0, 9));
+/// assert_eq!(lsp.range.end, LspPosition::new(0, 11));
+/// ```
+pub fn to_lsp<Files: ReportingFiles>(
+    diagnostic: &Diagnostic<Files::Span>,
+    files: &Files,
+) -> LspDiagnostic {
+    to_lsp_with_encoding(diagnostic, files, PositionEncoding::Utf16)
+}
+
+/// Like `to_lsp`, with positions in `encoding`.
+pub fn to_lsp_with_encoding<Files: ReportingFiles>(
+    diagnostic: &Diagnostic<Files::Span>,
+    files: &Files,
+    encoding: PositionEncoding,
+) -> LspDiagnostic {
+    let primary = diagnostic
+        .labels
+        .iter()
+        .position(|label| label.style == LabelStyle::Primary);
+
+    let range = |span: &Files::Span| lsp_range(files, span, encoding).unwrap_or_default();
+
+    let mut message = diagnostic.message.clone();
+
+    if let Some(label_message) = primary.and_then(|i| diagnostic.labels[i].message.as_ref()) {
+        message.push('\n');
+        message.push_str(label_message);
+    }
+
+    let related_information = diagnostic
+        .labels
+        .iter()
+        .enumerate()
+        .filter(|&(i, _)| Some(i) != primary)
+        .map(|(_, label)| LspRelatedInformation {
+            location: LspLocation {
+                uri: uri(files.file_name(files.file_id(label.span.clone()))),
+                range: range(&label.span),
+            },
+            message: label.message.clone().unwrap_or_default(),
+        })
+        .collect();
+
+    LspDiagnostic {
+        range: primary.map_or_else(LspRange::default, |i| range(&diagnostic.labels[i].span)),
+        severity: lsp_severity(diagnostic.severity),
+        code: diagnostic.code.clone(),
+        message,
+        related_information,
+    }
+}
+
+/// The LSP's `DiagnosticSeverity` for a severity. Bugs are errors, notes are
+/// information, and help messages are hints.
+pub fn lsp_severity(severity: Severity) -> u8 {
+    match severity {
+        Severity::Bug | Severity::Error => 1,
+        Severity::Warning => 2,
+        Severity::Note => 3,
+        Severity::Help => 4,
+    }
+}
+
+/// The LSP position of `byte_index` in `file`, if `files` has it.
+pub fn lsp_position<Files: ReportingFiles>(
+    files: &Files,
+    file: Files::FileId,
+    byte_index: usize,
+    encoding: PositionEncoding,
+) -> Option<LspPosition> {
+    let location = files.location(file, byte_index)?;
+    let line = files.line_span(file, location.line)?;
+    let before = files.source(line.with_end(byte_index))?;
+
+    Some(LspPosition::new(
+        location.line as u32,
+        encoding.len(&before) as u32,
+    ))
+}
+
+/// The byte index of an LSP position in `file`, the inverse of
+/// `lsp_position`. As the LSP specifies, a character past the end of the
+/// line is the end of the line. A character in the middle of a multi-unit
+/// character is the end of that character.
+pub fn lsp_byte_index<Files: ReportingFiles>(
+    files: &Files,
+    file: Files::FileId,
+    position: LspPosition,
+    encoding: PositionEncoding,
+) -> Option<usize> {
+    let line = files.line_span(file, position.line as usize)?;
+    let source = files.source(line.clone())?;
+    let source = source.trim_end_matches(['\n', '\r']);
+    let mut units = 0;
+
+    for (i, ch) in source.char_indices() {
+        if units >= position.character as usize {
+            return Some(line.start() + i);
+        }
+
+        units += encoding.char_len(ch);
+    }
+
+    Some(line.start() + source.len())
+}
+
+fn lsp_range<Files: ReportingFiles>(
+    files: &Files,
+    span: &Files::Span,
+    encoding: PositionEncoding,
+) -> Option<LspRange> {
+    let file = files.file_id(span.clone());
+
+    Some(LspRange {
+        start: lsp_position(files, file, span.start(), encoding)?,
+        end: lsp_position(files, file, span.end(), encoding)?,
+    })
+}
+
+fn uri(name: FileName) -> String {
+    match name {
+        FileName::Real(path) | FileName::Virtual(path) => path.display().to_string(),
+        FileName::Verbatim(name) => name,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostic::Label;
+    use crate::simple::{SimpleReportingFiles, SimpleSpan};
+
+    fn files() -> (SimpleReportingFiles, usize) {
+        let mut files = SimpleReportingFiles::default();
+        let file = files.add("test", "let 🦀 = \"é\";\nlet x = 1;\n");
+
+        (files, file)
+    }
+
+    #[test]
+    fn test_to_lsp() {
+        let (files, file) = files();
+
+        let diagnostic = Diagnostic::new_warning("Unexpected string")
+            .with_code("W0001")
+            .with_label(Label::new_primary(SimpleSpan::new(file, 11, 15)).with_message("string"))
+            .with_label(Label::new_secondary(SimpleSpan::new(file, 4, 8)).with_message("crab"))
+            .with_label(Label::new_secondary(SimpleSpan::new(file, 22, 27)));
+
+        let lsp = to_lsp(&diagnostic, &files);
+
+        let range = |start: (u32, u32), end: (u32, u32)| LspRange {
+            start: LspPosition::new(start.0, start.1),
+            end: LspPosition::new(end.0, end.1),
+        };
+
+        assert_eq!(
+            lsp,
+            LspDiagnostic {
+                range: range((0, 9), (0, 12)),
+                severity: 2,
+                code: Some("W0001".to_string()),
+                message: "Unexpected string\nstring".to_string(),
+                related_information: vec![
+                    LspRelatedInformation {
+                        location: LspLocation {
+                            uri: "test".to_string(),
+                            range: range((0, 4), (0, 6)),
+                        },
+                        message: "crab".to_string(),
+                    },
+                    LspRelatedInformation {
+                        location: LspLocation {
+                            uri: "test".to_string(),
+                            range: range((1, 5), (1, 10)),
+                        },
+                        message: "".to_string(),
+                    },
+                ],
+            }
+        );
+
+        assert_eq!(
+            serde_json::to_value(&lsp).unwrap()["relatedInformation"][0]["location"]["range"],
+            serde_json::json!({
+                "start": { "line": 0, "character": 4 },
+                "end": { "line": 0, "character": 6 },
+            })
+        );
+    }
+
+    #[test]
+    fn test_to_lsp_inside_a_character() {
+        let (files, file) = files();
+
+        // The span ends inside `é`
+        let diagnostic = Diagnostic::new_error("Unexpected string")
+            .with_label(Label::new_primary(SimpleSpan::new(file, 11, 13)));
+
+        assert_eq!(to_lsp(&diagnostic, &files).range, LspRange::default());
+    }
+
+    #[test]
+    fn test_to_lsp_without_labels() {
+        let (files, _) = files();
+        let diagnostic: Diagnostic<SimpleSpan> = Diagnostic::new_help("Try this");
+
+        let lsp = to_lsp(&diagnostic, &files);
+
+        assert_eq!(lsp.range, LspRange::default());
+        assert_eq!(lsp.severity, 4);
+        assert_eq!(lsp.message, "Try this");
+        assert_eq!(
+            serde_json::to_string(&lsp).unwrap(),
+            r#"{"range":{"start":{"line":0,"character":0},"end":{"line":0,"character":0}},"#
+                .to_string()
+                + r#""severity":4,"message":"Try this"}"#
+        );
+    }
+
+    #[test]
+    fn test_severity() {
+        assert_eq!(lsp_severity(Severity::Bug), 1);
+        assert_eq!(lsp_severity(Severity::Error), 1);
+        assert_eq!(lsp_severity(Severity::Warning), 2);
+        assert_eq!(lsp_severity(Severity::Note), 3);
+        assert_eq!(lsp_severity(Severity::Help), 4);
+    }
+
+    #[test]
+    fn test_encodings() {
+        let (files, file) = files();
+
+        // The `=` after the crab, which is 4 bytes, 2 UTF-16 code units and 1
+        // character
+        let position = |encoding| lsp_position(&files, file, 9, encoding);
+
+        assert_eq!(position(PositionEncoding::Utf8), Some(LspPosition::new(0, 9)));
+        assert_eq!(position(PositionEncoding::Utf16), Some(LspPosition::new(0, 7)));
+        assert_eq!(position(PositionEncoding::Utf32), Some(LspPosition::new(0, 6)));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let (files, file) = files();
+        let source = "let 🦀 = \"é\";\nlet x = 1;\n";
+
+        for encoding in &[
+            PositionEncoding::Utf8,
+            PositionEncoding::Utf16,
+            PositionEncoding::Utf32,
+        ] {
+            for (index, _) in source.char_indices() {
+                let position = lsp_position(&files, file, index, *encoding).unwrap();
+
+                assert_eq!(
+                    lsp_byte_index(&files, file, position, *encoding),
+                    Some(index),
+                    "{:?} at {}",
+                    encoding,
+                    index
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_byte_index_clamping() {
+        let (files, file) = files();
+
+        // Past the end of the line
+        assert_eq!(
+            lsp_byte_index(&files, file, LspPosition::new(1, 100), PositionEncoding::Utf16),
+            Some(27)
+        );
+
+        // In the middle of the crab's surrogate pair
+        assert_eq!(
+            lsp_byte_index(&files, file, LspPosition::new(0, 5), PositionEncoding::Utf16),
+            Some(8)
+        );
+
+        assert_eq!(
+            lsp_byte_index(&files, file, LspPosition::new(5, 0), PositionEncoding::Utf16),
+            None
+        );
+    }
+}
@@ -1,43 +1,184 @@
-use crate::diagnostic::Diagnostic;
-use crate::{FileName, Label, LabelStyle, Location, ReportingFiles, ReportingSpan, Severity};
+use crate::diagnostic::{Diagnostic, Note, NoteStyle, Suggestion};
+use crate::{
+    FileName, Label, LabelStyle, LabelVerbosity, Location, ReportingFiles, ReportingSpan, Severity,
+};
+use std::borrow::Cow;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
 #[derive(Copy, Clone, Debug)]
 pub(crate) struct Header<'doc> {
     severity: Severity,
     code: Option<&'doc str>,
     message: &'doc str,
+    config: &'doc dyn crate::Config,
 }
 
 impl<'doc> Header<'doc> {
-    pub(crate) fn new(diagnostic: &'doc Diagnostic<impl ReportingSpan>) -> Header<'doc> {
+    pub(crate) fn new(
+        diagnostic: &'doc Diagnostic<impl ReportingSpan>,
+        config: &'doc dyn crate::Config,
+    ) -> Header<'doc> {
         Header {
             severity: diagnostic.severity,
             code: diagnostic.code.as_ref().map(|c| &c[..]),
             message: &diagnostic.message,
+            config,
         }
     }
 
-    pub(crate) fn severity(&self) -> &'static str {
-        match self.severity {
-            Severity::Bug => "bug",
-            Severity::Error => "error",
-            Severity::Warning => "warning",
-            Severity::Help => "help",
-            Severity::Note => "note",
+    /// The severity as it is displayed in the header. The stylesheet section
+    /// for the severity doesn't change when `Config::severity_name` renames it.
+    pub(crate) fn severity_name(&self) -> String {
+        self.config.severity_name(self.severity)
+    }
+
+    /// The code as it is displayed after the severity, as formatted by
+    /// `Config::code_display`.
+    pub(crate) fn code(&self) -> Option<String> {
+        self.code.map(|code| self.config.code_display(code))
+    }
+
+    /// The width of the `severity[code]: ` prefix, which wrapped message
+    /// lines are indented by.
+    pub(crate) fn prefix_width(&self) -> usize {
+        let code_width = match self.code() {
+            None => 0,
+            Some(code) => display_width(&code),
+        };
+
+        display_width(&self.severity_name()) + code_width + ": ".len()
+    }
+
+    /// The message, word-wrapped to fit in `Config::wrap_width` after the
+    /// prefix, or escaped on a single line for
+    /// `Config::escape_messages_for`.
+    pub(crate) fn message_lines(&self) -> Vec<String> {
+        if let Some(dialect) = self.config.escape_messages_for() {
+            return vec![dialect.escape(self.message).into_owned()];
+        }
+
+        match self.config.wrap_width() {
+            None => vec![self.message.to_string()],
+            Some(width) => wrap_words(self.message, width.saturating_sub(self.prefix_width())),
         }
     }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct NoteLine<'doc> {
+    style: NoteStyle,
+    message: &'doc str,
+    config: &'doc dyn crate::Config,
+}
 
-    pub(crate) fn code(&self) -> &Option<&'doc str> {
-        &self.code
+impl<'doc> NoteLine<'doc> {
+    pub(crate) fn new(note: &'doc Note, config: &'doc dyn crate::Config) -> NoteLine<'doc> {
+        NoteLine {
+            style: note.style,
+            message: &note.message,
+            config,
+        }
+    }
+
+    /// A secondary label's message, shown as a note when
+    /// `Config::label_verbosity` is `LabelVerbosity::MessagesOnly`.
+    pub(crate) fn for_label<Span: ReportingSpan>(
+        label: &'doc Label<Span>,
+        config: &'doc dyn crate::Config,
+    ) -> Option<NoteLine<'doc>> {
+        let message = label.message.as_ref()?;
+
+        Some(NoteLine {
+            style: NoteStyle::Note,
+            message,
+            config,
+        })
     }
 
-    pub(crate) fn message(&self) -> String {
-        self.message.to_string()
+    /// The note that's added when labelled source contains right-to-left
+    /// text and `Config::assume_ltr` is `false`.
+    pub(crate) fn right_to_left(config: &'doc dyn crate::Config) -> NoteLine<'doc> {
+        NoteLine {
+            style: NoteStyle::Note,
+            message: "underlines follow the logical order of right-to-left text, \
+                      which may be displayed in a different order",
+            config,
+        }
+    }
+
+    /// The prefix before the message, like `help: `, from
+    /// `Config::note_prefix` or `Config::help_prefix`.
+    pub(crate) fn prefix(&self) -> String {
+        match self.style {
+            NoteStyle::Note => self.config.note_prefix(),
+            NoteStyle::Help => self.config.help_prefix(),
+        }
+    }
+
+    pub(crate) fn message(&self) -> &'doc str {
+        self.message
     }
 }
 
+/// Repeat the characters of `mark` to fill `width` columns, without going over
+/// when the mark is wider than one column. There's always at least one
+/// character, so that an empty region still has something to point at.
+/// Zero-width characters in the mark are skipped.
+pub(crate) fn fill(mark: &str, width: usize) -> String {
+    let mut filled = String::new();
+    let mut filled_width = 0;
+
+    let visible = mark.chars().filter(|ch| ch.width().unwrap_or(0) > 0);
+
+    for ch in visible.cycle() {
+        let ch_width = ch.width().unwrap_or(0);
+
+        if !filled.is_empty() && filled_width + ch_width > width {
+            break;
+        }
+
+        filled.push(ch);
+        filled_width += ch_width;
+
+        if filled_width >= width {
+            break;
+        }
+    }
+
+    filled
+}
+
+/// Greedily word-wrap `text` into lines that are at most `width` columns wide.
+/// Words that are wider than `width` get a line of their own.
+pub(crate) fn wrap_words(text: &str, width: usize) -> Vec<String> {
+    let mut lines = vec![];
+    let mut current = String::new();
+
+    for word in text.split_whitespace() {
+        if !current.is_empty() && display_width(&current) + 1 + display_width(word) > width {
+            lines.push(std::mem::take(&mut current));
+        }
+
+        if !current.is_empty() {
+            current.push(' ');
+        }
+
+        current.push_str(word);
+    }
+
+    if !current.is_empty() || lines.is_empty() {
+        lines.push(current);
+    }
+
+    lines
+}
+
 pub(crate) fn severity(diagnostic: &Diagnostic<impl ReportingSpan>) -> &'static str {
-    match diagnostic.severity {
+    severity_section(diagnostic.severity)
+}
+
+pub(crate) fn severity_section(severity: Severity) -> &'static str {
+    match severity {
         Severity::Bug => "bug",
         Severity::Error => "error",
         Severity::Warning => "warning",
@@ -46,10 +187,96 @@ pub(crate) fn severity(diagnostic: &Diagnostic<impl ReportingSpan>) -> &'static
     }
 }
 
+/// The labels whose source is shown, in the order they're shown. Secondary
+/// labels are left out unless `Config::label_verbosity` is
+/// `LabelVerbosity::All`, and so are labels that `files` can't resolve, like
+/// labels in a file that couldn't be read.
+pub(crate) fn shown_labels<'doc, Files: ReportingFiles>(
+    files: &'doc Files,
+    labels: &'doc [Label<Files::Span>],
+    config: &'doc dyn crate::Config,
+) -> Vec<&'doc Label<Files::Span>> {
+    let mut labels = sorted_labels(files, labels, config);
+
+    if config.label_verbosity() != LabelVerbosity::All {
+        labels.retain(|label| label.style == LabelStyle::Primary);
+    }
+
+    labels.retain(|label| SourceLine::new(files, label, config).resolves());
+
+    labels
+}
+
+/// The secondary labels whose messages are shown as notes, in the order
+/// they're shown, if `Config::label_verbosity` is
+/// `LabelVerbosity::MessagesOnly`.
+pub(crate) fn label_notes<'doc, Files: ReportingFiles>(
+    files: &'doc Files,
+    labels: &'doc [Label<Files::Span>],
+    config: &'doc dyn crate::Config,
+) -> Vec<NoteLine<'doc>> {
+    if config.label_verbosity() != LabelVerbosity::MessagesOnly {
+        return vec![];
+    }
+
+    sorted_labels(files, labels, config)
+        .into_iter()
+        .filter(|label| label.style == LabelStyle::Secondary)
+        .filter_map(|label| NoteLine::for_label(label, config))
+        .collect()
+}
+
+/// The labels in the order that they're rendered: grouped by file, starting
+/// with the file of the first primary label and then in the order that each
+/// file is first labelled, and then by position in the file. Primary labels
+/// come before secondary labels with the same span. The sort is stable, so
+/// otherwise equal labels stay in insertion order.
+///
+/// If `Config::sort_labels` is `false`, the labels are returned in insertion
+/// order.
+pub(crate) fn sorted_labels<'doc, Files: ReportingFiles>(
+    files: &'doc Files,
+    labels: &'doc [Label<Files::Span>],
+    config: &'doc dyn crate::Config,
+) -> Vec<&'doc Label<Files::Span>> {
+    if !config.sort_labels() {
+        return labels.iter().collect();
+    }
+
+    // `FileId`s can't be compared, so files are compared by name
+    let filenames: Vec<String> = labels
+        .iter()
+        .map(|label| SourceLine::new(files, label, config).filename())
+        .collect();
+
+    let primary_file = labels
+        .iter()
+        .position(|label| label.style == LabelStyle::Primary)
+        .map(|i| &filenames[i]);
+
+    let mut order: Vec<usize> = (0..labels.len()).collect();
+
+    order.sort_by_key(|&i| {
+        let label = &labels[i];
+        let file = match primary_file {
+            Some(primary_file) if *primary_file == filenames[i] => 0,
+            _ => 1 + filenames.iter().position(|name| *name == filenames[i]).unwrap(),
+        };
+        let style = match label.style {
+            LabelStyle::Primary => 0,
+            LabelStyle::Secondary => 1,
+        };
+
+        (file, label.span.start(), label.span.end(), style)
+    });
+
+    order.into_iter().map(|i| &labels[i]).collect()
+}
+
 #[derive(Copy, Clone, Debug)]
 pub(crate) struct SourceLine<'doc, Files: ReportingFiles> {
     files: &'doc Files,
-    label: &'doc Label<Files::Span>,
+    span: &'doc Files::Span,
     config: &'doc dyn crate::Config,
 }
 
@@ -58,24 +285,88 @@ impl<'doc, Files: ReportingFiles> SourceLine<'doc, Files> {
         files: &'doc Files,
         label: &'doc Label<Files::Span>,
         config: &'doc dyn crate::Config,
+    ) -> SourceLine<'doc, Files> {
+        SourceLine::for_span(files, &label.span, config)
+    }
+
+    /// The line that a span starts on, for things other than labels that
+    /// point at source, like suggestions.
+    pub(crate) fn for_span(
+        files: &'doc Files,
+        span: &'doc Files::Span,
+        config: &'doc dyn crate::Config,
     ) -> SourceLine<'doc, Files> {
         SourceLine {
             files,
-            label,
+            span,
             config,
         }
     }
 
+    /// Whether `files` has everything that's needed to show the span's line:
+    /// the locations of its start and end, its line, and its source.
+    pub(crate) fn resolves(&self) -> bool {
+        let file = self.files.file_id(self.span.clone());
+        let line_span = self
+            .files
+            .location(file, self.span.start())
+            .and_then(|location| self.files.line_span(file, location.line));
+
+        match line_span {
+            None => false,
+            Some(line_span) => {
+                self.files.location(file, self.span.end()).is_some()
+                    && self.files.source(line_span.with_end(self.span.start())).is_some()
+                    && self.files.source(self.span.clone()).is_some()
+            }
+        }
+    }
+
     pub(crate) fn location(&self) -> Location {
-        let span = self.label.span;
+        let span = self.span;
+
+        match self.files.location(self.files.file_id(span.clone()), span.start()) {
+            Some(location) => location,
+            None => self.unresolved("location", span),
+        }
+    }
+
+    /// The label's location as it's shown in the diagnostic, counted from
+    /// `Config::location_base`.
+    pub(crate) fn display_location(&self) -> Location {
+        let Location { line, column } = self.location();
+        let base = self.config.location_base();
 
-        self.files
-            .location(self.files.file_id(span), span.start())
-            .expect("A valid location")
+        Location::new(line + base, column + base)
+    }
+
+    /// The label's location as it's shown in the location line: where it
+    /// starts, like `2:9`, or its whole range, like `2:9-2:11`, if
+    /// `Config::location_range` is set.
+    pub(crate) fn display_position(&self) -> String {
+        let start = self.display_location();
+        let start = format!("{}:{}", start.line, start.column);
+
+        if !self.config.location_range() {
+            return start;
+        }
+
+        let span = self.span;
+        let Location { line, column } =
+            match self.files.location(self.files.file_id(span.clone()), span.end()) {
+                Some(location) => location,
+                None => self.unresolved("end location", span),
+            };
+        let base = self.config.location_base();
+
+        format!("{}-{}:{}", start, line + base, column + base)
     }
 
     pub(crate) fn filename(&self) -> String {
-        match &self.files.file_name(self.files.file_id(self.label.span)) {
+        match &self
+            .files
+            .file_name(self.files.file_id(self.span.clone()))
+        {
             FileName::Virtual(name) => format!("<{}>", name.to_str().unwrap()),
             FileName::Real(name) => self.config.filename(name),
             FileName::Verbatim(name) => format!("{}", name),
@@ -83,11 +374,12 @@ impl<'doc, Files: ReportingFiles> SourceLine<'doc, Files> {
     }
 
     pub(crate) fn line_span(&self) -> Files::Span {
-        let span = self.label.span;
+        let span = self.span;
 
-        self.files
-            .line_span(self.files.file_id(span), self.location().line)
-            .expect("line_span")
+        match self.files.line_span(self.files.file_id(span.clone()), self.location().line) {
+            Some(line_span) => line_span,
+            None => self.unresolved("line", span),
+        }
     }
 
     pub(crate) fn line_number(&self) -> usize {
@@ -98,34 +390,371 @@ impl<'doc, Files: ReportingFiles> SourceLine<'doc, Files> {
         self.line_number().to_string().len()
     }
 
+    /// The separator between the gutter's line numbers and the source, from
+    /// `Config::gutter_separator`.
+    pub(crate) fn gutter_separator(&self) -> String {
+        self.config.gutter_separator()
+    }
+
+    /// The prefix of the `- file:line:column` line, from
+    /// `Config::location_prefix`.
+    pub(crate) fn location_prefix(&self) -> String {
+        self.config.location_prefix()
+    }
+
+    /// An unlabelled line of the label's file, if the file has that line.
+    pub(crate) fn context_line(&self, line: usize) -> Option<ContextLine<'doc>> {
+        let file = self.files.file_id(self.span.clone());
+        let span = self.files.line_span(file, line)?;
+
+        // The empty remainder after a trailing newline isn't a line of its own
+        if span.start() == span.end() && self.files.line_span(file, line + 1).is_none() {
+            return None;
+        }
+
+        let source = trim_line_ending(self.source(span, "context line"));
+
+        Some(ContextLine {
+            config: self.config,
+            line_number: line + 1,
+            source: match self.config.max_line_width() {
+                Some(width) => truncate_end(source, width),
+                None => source,
+            },
+        })
+    }
+
     // pub(crate) fn before_line_len(&self) -> usize {
     //     // TODO: Improve
     //     self.before_marked().len() + self.line_number().to_string().len()
     // }
 
-    pub(crate) fn before_marked(&self) -> String {
-        self.files
-            .source(self.line_span().with_end(self.label.span.start()))
-            .expect("line_prefix")
+    /// The source before the marked region. A label that starts at the end
+    /// of a line can reach past the end of the line span, so the line ending
+    /// is trimmed.
+    pub(crate) fn before_marked(&self) -> Cow<'doc, str> {
+        trim_line_ending(self.source(
+            self.line_span().with_end(self.span.start()),
+            "line prefix",
+        ))
     }
 
-    pub(crate) fn after_marked(&self) -> String {
-        self.files
-            .source(self.line_span().with_start(self.label.span.end()))
-            .expect("line_suffix")
-            .trim_end_matches(|ch| ch == '\r' || ch == '\n')
-            .to_string()
+    /// The source after the marked region, which is empty if the label
+    /// reaches the end of the line.
+    pub(crate) fn after_marked(&self) -> Cow<'doc, str> {
+        let line_span = self.line_span();
+        let start = self.span.end().min(line_span.end());
+
+        trim_line_ending(self.source(line_span.with_start(start), "line suffix"))
     }
 
-    pub(crate) fn marked(&self) -> String {
-        self.files.source(self.label.span).expect("line_marked")
+    /// Whether the labelled line contains any right-to-left characters.
+    pub(crate) fn has_rtl(&self) -> bool {
+        self.source(self.line_span(), "source line")
+            .chars()
+            .any(is_rtl)
+    }
+
+    pub(crate) fn marked(&self) -> Cow<'doc, str> {
+        self.source(self.span.clone(), "marked source")
+    }
+
+    /// The marked source as it's shown on the source line, without a line
+    /// ending at the end of it.
+    pub(crate) fn marked_line(&self) -> Cow<'doc, str> {
+        trim_line_ending(self.marked())
+    }
+
+    /// Get the source for a span, panicking with a message that names the
+    /// file and span if the `ReportingFiles` doesn't have it.
+    fn source(&self, span: Files::Span, description: &str) -> Cow<'doc, str> {
+        match self.files.source(span.clone()) {
+            Some(source) => source,
+            None => self.unresolved(description, &span),
+        }
+    }
+
+    /// Panic because `files` couldn't resolve the `description` of `span`,
+    /// naming the file, the span and the file's length.
+    fn unresolved(&self, description: &str, span: &Files::Span) -> ! {
+        let len = match self.file_len() {
+            Some(len) => format!("{} bytes", len),
+            None => "which couldn't be read".to_string(),
+        };
+
+        panic!(
+            "Couldn't get the {} for {}..{} in {} ({})",
+            description,
+            span.start(),
+            span.end(),
+            self.filename(),
+            len
+        )
+    }
+
+    /// The length of the file in bytes, which is where its last line ends,
+    /// or `None` if `files` doesn't have any lines for it. `ReportingFiles`
+    /// doesn't know the lengths of files, so the last line is searched for.
+    fn file_len(&self) -> Option<usize> {
+        let file = self.files.file_id(self.span.clone());
+        let has_line = |line| self.files.line_span(file, line).is_some();
+
+        if !has_line(0) {
+            return None;
+        }
+
+        // Find a line past the end of the file, and then the last line
+        // before it
+        let (mut last, mut past) = (0, 1);
+
+        while has_line(past) {
+            last = past;
+            past *= 2;
+        }
+
+        while past - last > 1 {
+            let middle = last + (past - last) / 2;
+
+            if has_line(middle) {
+                last = middle;
+            } else {
+                past = middle;
+            }
+        }
+
+        self.files.line_span(file, last).map(|span| span.end())
+    }
+
+    /// The parts of the line, truncated to fit in `Config::max_line_width`.
+    pub(crate) fn visible(&self) -> VisibleLine<'doc> {
+        let line = VisibleLine {
+            before: self.before_marked(),
+            marked: self.marked_line(),
+            after: self.after_marked(),
+        };
+
+        match self.config.max_line_width() {
+            Some(width) => line.truncate(width),
+            None => line,
+        }
     }
 }
 
+const ELLIPSIS: &str = "\u{2026}";
+
+/// The source before, in and after the marked region of a labelled line, as
+/// it's displayed.
+#[derive(Clone, Debug)]
+pub(crate) struct VisibleLine<'doc> {
+    before: Cow<'doc, str>,
+    marked: Cow<'doc, str>,
+    after: Cow<'doc, str>,
+}
+
+impl<'doc> VisibleLine<'doc> {
+    pub(crate) fn before(&self) -> &str {
+        &self.before
+    }
+
+    pub(crate) fn marked(&self) -> &str {
+        &self.marked
+    }
+
+    pub(crate) fn after(&self) -> &str {
+        &self.after
+    }
+
+    /// The number of terminal columns taken up by the source before the
+    /// marked region.
+    pub(crate) fn before_width(&self) -> usize {
+        display_width(&self.before)
+    }
+
+    /// The number of terminal columns taken up by the marked region.
+    pub(crate) fn marked_width(&self) -> usize {
+        display_width(&self.marked)
+    }
+
+    /// The number of marks to underline the marked region with. An empty
+    /// region, which points between two characters, still gets one mark so
+    /// that its message has something to point at.
+    pub(crate) fn underline_width(&self) -> usize {
+        self.marked_width().max(1)
+    }
+
+    fn after_width(&self) -> usize {
+        display_width(&self.after)
+    }
+
+    /// Elide the source around the marked region so that the line fits in
+    /// `width` columns, keeping as much of the marked region as possible.
+    fn truncate(self, width: usize) -> VisibleLine<'doc> {
+        let before_width = self.before_width();
+        let marked_width = self.marked_width();
+        let after_width = self.after_width();
+
+        if before_width + marked_width + after_width <= width {
+            return self;
+        }
+
+        let ellipsis = |source: &str| if source.is_empty() { "" } else { ELLIPSIS };
+
+        // The marked region doesn't fit with an ellipsis on either side, so
+        // elide everything around it, and the middle of it
+        if marked_width + 2 >= width {
+            let before = ellipsis(&self.before);
+            let after = ellipsis(&self.after);
+            let marked_space = width.saturating_sub(display_width(before) + display_width(after));
+
+            return VisibleLine {
+                before: Cow::Borrowed(before),
+                marked: Cow::Owned(truncate_middle(&self.marked, marked_space)),
+                after: Cow::Borrowed(after),
+            };
+        }
+
+        // Split the remaining space between the two sides, giving any space
+        // that one side doesn't need to the other
+        let space = width - marked_width;
+        let mut before_space = space / 2;
+        let mut after_space = space - before_space;
+
+        if before_width < before_space {
+            after_space += before_space - before_width;
+            before_space = before_width;
+        } else if after_width < after_space {
+            before_space += after_space - after_width;
+            after_space = after_width;
+        }
+
+        VisibleLine {
+            before: truncate_start(self.before, before_space),
+            marked: self.marked,
+            after: truncate_end(self.after, after_space),
+        }
+    }
+}
+
+/// Keep the end of `source`, replacing the rest with an ellipsis, so that it
+/// fits in `width` columns.
+fn truncate_start(source: Cow<'_, str>, width: usize) -> Cow<'_, str> {
+    if display_width(&source) <= width {
+        return source;
+    }
+
+    let kept: String = take_width(source.chars().rev(), width.saturating_sub(1))
+        .into_iter()
+        .rev()
+        .collect();
+
+    Cow::Owned(format!("{}{}", ELLIPSIS, kept))
+}
+
+/// Keep the start of `source`, replacing the rest with an ellipsis, so that it
+/// fits in `width` columns.
+fn truncate_end(source: Cow<'_, str>, width: usize) -> Cow<'_, str> {
+    if display_width(&source) <= width {
+        return source;
+    }
+
+    let kept: String = take_width(source.chars(), width.saturating_sub(1))
+        .into_iter()
+        .collect();
+
+    Cow::Owned(format!("{}{}", kept, ELLIPSIS))
+}
+
+/// Keep the start and end of `source`, replacing the middle with an ellipsis,
+/// so that it fits in `width` columns.
+fn truncate_middle(source: &str, width: usize) -> String {
+    if display_width(source) <= width {
+        return source.to_string();
+    }
+
+    let space = width.saturating_sub(1);
+    let start: String = take_width(source.chars(), space - space / 2)
+        .into_iter()
+        .collect();
+    let end: String = take_width(source.chars().rev(), space / 2)
+        .into_iter()
+        .rev()
+        .collect();
+
+    format!("{}{}{}", start, ELLIPSIS, end)
+}
+
+/// The leading characters of `chars` that fit in `width` columns.
+fn take_width(chars: impl Iterator<Item = char>, width: usize) -> Vec<char> {
+    let mut used = 0;
+
+    chars
+        .take_while(|ch| {
+            used += UnicodeWidthChar::width(*ch).unwrap_or(0);
+            used <= width
+        })
+        .collect()
+}
+
+fn trim_line_ending(line: Cow<'_, str>) -> Cow<'_, str> {
+    let is_newline = |ch| ch == '\r' || ch == '\n';
+
+    match line {
+        Cow::Borrowed(line) => Cow::Borrowed(line.trim_end_matches(is_newline)),
+        Cow::Owned(line) => Cow::Owned(line.trim_end_matches(is_newline).to_string()),
+    }
+}
+
+/// A line of unlabelled source that is shown around a labelled line.
+#[derive(Clone, Debug)]
+pub(crate) struct ContextLine<'doc> {
+    config: &'doc dyn crate::Config,
+    line_number: usize,
+    source: Cow<'doc, str>,
+}
+
+impl<'doc> ContextLine<'doc> {
+    pub(crate) fn gutter_separator(&self) -> String {
+        self.config.gutter_separator()
+    }
+
+    pub(crate) fn line_number(&self) -> usize {
+        self.line_number
+    }
+
+    pub(crate) fn source(&self) -> &str {
+        &self.source
+    }
+}
+
+/// Whether `ch` is in one of the blocks of right-to-left scripts, like Hebrew
+/// and Arabic.
+fn is_rtl(ch: char) -> bool {
+    matches!(
+        ch,
+        '\u{0590}'..='\u{08FF}'
+            | '\u{FB1D}'..='\u{FDFF}'
+            | '\u{FE70}'..='\u{FEFF}'
+            | '\u{10800}'..='\u{10FFF}'
+            | '\u{1E800}'..='\u{1EFFF}'
+    )
+}
+
+/// The number of terminal columns a string occupies, so that underlines line
+/// up with multi-byte and double-width characters.
+pub(crate) fn display_width(source: &str) -> usize {
+    UnicodeWidthStr::width(source)
+}
+
 #[derive(Clone)]
 pub struct LabelledLine<'doc, Files: ReportingFiles> {
     source_line: SourceLine<'doc, Files>,
     label: &'doc Label<Files::Span>,
+    /// The last line shown by the previous label, if it's in the same file.
+    previous_line: Option<usize>,
+    /// The line of the next label, if it's in the same file.
+    next_line: Option<usize>,
+    /// The number of columns that every row of the label is indented by.
+    indent: usize,
 }
 
 impl<'doc, Files: ReportingFiles> LabelledLine<'doc, Files> {
@@ -133,16 +762,101 @@ impl<'doc, Files: ReportingFiles> LabelledLine<'doc, Files> {
         source_line: SourceLine<'doc, Files>,
         label: &'doc Label<Files::Span>,
     ) -> LabelledLine<'doc, Files> {
-        LabelledLine { source_line, label }
+        LabelledLine {
+            source_line,
+            label,
+            previous_line: None,
+            next_line: None,
+            indent: 0,
+        }
     }
 
-    pub(crate) fn mark(&self) -> &'static str {
-        match self.label.style {
-            LabelStyle::Primary => "^",
-            LabelStyle::Secondary => "-",
+    /// Avoid repeating context lines that the neighboring labels show.
+    pub(crate) fn with_neighbors(
+        mut self,
+        previous_line: Option<usize>,
+        next_line: Option<usize>,
+    ) -> LabelledLine<'doc, Files> {
+        self.previous_line = previous_line;
+        self.next_line = next_line;
+        self
+    }
+
+    pub(crate) fn with_indent(mut self, indent: usize) -> LabelledLine<'doc, Files> {
+        self.indent = indent;
+        self
+    }
+
+    pub(crate) fn indent(&self) -> usize {
+        self.indent
+    }
+
+    pub(crate) fn is_primary(&self) -> bool {
+        self.label.style == LabelStyle::Primary
+    }
+
+    /// Up to `Config::context_lines` lines before the labelled line, starting
+    /// after any lines that the previous label already showed.
+    pub(crate) fn context_before(&self) -> Vec<ContextLine<'doc>> {
+        let line = self.source_line.location().line;
+        let mut first = line.saturating_sub(self.source_line.config.context_lines());
+
+        if let Some(previous) = self.previous_line {
+            if previous <= line {
+                first = first.max(previous + 1);
+            }
+        }
+
+        (first..line)
+            .filter_map(|line| self.source_line.context_line(line))
+            .collect()
+    }
+
+    /// Up to `Config::context_lines` lines after the labelled line, stopping
+    /// at the end of the file or before the next label's line.
+    pub(crate) fn context_after(&self) -> Vec<ContextLine<'doc>> {
+        let line = self.source_line.location().line;
+        let mut last = line + self.source_line.config.context_lines();
+
+        if let Some(next) = self.next_line {
+            if next >= line {
+                last = last.min(next.saturating_sub(1));
+            }
+        }
+
+        (line + 1..=last)
+            .map(|line| self.source_line.context_line(line))
+            .take_while(|context| context.is_some())
+            .flatten()
+            .collect()
+    }
+
+    /// The last (zero-based) line that is shown for this label.
+    pub(crate) fn last_line(&self) -> usize {
+        match self.context_after().last() {
+            Some(context) => context.line_number() - 1,
+            None => self.source_line.location().line,
         }
     }
 
+    /// The width of the line numbers in the gutter, which is wide enough for
+    /// the last context line.
+    pub(crate) fn gutter_width(&self) -> usize {
+        (self.last_line() + 1).to_string().len()
+    }
+
+    /// The underline for a region that's `width` columns wide, made of
+    /// `Config::primary_mark` or `Config::secondary_mark`.
+    pub(crate) fn underline(&self, width: usize) -> String {
+        let config = self.source_line.config;
+        let mark = match self.label.style {
+            LabelStyle::Primary => config.primary_mark(),
+            LabelStyle::Secondary => config.secondary_mark(),
+        };
+
+        fill(&mark, width)
+    }
+
     pub(crate) fn style(&self) -> &'static str {
         match self.label.style {
             LabelStyle::Primary => "primary",
@@ -154,7 +868,193 @@ impl<'doc, Files: ReportingFiles> LabelledLine<'doc, Files> {
         self.label.message()
     }
 
+    /// The message, word-wrapped to fit in `Config::wrap_width` when it
+    /// starts at `column`. There are no lines if there's no message.
+    pub(crate) fn message_lines(&self, column: usize) -> Vec<String> {
+        let message = match self.message() {
+            None => return vec![],
+            Some(message) => message,
+        };
+
+        match self.source_line.config.wrap_width() {
+            None => vec![message.clone()],
+            Some(width) => wrap_words(message, width.saturating_sub(column)),
+        }
+    }
+
+    pub(crate) fn show_underline(&self) -> bool {
+        self.source_line.config.show_underline()
+    }
+
+    /// The message to show at the end of the source line, which is only used
+    /// when there's no underline row to show it on.
+    pub(crate) fn inline_message(&self) -> &Option<String> {
+        if self.show_underline() {
+            &None
+        } else {
+            self.message()
+        }
+    }
+
+    pub(crate) fn source_line(&self) -> &SourceLine<'doc, Files> {
+        &self.source_line
+    }
+}
+
+/// A suggested edit, shown as the line would read after the edit.
+#[derive(Clone)]
+pub(crate) struct SuggestionLine<'doc, Files: ReportingFiles> {
+    source_line: SourceLine<'doc, Files>,
+    suggestion: &'doc Suggestion<Files::Span>,
+}
+
+impl<'doc, Files: ReportingFiles> SuggestionLine<'doc, Files> {
+    pub(crate) fn new(
+        files: &'doc Files,
+        suggestion: &'doc Suggestion<Files::Span>,
+        config: &'doc dyn crate::Config,
+    ) -> SuggestionLine<'doc, Files> {
+        SuggestionLine {
+            source_line: SourceLine::for_span(files, &suggestion.span, config),
+            suggestion,
+        }
+    }
+
     pub(crate) fn source_line(&self) -> &SourceLine<'doc, Files> {
         &self.source_line
     }
+
+    /// Whether `files` can resolve the suggestion's span, so that its line
+    /// can be shown.
+    pub(crate) fn resolves(&self) -> bool {
+        self.source_line.resolves()
+    }
+
+    pub(crate) fn prefix(&self) -> String {
+        self.source_line.config.help_prefix()
+    }
+
+    /// Whether the suggestion is laid out like `RustcBody`, from
+    /// `Config::rustc_style`.
+    pub(crate) fn rustc_style(&self) -> bool {
+        self.source_line.config.rustc_style()
+    }
+
+    pub(crate) fn leading_gutter_line(&self) -> bool {
+        self.source_line.config.leading_gutter_line()
+    }
+
+    pub(crate) fn message(&self) -> &'doc str {
+        &self.suggestion.message
+    }
+
+    fn is_insertion(&self) -> bool {
+        self.suggestion.span.is_empty()
+    }
+
+    fn is_deletion(&self) -> bool {
+        !self.is_insertion() && self.suggestion.replacement.is_empty()
+    }
+
+    pub(crate) fn before(&self) -> Cow<'doc, str> {
+        self.source_line.before_marked()
+    }
+
+    /// The replacement, or the source that's removed by a deletion.
+    pub(crate) fn edited(&self) -> Cow<'doc, str> {
+        if self.is_deletion() {
+            self.source_line.marked_line()
+        } else {
+            Cow::Borrowed(&self.suggestion.replacement)
+        }
+    }
+
+    pub(crate) fn after(&self) -> Cow<'doc, str> {
+        self.source_line.after_marked()
+    }
+
+    /// `+` under inserted text, `-` under deleted text and `~` under
+    /// replaced text.
+    pub(crate) fn mark(&self) -> &'static str {
+        if self.is_insertion() {
+            "+"
+        } else if self.is_deletion() {
+            "-"
+        } else {
+            "~"
+        }
+    }
+
+    pub(crate) fn style(&self) -> &'static str {
+        if self.is_deletion() {
+            "removed"
+        } else {
+            "inserted"
+        }
+    }
+
+    pub(crate) fn before_width(&self) -> usize {
+        display_width(&self.before())
+    }
+
+    pub(crate) fn underline_width(&self) -> usize {
+        display_width(&self.edited()).max(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fill, wrap_words, SourceLine};
+    use crate::{DefaultConfig, SimpleReportingFiles, SimpleSpan};
+
+    #[test]
+    fn test_wrap_words() {
+        assert_eq!(
+            wrap_words("the quick brown fox jumps", 10),
+            vec!["the quick", "brown fox", "jumps"]
+        );
+        assert_eq!(
+            wrap_words("a supercalifragilistic word", 10),
+            vec!["a", "supercalifragilistic", "word"]
+        );
+        assert_eq!(wrap_words("", 10), vec![""]);
+    }
+
+    #[test]
+    fn test_fill() {
+        assert_eq!(fill("^", 3), "^^^");
+        assert_eq!(fill("~-", 5), "~-~-~");
+        assert_eq!(fill("^", 0), "^");
+        assert_eq!(fill("", 3), "");
+    }
+
+    #[test]
+    fn test_fill_with_wide_marks() {
+        // `▔` is one column wide, but three bytes long
+        assert_eq!(fill("\u{2594}", 3), "\u{2594}\u{2594}\u{2594}");
+        // `＾` is two columns wide
+        assert_eq!(fill("\u{ff3e}", 4), "\u{ff3e}\u{ff3e}");
+        assert_eq!(fill("\u{ff3e}", 3), "\u{ff3e}");
+        assert_eq!(fill("\u{ff3e}", 1), "\u{ff3e}");
+    }
+
+    #[test]
+    #[should_panic(expected = "Couldn't get the location for 10..12 in test (4 bytes)")]
+    fn test_unresolved_location() {
+        let mut files = SimpleReportingFiles::default();
+        let file = files.add("test", "abc\n");
+        let span = SimpleSpan::new(file, 10, 12);
+
+        SourceLine::for_span(&files, &span, &DefaultConfig).location();
+    }
+
+    #[test]
+    #[should_panic(expected = "Couldn't get the marked source for 0..4 in test (11 bytes)")]
+    fn test_unresolved_source() {
+        let mut files = SimpleReportingFiles::default();
+        let file = files.add("test", "café\nabc\nd");
+        let span = SimpleSpan::new(file, 0, 4);
+
+        SourceLine::for_span(&files, &span, &DefaultConfig).marked();
+    }
 }
@@ -0,0 +1,242 @@
+use crate::{Diagnostic, ReportingSpan, Severity};
+use std::collections::HashMap;
+
+/// What a `SeverityPolicy` does with diagnostics that have a given code.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum CodeLevel {
+    Allow,
+    Deny,
+}
+
+/// Rules that change the severity of diagnostics before they're emitted, like
+/// rustc's `-D warnings`, `-A` and `--cap-lints` flags.
+///
+/// - `deny(code)` turns diagnostics with that code into errors
+/// - `allow(code)` drops diagnostics with that code
+/// - `deny_warnings()` turns all warnings into errors
+/// - `cap(severity)` lowers anything more severe than `severity` to it,
+///   after the other rules are applied
+///
+/// If a code is both denied and allowed, the last rule wins. A code's rule
+/// overrides `deny_warnings`, so an allowed warning is dropped even when
+/// warnings are denied.
+///
+/// A diagnostic's new severity is used for everything, including the
+/// stylesheet section it's rendered in, so a denied warning is styled like an
+/// error. Use the policy with `Emitter::severity_policy`, or apply it to a
+/// batch with `SeverityPolicy::apply` before `emit_all`.
+///
+/// ```
+/// use language_reporting::{Diagnostic, Severity, SeverityPolicy, SimpleSpan};
+///
+/// let policy = SeverityPolicy::new().deny_warnings().allow("W0002");
+///
+/// let unused: Diagnostic<SimpleSpan> = Diagnostic::new_warning("unused").with_code("W0001");
+/// let shadowed: Diagnostic<SimpleSpan> = Diagnostic::new_warning("shadowed").with_code("W0002");
+///
+/// assert_eq!(policy.severity(&unused), Some(Severity::Error));
+/// assert_eq!(policy.severity(&shadowed), None);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct SeverityPolicy {
+    codes: HashMap<String, CodeLevel>,
+    deny_warnings: bool,
+    cap: Option<Severity>,
+}
+
+impl SeverityPolicy {
+    /// A policy that leaves every diagnostic as it is.
+    pub fn new() -> SeverityPolicy {
+        SeverityPolicy::default()
+    }
+
+    /// Turn diagnostics with `code` into errors. Bugs stay bugs.
+    pub fn deny<S: Into<String>>(mut self, code: S) -> SeverityPolicy {
+        self.codes.insert(code.into(), CodeLevel::Deny);
+        self
+    }
+
+    /// Drop diagnostics with `code` entirely.
+    pub fn allow<S: Into<String>>(mut self, code: S) -> SeverityPolicy {
+        self.codes.insert(code.into(), CodeLevel::Allow);
+        self
+    }
+
+    /// Turn every warning into an error.
+    pub fn deny_warnings(mut self) -> SeverityPolicy {
+        self.deny_warnings = true;
+        self
+    }
+
+    /// Lower diagnostics that are more severe than `severity` to it.
+    pub fn cap(mut self, severity: Severity) -> SeverityPolicy {
+        self.cap = Some(severity);
+        self
+    }
+
+    /// The severity that `diagnostic` should be emitted with, or `None` if
+    /// it's allowed and shouldn't be emitted at all.
+    pub fn severity<Span: ReportingSpan>(&self, diagnostic: &Diagnostic<Span>) -> Option<Severity> {
+        let level = diagnostic
+            .code
+            .as_ref()
+            .and_then(|code| self.codes.get(code));
+
+        let mut severity = diagnostic.severity;
+
+        match level {
+            Some(CodeLevel::Allow) => return None,
+            Some(CodeLevel::Deny) if severity < Severity::Error => severity = Severity::Error,
+            Some(CodeLevel::Deny) => {}
+            None if self.deny_warnings && severity == Severity::Warning => {
+                severity = Severity::Error
+            }
+            None => {}
+        }
+
+        match self.cap {
+            Some(cap) if severity > cap => Some(cap),
+            _ => Some(severity),
+        }
+    }
+
+    /// `diagnostic` with the severity from `severity`, or `None` if it's
+    /// allowed.
+    pub fn apply<Span: ReportingSpan>(
+        &self,
+        diagnostic: Diagnostic<Span>,
+    ) -> Option<Diagnostic<Span>> {
+        let severity = self.severity(&diagnostic)?;
+
+        Some(Diagnostic {
+            severity,
+            ..diagnostic
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SeverityPolicy;
+    use crate::{Diagnostic, Severity, SimpleSpan};
+
+    fn diagnostic(severity: Severity, code: &str) -> Diagnostic<SimpleSpan> {
+        Diagnostic::new(severity, "message").with_code(code)
+    }
+
+    #[test]
+    fn test_default_policy() {
+        let policy = SeverityPolicy::new();
+
+        for &severity in &[
+            Severity::Bug,
+            Severity::Error,
+            Severity::Warning,
+            Severity::Help,
+        ] {
+            assert_eq!(policy.severity(&diagnostic(severity, "X")), Some(severity));
+        }
+    }
+
+    #[test]
+    fn test_deny_by_code() {
+        let policy = SeverityPolicy::new().deny("W0001");
+
+        assert_eq!(
+            policy.severity(&diagnostic(Severity::Warning, "W0001")),
+            Some(Severity::Error)
+        );
+        assert_eq!(
+            policy.severity(&diagnostic(Severity::Note, "W0001")),
+            Some(Severity::Error)
+        );
+        assert_eq!(
+            policy.severity(&diagnostic(Severity::Bug, "W0001")),
+            Some(Severity::Bug)
+        );
+        assert_eq!(
+            policy.severity(&diagnostic(Severity::Warning, "W0002")),
+            Some(Severity::Warning)
+        );
+
+        let uncoded: Diagnostic<SimpleSpan> = Diagnostic::new_warning("message");
+        assert_eq!(policy.severity(&uncoded), Some(Severity::Warning));
+    }
+
+    #[test]
+    fn test_deny_warnings() {
+        let policy = SeverityPolicy::new().deny_warnings();
+
+        assert_eq!(
+            policy.severity(&diagnostic(Severity::Warning, "W0001")),
+            Some(Severity::Error)
+        );
+        assert_eq!(
+            policy.severity(&diagnostic(Severity::Note, "N0001")),
+            Some(Severity::Note)
+        );
+
+        let uncoded: Diagnostic<SimpleSpan> = Diagnostic::new_warning("message");
+        assert_eq!(policy.severity(&uncoded), Some(Severity::Error));
+    }
+
+    #[test]
+    fn test_allow_overrides_deny() {
+        let policy = SeverityPolicy::new().deny_warnings().allow("W0002");
+
+        assert_eq!(
+            policy.severity(&diagnostic(Severity::Warning, "W0002")),
+            None
+        );
+        assert_eq!(
+            policy.severity(&diagnostic(Severity::Warning, "W0001")),
+            Some(Severity::Error)
+        );
+
+        // The last rule for a code wins
+        let policy = SeverityPolicy::new().deny("W0002").allow("W0002");
+        assert_eq!(
+            policy.severity(&diagnostic(Severity::Warning, "W0002")),
+            None
+        );
+
+        let policy = SeverityPolicy::new().allow("W0002").deny("W0002");
+        assert_eq!(
+            policy.severity(&diagnostic(Severity::Warning, "W0002")),
+            Some(Severity::Error)
+        );
+    }
+
+    #[test]
+    fn test_cap() {
+        let policy = SeverityPolicy::new().deny_warnings().cap(Severity::Warning);
+
+        assert_eq!(
+            policy.severity(&diagnostic(Severity::Bug, "E0001")),
+            Some(Severity::Warning)
+        );
+        assert_eq!(
+            policy.severity(&diagnostic(Severity::Warning, "W0001")),
+            Some(Severity::Warning)
+        );
+        assert_eq!(
+            policy.severity(&diagnostic(Severity::Help, "H0001")),
+            Some(Severity::Help)
+        );
+    }
+
+    #[test]
+    fn test_apply() {
+        let policy = SeverityPolicy::new().deny("W0001").allow("W0002");
+
+        let denied = policy
+            .apply(diagnostic(Severity::Warning, "W0001"))
+            .unwrap();
+        assert_eq!(denied.severity, Severity::Error);
+        assert_eq!(denied.code.as_deref(), Some("W0001"));
+
+        assert!(policy
+            .apply(diagnostic(Severity::Warning, "W0002"))
+            .is_none());
+    }
+}
@@ -0,0 +1,499 @@
+//! Emitting a batch of diagnostics as a SARIF 2.1.0 log, for CI systems and
+//! code scanning tools that ingest SARIF.
+
+use crate::diagnostic::{Diagnostic, Label};
+use crate::span::{FileName, ReportingFiles, ReportingSpan};
+use crate::{LabelStyle, Severity};
+use serde_derive::Serialize;
+use std::io;
+use std::path::Path;
+
+const SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+/// Emit `diagnostics` as a SARIF log with a single run of the tool named
+/// `tool_name`.
+///
+/// Each diagnostic is a result, whose `ruleId` is the diagnostic's code and
+/// whose `level` is `error` for bugs and errors, `warning` for warnings, and
+/// `note` for notes and help messages. Primary labels are the result's
+/// locations, and secondary labels are its related locations. Lines and
+/// columns are one-based, and columns count characters, so the run's
+/// `columnKind` is `unicodeCodePoints`.
+///
+/// File names map to artifact URIs like this:
+///
+/// - A `FileName::Real` path is a relative URI reference, or a `file://`
+///   URI if the path is absolute.
+/// - A `FileName::Verbatim` name is used as a relative URI reference.
+/// - A `FileName::Virtual` file isn't an artifact, so its labels have a
+///   logical location named after the path instead of a physical location.
+///
+/// Characters that aren't allowed in a URI path are percent-encoded.
+///
+/// ```rust
+/// use language_reporting::*;
+///
+/// let mut files = SimpleReportingFiles::default();
+/// let file = files.add("src/main.lang", "(+ test \"\")\n");
+///
+/// let error = Diagnostic::new_error("Unexpected string")
+///     .with_code("E0001")
+///     .with_label(Label::new_primary(SimpleSpan::new(file, 8, 10)));
+///
+/// let mut output = vec![];
+/// emit_sarif(&mut output, "my-compiler", &files, &[error]).unwrap();
+///
+/// let log: serde_json::Value = serde_json::from_slice(&output).unwrap();
+/// let result = &log["runs"][0]["results"][0];
+///
+/// assert_eq!(result["ruleId"], "E0001");
+/// assert_eq!(result["level"], "error");
+/// assert_eq!(
+///     result["locations"][0]["physicalLocation"]["artifactLocation"]["uri"],
+///     "src/main.lang"
+/// );
+/// ```
+pub fn emit_sarif<W, Files: ReportingFiles>(
+    mut writer: W,
+    tool_name: &str,
+    files: &Files,
+    diagnostics: &[Diagnostic<Files::Span>],
+) -> io::Result<()>
+where
+    W: io::Write,
+{
+    let log = SarifLog {
+        schema: SCHEMA,
+        version: "2.1.0",
+        runs: vec![SarifRun {
+            tool: SarifTool {
+                driver: SarifDriver { name: tool_name },
+            },
+            column_kind: "unicodeCodePoints",
+            results: diagnostics
+                .iter()
+                .map(|diagnostic| SarifResult::new(files, diagnostic))
+                .collect(),
+        }],
+    };
+
+    serde_json::to_writer(&mut writer, &log).map_err(io::Error::other)?;
+
+    writeln!(writer)
+}
+
+#[derive(Debug, Serialize)]
+struct SarifLog<'doc> {
+    #[serde(rename = "$schema")]
+    schema: &'static str,
+    version: &'static str,
+    runs: Vec<SarifRun<'doc>>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifRun<'doc> {
+    tool: SarifTool<'doc>,
+    column_kind: &'static str,
+    results: Vec<SarifResult<'doc>>,
+}
+
+#[derive(Debug, Serialize)]
+struct SarifTool<'doc> {
+    driver: SarifDriver<'doc>,
+}
+
+#[derive(Debug, Serialize)]
+struct SarifDriver<'doc> {
+    name: &'doc str,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifResult<'doc> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rule_id: Option<&'doc str>,
+    level: &'static str,
+    message: SarifMessage<'doc>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    locations: Vec<SarifLocation<'doc>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    related_locations: Vec<SarifLocation<'doc>>,
+}
+
+impl<'doc> SarifResult<'doc> {
+    fn new<Files: ReportingFiles>(
+        files: &Files,
+        diagnostic: &'doc Diagnostic<Files::Span>,
+    ) -> SarifResult<'doc> {
+        let locations = |style: LabelStyle| {
+            diagnostic
+                .labels
+                .iter()
+                .filter(|label| label.style == style)
+                .map(|label| SarifLocation::new(files, label))
+                .collect()
+        };
+
+        SarifResult {
+            rule_id: diagnostic.code.as_deref(),
+            level: match diagnostic.severity {
+                Severity::Bug | Severity::Error => "error",
+                Severity::Warning => "warning",
+                Severity::Note | Severity::Help => "note",
+            },
+            message: SarifMessage {
+                text: &diagnostic.message,
+            },
+            locations: locations(LabelStyle::Primary),
+            related_locations: locations(LabelStyle::Secondary),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct SarifMessage<'doc> {
+    text: &'doc str,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifLocation<'doc> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    physical_location: Option<SarifPhysicalLocation>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    logical_locations: Vec<SarifLogicalLocation>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<SarifMessage<'doc>>,
+}
+
+impl<'doc> SarifLocation<'doc> {
+    fn new<Files: ReportingFiles>(
+        files: &Files,
+        label: &'doc Label<Files::Span>,
+    ) -> SarifLocation<'doc> {
+        let file = files.file_id(label.span.clone());
+        let message = label.message.as_ref().map(|text| SarifMessage { text });
+
+        let uri = match files.file_name(file) {
+            FileName::Virtual(path) => {
+                return SarifLocation {
+                    physical_location: None,
+                    logical_locations: vec![SarifLogicalLocation {
+                        name: path.display().to_string(),
+                    }],
+                    message,
+                }
+            }
+            FileName::Real(path) => path_uri(&path),
+            FileName::Verbatim(name) => percent_encode(&name),
+        };
+
+        let position = |index| files.location(file, index);
+
+        // A span that `files` can't resolve still has a location, just
+        // without a region
+        let region = match (position(label.span.start()), position(label.span.end())) {
+            (Some(start), Some(end)) => Some(SarifRegion {
+                start_line: start.line + 1,
+                start_column: start.column + 1,
+                end_line: end.line + 1,
+                end_column: end.column + 1,
+            }),
+            _ => None,
+        };
+
+        SarifLocation {
+            physical_location: Some(SarifPhysicalLocation {
+                artifact_location: SarifArtifactLocation { uri },
+                region,
+            }),
+            logical_locations: vec![],
+            message,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifPhysicalLocation {
+    artifact_location: SarifArtifactLocation,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    region: Option<SarifRegion>,
+}
+
+#[derive(Debug, Serialize)]
+struct SarifArtifactLocation {
+    uri: String,
+}
+
+/// A one-based region. The end column is the column after the last
+/// character.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifRegion {
+    start_line: usize,
+    start_column: usize,
+    end_line: usize,
+    end_column: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct SarifLogicalLocation {
+    name: String,
+}
+
+/// A relative URI reference for a relative path, or a `file://` URI for an
+/// absolute one. Backslashes are separators, as they are on Windows.
+fn path_uri(path: &Path) -> String {
+    let path = percent_encode(&path.to_string_lossy().replace('\\', "/"));
+
+    if path.starts_with('/') {
+        format!("file://{}", path)
+    } else if path.get(1..2) == Some(":") {
+        // A Windows path with a drive letter, like `C:/src/main.lang`
+        format!("file:///{}", path)
+    } else {
+        path
+    }
+}
+
+/// Percent-encode the characters that aren't allowed in a URI path.
+fn percent_encode(path: &str) -> String {
+    let mut encoded = String::with_capacity(path.len());
+
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' => encoded.push(byte as char),
+            b'-' | b'.' | b'_' | b'~' | b'/' | b':' | b'@' | b'!' | b'$' | b'&' | b'\''
+            | b'(' | b')' | b'*' | b'+' | b',' | b';' | b'=' => encoded.push(byte as char),
+            other => encoded.push_str(&format!("%{:02X}", other)),
+        }
+    }
+
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simple::{SimpleReportingFiles, SimpleSpan};
+    use crate::span::Location;
+    use serde_json::Value;
+    use std::borrow::Cow;
+    use std::path::PathBuf;
+
+    fn emit(files: &SimpleReportingFiles, diagnostics: &[Diagnostic<SimpleSpan>]) -> Value {
+        let mut output = vec![];
+        emit_sarif(&mut output, "test-compiler", files, diagnostics).unwrap();
+
+        assert_eq!(output.last(), Some(&b'\n'));
+
+        serde_json::from_slice(&output).unwrap()
+    }
+
+    /// Check the parts of the SARIF 2.1.0 schema that the log uses: the
+    /// required properties, their types, and the allowed values.
+    fn validate(log: &Value) {
+        assert_eq!(log["version"], "2.1.0");
+        assert!(log["$schema"].is_string());
+
+        let runs = log["runs"].as_array().expect("runs is an array");
+        assert_eq!(runs.len(), 1);
+
+        let run = &runs[0];
+        assert!(run["tool"]["driver"]["name"].is_string());
+        assert_eq!(run["columnKind"], "unicodeCodePoints");
+
+        for result in run["results"].as_array().expect("results is an array") {
+            assert!(result["message"]["text"].is_string());
+            assert!(result["ruleId"].is_null() || result["ruleId"].is_string());
+            assert!(["error", "warning", "note", "none"]
+                .iter()
+                .any(|level| result["level"] == *level));
+
+            let locations = ["locations", "relatedLocations"]
+                .iter()
+                .filter_map(|key| result[*key].as_array())
+                .flatten();
+
+            for location in locations {
+                let physical = &location["physicalLocation"];
+
+                if physical.is_null() {
+                    assert!(location["logicalLocations"][0]["name"].is_string());
+                    continue;
+                }
+
+                assert!(physical["artifactLocation"]["uri"].is_string());
+
+                let region = &physical["region"];
+
+                if !region.is_null() {
+                    for key in &["startLine", "startColumn", "endLine", "endColumn"] {
+                        assert!(region[*key].as_u64().unwrap() >= 1, "{} is positive", key);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_emit_sarif() {
+        let mut files = SimpleReportingFiles::default();
+        let file = files.add("src/main.lang", "(define test 123)\n(+ test \"é\")\n");
+
+        let diagnostics = vec![
+            Diagnostic::new_error("Unexpected type in `+` application")
+                .with_code("E0001")
+                .with_label(
+                    Label::new_primary(SimpleSpan::new(file, 26, 30))
+                        .with_message("Expected integer but got string"),
+                )
+                .with_label(
+                    Label::new_secondary(SimpleSpan::new(file, 8, 12)).with_message("Defined here"),
+                ),
+            Diagnostic::new_warning("Unused result"),
+            Diagnostic::new(Severity::Bug, "Crashed"),
+            Diagnostic::new_help("Try this"),
+        ];
+
+        let log = emit(&files, &diagnostics);
+        validate(&log);
+
+        assert_eq!(log["runs"][0]["tool"]["driver"]["name"], "test-compiler");
+
+        let results = &log["runs"][0]["results"];
+        assert_eq!(
+            results[0],
+            serde_json::json!({
+                "ruleId": "E0001",
+                "level": "error",
+                "message": { "text": "Unexpected type in `+` application" },
+                "locations": [{
+                    "physicalLocation": {
+                        "artifactLocation": { "uri": "src/main.lang" },
+                        "region": {
+                            "startLine": 2,
+                            "startColumn": 9,
+                            "endLine": 2,
+                            "endColumn": 12,
+                        },
+                    },
+                    "message": { "text": "Expected integer but got string" },
+                }],
+                "relatedLocations": [{
+                    "physicalLocation": {
+                        "artifactLocation": { "uri": "src/main.lang" },
+                        "region": {
+                            "startLine": 1,
+                            "startColumn": 9,
+                            "endLine": 1,
+                            "endColumn": 13,
+                        },
+                    },
+                    "message": { "text": "Defined here" },
+                }],
+            })
+        );
+        assert_eq!(
+            results[1],
+            serde_json::json!({
+                "level": "warning",
+                "message": { "text": "Unused result" },
+            })
+        );
+        assert_eq!(results[2]["level"], "error");
+        assert_eq!(results[3]["level"], "note");
+    }
+
+    #[test]
+    fn test_empty_log() {
+        let log = emit(&SimpleReportingFiles::default(), &[]);
+        validate(&log);
+
+        assert_eq!(log["runs"][0]["results"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn test_path_uris() {
+        assert_eq!(path_uri(Path::new("src/main.lang")), "src/main.lang");
+        assert_eq!(path_uri(Path::new("/home/me/main.lang")), "file:///home/me/main.lang");
+        assert_eq!(path_uri(Path::new("src\\main.lang")), "src/main.lang");
+        assert_eq!(path_uri(Path::new("C:\\src\\main.lang")), "file:///C:/src/main.lang");
+        assert_eq!(path_uri(Path::new("my src/#1 é.lang")), "my%20src/%231%20%C3%A9.lang");
+    }
+
+    /// One file with a configurable `FileName`, to test the names that
+    /// `SimpleReportingFiles` doesn't produce.
+    #[derive(Debug, Clone)]
+    struct NamedFile(fn() -> FileName);
+
+    impl ReportingFiles for NamedFile {
+        type Span = SimpleSpan;
+        type FileId = usize;
+
+        fn byte_span(&self, file: usize, from_index: usize, to_index: usize) -> Option<SimpleSpan> {
+            Some(SimpleSpan::new(file, from_index, to_index))
+        }
+
+        fn file_id(&self, span: SimpleSpan) -> usize {
+            span.file_id
+        }
+
+        fn file_name(&self, _file: usize) -> FileName {
+            (self.0)()
+        }
+
+        fn byte_index(&self, _file: usize, _line: usize, column: usize) -> Option<usize> {
+            Some(column)
+        }
+
+        fn location(&self, _file: usize, byte_index: usize) -> Option<Location> {
+            Some(Location::new(0, byte_index))
+        }
+
+        fn line_span(&self, file: usize, _line: usize) -> Option<SimpleSpan> {
+            Some(SimpleSpan::new(file, 0, 10))
+        }
+
+        fn source(&self, _span: SimpleSpan) -> Option<Cow<'_, str>> {
+            None
+        }
+    }
+
+    fn emit_named(name: fn() -> FileName) -> Value {
+        let diagnostic = Diagnostic::new_error("Unexpected string")
+            .with_label(Label::new_primary(SimpleSpan::new(0, 2, 4)));
+
+        let mut output = vec![];
+        emit_sarif(&mut output, "test-compiler", &NamedFile(name), &[diagnostic]).unwrap();
+
+        let log = serde_json::from_slice(&output).unwrap();
+        validate(&log);
+
+        log["runs"][0]["results"][0]["locations"][0].clone()
+    }
+
+    #[test]
+    fn test_file_names() {
+        assert_eq!(
+            emit_named(|| FileName::Verbatim("main lang".to_string())),
+            serde_json::json!({
+                "physicalLocation": {
+                    "artifactLocation": { "uri": "main%20lang" },
+                    "region": { "startLine": 1, "startColumn": 3, "endLine": 1, "endColumn": 5 },
+                },
+            })
+        );
+        assert_eq!(
+            emit_named(|| FileName::Virtual(PathBuf::from("repl"))),
+            serde_json::json!({ "logicalLocations": [{ "name": "repl" }] })
+        );
+        assert_eq!(
+            emit_named(|| FileName::Real(PathBuf::from("/src/main.lang")))["physicalLocation"]
+                ["artifactLocation"]["uri"],
+            "file:///src/main.lang"
+        );
+    }
+}
@@ -1,9 +1,77 @@
+use crate::span::{char_column, column_offset};
+use std::borrow::Cow;
+
 #[derive(Debug, Clone)]
 pub struct SimpleFile {
     name: String,
     contents: String,
+    /// The byte index of the start of each line, so that lookups can use
+    /// a binary search rather than re-scanning the contents.
+    line_starts: Vec<usize>,
+}
+
+impl SimpleFile {
+    pub(crate) fn new(name: String, contents: String) -> SimpleFile {
+        let line_starts = std::iter::once(0)
+            .chain(contents.match_indices('\n').map(|(pos, _)| pos + 1))
+            .collect();
+
+        SimpleFile {
+            name,
+            contents,
+            line_starts,
+        }
+    }
+
+    pub(crate) fn contents(&self) -> &str {
+        &self.contents
+    }
+
+    /// The line containing `index`. The newline that terminates a line is
+    /// part of that line.
+    fn line_index(&self, index: usize) -> usize {
+        self.line_starts.partition_point(|&start| start <= index) - 1
+    }
+
+    /// The line and column of a byte index, if it's inside the file.
+    pub(crate) fn location(&self, index: usize) -> Option<crate::Location> {
+        if index > self.contents.len() {
+            return None;
+        }
+
+        let line = self.line_index(index);
+        let start = self.line_starts[line];
+        let column = char_column(&self.contents[start..], index - start);
+
+        Some(crate::Location::new(line, column))
+    }
+
+    /// The byte index of a line and (character) column, if the line has
+    /// that column.
+    pub(crate) fn byte_index(&self, line: usize, column: usize) -> Option<usize> {
+        let (start, end) = self.line_range(line)?;
+
+        Some(start + column_offset(&self.contents[start..end], column)?)
+    }
+
+    /// The byte range of a line, not including its trailing `\n` or `\r\n`.
+    pub(crate) fn line_range(&self, line: usize) -> Option<(usize, usize)> {
+        let start = *self.line_starts.get(line)?;
+        let end = match self.line_starts.get(line + 1) {
+            Some(next) if self.contents[..next - 1].ends_with('\r') => next - 2,
+            Some(next) => next - 1,
+            None => self.contents.len(),
+        };
+
+        Some((start, end))
+    }
 }
 
+/// An in-memory `ReportingFiles` implementation.
+///
+/// The start of every line is indexed when a file is added, so `byte_index`,
+/// `location` and `line_span` are binary searches rather than scans over the
+/// whole file.
 #[derive(Debug, Clone, Default)]
 pub struct SimpleReportingFiles {
     files: Vec<SimpleFile>,
@@ -11,10 +79,7 @@ pub struct SimpleReportingFiles {
 
 impl SimpleReportingFiles {
     pub fn add(&mut self, name: impl Into<String>, value: impl Into<String>) -> usize {
-        self.files.push(SimpleFile {
-            name: name.into(),
-            contents: value.into(),
-        });
+        self.files.push(SimpleFile::new(name.into(), value.into()));
 
         self.files.len() - 1
     }
@@ -37,68 +102,31 @@ impl crate::ReportingFiles for SimpleReportingFiles {
     }
 
     fn byte_index(&self, file: usize, line: usize, column: usize) -> Option<usize> {
-        let source = &self.files[file].contents;
-        let mut seen_lines = 0;
-        let mut seen_bytes = 0;
-
-        for (pos, _) in source.match_indices('\n') {
-            if seen_lines == line {
-                return Some(seen_bytes + column);
-            } else {
-                seen_lines += 1;
-                seen_bytes = pos + 1;
-            }
-        }
-
-        None
+        self.files[file].byte_index(line, column)
     }
 
     fn location(&self, file: usize, index: usize) -> Option<crate::Location> {
-        let source = &self.files[file].contents;
-        let mut seen_lines = 0;
-        let mut seen_bytes = 0;
-
-        for (pos, _) in source.match_indices('\n') {
-            if pos > index {
-                return Some(crate::Location::new(seen_lines, index - seen_bytes));
-            } else {
-                seen_lines += 1;
-                seen_bytes = pos;
-            }
-        }
-
-        None
+        self.files[file].location(index)
     }
 
     fn line_span(&self, file: usize, line: usize) -> Option<Self::Span> {
-        let source = &self.files[file].contents;
-        let mut seen_lines = 0;
-        let mut seen_bytes = 0;
-
-        for (pos, _) in source.match_indices('\n') {
-            if seen_lines == line {
-                return Some(SimpleSpan::new(file, seen_bytes, pos));
-            } else {
-                seen_lines += 1;
-                seen_bytes = pos + 1;
-            }
-        }
+        let (start, end) = self.files[file].line_range(line)?;
 
-        None
+        Some(SimpleSpan::new(file, start, end))
     }
 
-    fn source(&self, span: SimpleSpan) -> Option<String> {
-        let source = &self.files[span.file_id].contents;
+    fn source(&self, span: SimpleSpan) -> Option<Cow<'_, str>> {
+        let source = self.files[span.file_id].contents.get(span.start..span.end)?;
 
-        Some(source[span.start..span.end].to_string())
+        Some(Cow::Borrowed(source))
     }
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct SimpleSpan {
-    file_id: usize,
-    start: usize,
-    end: usize,
+    pub(crate) file_id: usize,
+    pub(crate) start: usize,
+    pub(crate) end: usize,
 }
 
 impl SimpleSpan {
@@ -134,4 +162,346 @@ impl crate::ReportingSpan for SimpleSpan {
     fn end(&self) -> usize {
         self.end
     }
+
+    fn merge(&self, other: &Self) -> Self {
+        assert_eq!(
+            self.file_id, other.file_id,
+            "Can't merge SimpleSpans in different files"
+        );
+
+        SimpleSpan::new(
+            self.file_id,
+            self.start.min(other.start),
+            self.end.max(other.end),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Location, ReportingFiles, ReportingSpan};
+
+    fn span_source(files: &SimpleReportingFiles, span: Option<SimpleSpan>) -> Option<String> {
+        span.and_then(|span| files.source(span))
+            .map(Cow::into_owned)
+    }
+
+    #[test]
+    fn test_location() {
+        let mut files = SimpleReportingFiles::default();
+        let file = files.add("test", "abc\ndef\nghi\n");
+
+        assert_eq!(files.location(file, 0), Some(Location::new(0, 0)));
+        assert_eq!(files.location(file, 2), Some(Location::new(0, 2)));
+        assert_eq!(files.location(file, 4), Some(Location::new(1, 0)));
+        assert_eq!(files.location(file, 6), Some(Location::new(1, 2)));
+        assert_eq!(files.location(file, 9), Some(Location::new(2, 1)));
+    }
+
+    #[test]
+    fn test_location_on_unterminated_last_line() {
+        let mut files = SimpleReportingFiles::default();
+        let file = files.add("test", "abc\ndef");
+
+        assert_eq!(files.location(file, 4), Some(Location::new(1, 0)));
+        assert_eq!(files.location(file, 6), Some(Location::new(1, 2)));
+        assert_eq!(files.location(file, 7), Some(Location::new(1, 3)));
+        assert_eq!(files.location(file, 8), None);
+    }
+
+    #[test]
+    fn test_location_without_newlines() {
+        let mut files = SimpleReportingFiles::default();
+        let file = files.add("test", "abcdef");
+
+        assert_eq!(files.location(file, 0), Some(Location::new(0, 0)));
+        assert_eq!(files.location(file, 3), Some(Location::new(0, 3)));
+        assert_eq!(files.location(file, 6), Some(Location::new(0, 6)));
+    }
+
+    #[test]
+    fn test_columns_count_characters() {
+        let mut files = SimpleReportingFiles::default();
+        let source = "abc\n(+ \"café\" \"日本\" 🦀)\n";
+        let file = files.add("test", source);
+
+        for (column, text) in &[(0, "(+"), (4, "café"), (11, "日本"), (15, "🦀"), (16, ")")] {
+            let index = source.find(text).unwrap();
+
+            assert_eq!(files.location(file, index), Some(Location::new(1, *column)));
+            assert_eq!(files.byte_index(file, 1, *column), Some(index));
+        }
+
+        // Just past the last character is the end of the line
+        assert_eq!(files.byte_index(file, 1, 17), Some(source.len() - 1));
+        assert_eq!(files.byte_index(file, 1, 18), None);
+
+        // An index inside a character is in that character's column
+        let inside = source.find("é").unwrap() + 1;
+        assert_eq!(files.location(file, inside), Some(Location::new(1, 7)));
+    }
+
+    #[test]
+    fn test_byte_index() {
+        let mut files = SimpleReportingFiles::default();
+        let file = files.add("test", "abc\ndef\nghi");
+
+        assert_eq!(files.byte_index(file, 0, 0), Some(0));
+        assert_eq!(files.byte_index(file, 1, 2), Some(6));
+        assert_eq!(files.byte_index(file, 2, 1), Some(9));
+        assert_eq!(files.byte_index(file, 3, 0), None);
+    }
+
+    #[test]
+    fn test_lookups_in_a_large_file() {
+        let mut files = SimpleReportingFiles::default();
+
+        // 4MB of source, 100,000 lines of 40 bytes each
+        let line_count = 100_000;
+        let source: String = (0..line_count)
+            .map(|line| format!("{:039}\n", line))
+            .collect();
+        assert_eq!(source.len(), 4_000_000);
+
+        let file = files.add("large", source);
+
+        for &line in &[0, 1, line_count / 2, line_count - 2, line_count - 1] {
+            let start = files.byte_index(file, line, 0).unwrap();
+            assert_eq!(start, line * 40);
+
+            assert_eq!(
+                files.location(file, start + 7),
+                Some(Location::new(line, 7))
+            );
+
+            let span = files.line_span(file, line);
+            assert_eq!(span_source(&files, span), Some(format!("{:039}", line)));
+        }
+    }
+
+    /// A straightforward scan over the source, which the line index must
+    /// agree with.
+    fn scan_location(source: &str, index: usize) -> Location {
+        let line = source[..index].matches('\n').count();
+        let line_start = source[..index].rfind('\n').map(|pos| pos + 1).unwrap_or(0);
+
+        Location::new(line, index - line_start)
+    }
+
+    #[test]
+    fn test_lookups_match_a_linear_scan() {
+        for source in &[
+            "",
+            "\n",
+            "abc",
+            "abc\n",
+            "abc\ndef",
+            "\n\nabc\n\ndef\n",
+            "a\nbc\ndef\nghij",
+        ] {
+            let mut files = SimpleReportingFiles::default();
+            let file = files.add("test", *source);
+
+            for index in 0..=source.len() {
+                let location = scan_location(source, index);
+
+                assert_eq!(files.location(file, index), Some(location), "{:?}", source);
+                assert_eq!(
+                    files.byte_index(file, location.line, location.column),
+                    Some(index),
+                    "{:?}",
+                    source
+                );
+
+                let line = span_source(&files, files.line_span(file, location.line));
+                let expected = source.split('\n').nth(location.line).map(str::to_string);
+                assert_eq!(line, expected, "{:?}", source);
+            }
+
+            assert_eq!(files.location(file, source.len() + 1), None);
+        }
+    }
+
+    #[test]
+    fn test_source_borrows_from_the_file() {
+        let mut files = SimpleReportingFiles::default();
+        let file = files.add("test", "abc\ndef\n");
+
+        match files.source(SimpleSpan::new(file, 4, 7)) {
+            Some(Cow::Borrowed(source)) => assert_eq!(source, "def"),
+            other => panic!("expected borrowed source, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_source_out_of_range() {
+        let mut files = SimpleReportingFiles::default();
+        let file = files.add("test", "abc\ndef");
+
+        assert_eq!(files.source(SimpleSpan::new(file, 4, 12)), None);
+    }
+
+    #[test]
+    fn test_source_inside_a_character() {
+        let mut files = SimpleReportingFiles::default();
+        let file = files.add("test", "café");
+
+        assert_eq!(files.source(SimpleSpan::new(file, 0, 4)), None);
+    }
+
+    #[test]
+    fn test_span_len() {
+        assert_eq!(SimpleSpan::new(0, 3, 7).len(), 4);
+        assert!(!SimpleSpan::new(0, 3, 7).is_empty());
+        assert_eq!(SimpleSpan::new(0, 5, 5).len(), 0);
+        assert!(SimpleSpan::new(0, 5, 5).is_empty());
+    }
+
+    #[test]
+    fn test_disjoint_spans() {
+        let left = SimpleSpan::new(0, 0, 3);
+        let right = SimpleSpan::new(0, 5, 8);
+
+        assert!(!left.contains(&right));
+        assert!(!left.intersects(&right));
+        assert!(!right.intersects(&left));
+        assert_eq!(left.merge(&right), SimpleSpan::new(0, 0, 8));
+        assert_eq!(right.merge(&left), SimpleSpan::new(0, 0, 8));
+
+        // Adjacent spans don't share a byte
+        assert!(!left.intersects(&SimpleSpan::new(0, 3, 5)));
+    }
+
+    #[test]
+    fn test_nested_spans() {
+        let outer = SimpleSpan::new(0, 2, 10);
+        let inner = SimpleSpan::new(0, 4, 6);
+
+        assert!(outer.contains(&inner));
+        assert!(!inner.contains(&outer));
+        assert!(outer.contains(&outer));
+        assert!(outer.intersects(&inner));
+        assert!(inner.intersects(&outer));
+        assert_eq!(outer.merge(&inner), outer);
+        assert_eq!(inner.merge(&outer), outer);
+    }
+
+    #[test]
+    fn test_overlapping_spans() {
+        let left = SimpleSpan::new(0, 2, 6);
+        let right = SimpleSpan::new(0, 4, 9);
+
+        assert!(!left.contains(&right));
+        assert!(!right.contains(&left));
+        assert!(left.intersects(&right));
+        assert!(right.intersects(&left));
+        assert_eq!(left.merge(&right), SimpleSpan::new(0, 2, 9));
+    }
+
+    #[test]
+    fn test_empty_spans() {
+        let span = SimpleSpan::new(0, 2, 6);
+        let empty = SimpleSpan::new(0, 4, 4);
+
+        assert!(span.contains(&empty));
+        assert!(span.contains(&SimpleSpan::new(0, 6, 6)));
+        assert!(!span.contains(&SimpleSpan::new(0, 7, 7)));
+        assert!(span.intersects(&empty));
+        assert!(!span.intersects(&SimpleSpan::new(0, 6, 6)));
+        assert!(!empty.intersects(&empty));
+        assert_eq!(empty.merge(&empty), empty);
+        assert_eq!(
+            empty.merge(&SimpleSpan::new(0, 8, 8)),
+            SimpleSpan::new(0, 4, 8)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Can't merge SimpleSpans in different files")]
+    fn test_merge_spans_in_different_files() {
+        SimpleSpan::new(0, 0, 3).merge(&SimpleSpan::new(1, 0, 3));
+    }
+
+    #[test]
+    fn test_line_span() {
+        let mut files = SimpleReportingFiles::default();
+        let file = files.add("test", "abc\ndef\n");
+
+        let first = files.line_span(file, 0);
+        assert_eq!(first.map(|span| (span.start(), span.end())), Some((0, 3)));
+        assert_eq!(span_source(&files, first), Some("abc".to_string()));
+
+        let second = files.line_span(file, 1);
+        assert_eq!(second.map(|span| (span.start(), span.end())), Some((4, 7)));
+        assert_eq!(span_source(&files, second), Some("def".to_string()));
+    }
+
+    #[test]
+    fn test_line_span_on_unterminated_last_line() {
+        let mut files = SimpleReportingFiles::default();
+        let file = files.add("test", "abc\ndef");
+
+        let last = files.line_span(file, 1);
+        assert_eq!(last.map(|span| (span.start(), span.end())), Some((4, 7)));
+        assert_eq!(span_source(&files, last), Some("def".to_string()));
+
+        assert!(files.line_span(file, 2).is_none());
+    }
+
+    #[test]
+    fn test_line_span_with_crlf() {
+        let mut files = SimpleReportingFiles::default();
+        let file = files.add("test", "abc\r\ndef\r\n\r\nghi");
+
+        let lines: Vec<_> = (0..4)
+            .map(|line| span_source(&files, files.line_span(file, line)))
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                Some("abc".to_string()),
+                Some("def".to_string()),
+                Some("".to_string()),
+                Some("ghi".to_string())
+            ]
+        );
+
+        assert!(files.line_span(file, 4).is_none());
+    }
+
+    #[test]
+    fn test_location_with_crlf() {
+        let lf = "abc\ndef\n\nghi";
+        let crlf = "abc\r\ndef\r\n\r\nghi";
+
+        let mut files = SimpleReportingFiles::default();
+        let lf_file = files.add("lf", lf);
+        let crlf_file = files.add("crlf", crlf);
+
+        for (line, column) in &[(0, 0), (0, 2), (1, 0), (1, 2), (3, 1)] {
+            let lf_index = files.byte_index(lf_file, *line, *column).unwrap();
+            let crlf_index = files.byte_index(crlf_file, *line, *column).unwrap();
+
+            assert_eq!(
+                lf[lf_index..].chars().next(),
+                crlf[crlf_index..].chars().next()
+            );
+            assert_eq!(
+                files.location(lf_file, lf_index),
+                files.location(crlf_file, crlf_index)
+            );
+        }
+    }
+
+    #[test]
+    fn test_line_span_without_newlines() {
+        let mut files = SimpleReportingFiles::default();
+        let file = files.add("test", "abcdef");
+
+        let only = files.line_span(file, 0);
+        assert_eq!(span_source(&files, only), Some("abcdef".to_string()));
+
+        assert!(files.line_span(file, 1).is_none());
+    }
 }
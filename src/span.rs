@@ -1,4 +1,6 @@
 use derive_new::new;
+use render_tree::{Document, Render};
+use std::borrow::Cow;
 use std::fmt::Debug;
 use std::path::PathBuf;
 
@@ -9,19 +11,147 @@ pub enum FileName {
     Verbatim(String),
 }
 
+/// A zero-based line and column in a file.
+///
+/// Columns count characters (Unicode scalar values) from the start of the
+/// line, rather than bytes, so that they match what editors show for lines
+/// with multi-byte characters. A character made of several scalar values,
+/// like an emoji with a skin tone modifier, counts as more than one column.
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd, new)]
 pub struct Location {
     pub line: usize,
     pub column: usize,
 }
 
-pub trait ReportingSpan: Debug + Copy {
+/// The column of the character at `offset` bytes into `line`. An offset
+/// inside a character gives the column of that character.
+pub(crate) fn char_column(line: &str, offset: usize) -> usize {
+    line.char_indices()
+        .take_while(|&(i, ch)| i + ch.len_utf8() <= offset)
+        .count()
+}
+
+/// The byte offset of the character at `column` in `line`, which is the end
+/// of the line if `column` is just past the last character.
+pub(crate) fn column_offset(line: &str, column: usize) -> Option<usize> {
+    line.char_indices()
+        .map(|(i, _)| i)
+        .chain(std::iter::once(line.len()))
+        .nth(column)
+}
+
+pub trait ReportingSpan: Debug + Clone {
     fn with_start(&self, start: usize) -> Self;
     fn with_end(&self, end: usize) -> Self;
     fn start(&self) -> usize;
     fn end(&self) -> usize;
+
+    /// The number of bytes in the span.
+    fn len(&self) -> usize {
+        self.end() - self.start()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether `other` is entirely inside this span. An empty span is
+    /// contained by any span that it's inside of or on the edge of.
+    fn contains(&self, other: &Self) -> bool {
+        self.start() <= other.start() && other.end() <= self.end()
+    }
+
+    /// Whether the spans overlap. Spans that only touch at an edge don't
+    /// overlap, and an empty span only overlaps a span that it's strictly
+    /// inside of.
+    fn intersects(&self, other: &Self) -> bool {
+        self.start() < other.end() && other.start() < self.end()
+    }
+
+    /// The smallest span that covers both spans, including any gap between
+    /// them. The spans should be in the same file.
+    fn merge(&self, other: &Self) -> Self {
+        self.with_start(self.start().min(other.start()))
+            .with_end(self.end().max(other.end()))
+    }
 }
 
+/// A database of source files that diagnostics can be reported against.
+///
+/// `SimpleReportingFiles` is an in-memory implementation, but it's
+/// straightforward to implement for your own file store:
+///
+/// ```rust
+/// use language_reporting::*;
+/// use std::borrow::Cow;
+///
+/// #[derive(Debug, Clone)]
+/// struct OneLiner(String);
+///
+/// #[derive(Debug, Clone)]
+/// struct Span(usize, usize);
+///
+/// impl ReportingSpan for Span {
+///     fn with_start(&self, start: usize) -> Span {
+///         Span(start, self.1)
+///     }
+///
+///     fn with_end(&self, end: usize) -> Span {
+///         Span(self.0, end)
+///     }
+///
+///     fn start(&self) -> usize {
+///         self.0
+///     }
+///
+///     fn end(&self) -> usize {
+///         self.1
+///     }
+/// }
+///
+/// impl ReportingFiles for OneLiner {
+///     type Span = Span;
+///     type FileId = ();
+///
+///     fn byte_span(&self, _file: (), from_index: usize, to_index: usize) -> Option<Span> {
+///         Some(Span(from_index, to_index))
+///     }
+///
+///     fn file_id(&self, _span: Span) {}
+///
+///     fn file_name(&self, _file: ()) -> FileName {
+///         FileName::Verbatim("main.lang".to_string())
+///     }
+///
+///     fn byte_index(&self, _file: (), line: usize, column: usize) -> Option<usize> {
+///         if line == 0 { Some(column) } else { None }
+///     }
+///
+///     fn location(&self, _file: (), byte_index: usize) -> Option<Location> {
+///         Some(Location::new(0, byte_index))
+///     }
+///
+///     fn line_span(&self, _file: (), line: usize) -> Option<Span> {
+///         if line == 0 { Some(Span(0, self.0.len())) } else { None }
+///     }
+///
+///     fn source(&self, span: Span) -> Option<Cow<'_, str>> {
+///         self.0.get(span.0..span.1).map(Cow::Borrowed)
+///     }
+/// }
+///
+/// let files = OneLiner(r#"let x = 1 + "";"#.to_string());
+/// let diagnostic = Diagnostic::new_error("Unexpected string")
+///     .with_label(Label::new_primary(Span(12, 14)));
+///
+/// assert_eq!(
+///     emit_to_string(&files, &diagnostic, &DefaultConfig),
+///     "error: Unexpected string\n\
+///      - main.lang:1:13\n\
+///      1 | let x = 1 + \"\";\n\
+///      \x20 |             ^^\n"
+/// );
+/// ```
 pub trait ReportingFiles: Debug + Clone {
     type Span: ReportingSpan;
     type FileId: Copy;
@@ -35,8 +165,77 @@ pub trait ReportingFiles: Debug + Clone {
 
     fn file_id(&self, span: Self::Span) -> Self::FileId;
     fn file_name(&self, file: Self::FileId) -> FileName;
+    /// The byte index of a (zero-based) line and column. Like `Location`,
+    /// the column counts characters rather than bytes.
     fn byte_index(&self, file: Self::FileId, line: usize, column: usize) -> Option<usize>;
+    /// The inverse of `byte_index`.
     fn location(&self, file: Self::FileId, byte_index: usize) -> Option<Location>;
     fn line_span(&self, file: Self::FileId, lineno: usize) -> Option<Self::Span>;
-    fn source(&self, span: Self::Span) -> Option<String>;
+    /// The source code covered by `span`. Implementations backed by a
+    /// contiguous buffer can borrow from it, while others can return an
+    /// owned string.
+    fn source(&self, span: Self::Span) -> Option<Cow<'_, str>>;
+}
+
+/// Renders a span's coordinates compactly, as
+/// `file:start_line:start_col-end_line:end_col`, for debugging the spans
+/// themselves. Lines and columns are one-based, and an endpoint that can't
+/// be resolved is rendered as `?`.
+pub struct RenderSpan<'files, Files: ReportingFiles>(pub Files::Span, pub &'files Files);
+
+impl<'files, Files: ReportingFiles> Render for RenderSpan<'files, Files> {
+    fn render(self, into: Document) -> Document {
+        let RenderSpan(span, files) = self;
+        let file = files.file_id(span.clone());
+
+        let endpoint = |index| match files.location(file, index) {
+            Some(Location { line, column }) => format!("{}:{}", line + 1, column + 1),
+            None => "?".to_string(),
+        };
+
+        let name = match files.file_name(file) {
+            FileName::Virtual(name) => format!("<{}>", name.display()),
+            FileName::Real(name) => format!("{}", name.display()),
+            FileName::Verbatim(name) => name,
+        };
+
+        into.add(format!(
+            "{}:{}-{}",
+            name,
+            endpoint(span.start()),
+            endpoint(span.end())
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{SimpleReportingFiles, SimpleSpan};
+
+    #[test]
+    fn test_render_span() -> std::io::Result<()> {
+        let mut files = SimpleReportingFiles::default();
+        let file = files.add("test", "(define test\n  123)\n");
+
+        let span = SimpleSpan::new(file, 1, 18);
+        let document = Document::with(RenderSpan(span, &files));
+
+        assert_eq!(document.to_string()?, "test:1:2-2:6");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_unresolved_span() -> std::io::Result<()> {
+        let mut files = SimpleReportingFiles::default();
+        let file = files.add("test", "(define test 123)");
+
+        let span = SimpleSpan::new(file, 8, 40);
+        let document = Document::with(RenderSpan(span, &files));
+
+        assert_eq!(document.to_string()?, "test:1:9-?");
+
+        Ok(())
+    }
 }
@@ -0,0 +1,53 @@
+#![cfg(feature = "codespan-interop")]
+
+use codespan::Files;
+use language_reporting::*;
+
+#[test]
+fn test_emit_from_a_codespan_files() {
+    let source = "(define test 123)\n(+ test \"\")\n()\n";
+
+    let mut codespan = Files::new();
+    codespan.add("other", String::from("unrelated\n"));
+    let file = codespan.add("test", String::from(source));
+
+    let files = CodespanFiles::new(&codespan);
+
+    let str_start = files.byte_index(file, 1, 8).unwrap();
+    assert_eq!(str_start, source.find("\"\"").unwrap());
+    assert_eq!(files.location(file, str_start), Some(Location::new(1, 8)));
+
+    let line = files.line_span(file, 1).unwrap();
+    assert_eq!(files.source(line).as_deref(), Some("(+ test \"\")\n"));
+    assert!(files.line_span(file, 4).is_none());
+
+    let span = files.byte_span(file, str_start, str_start + 2).unwrap();
+    assert_eq!(files.source(span).as_deref(), Some("\"\""));
+
+    let error = Diagnostic::new_error("Unexpected type in `+` application")
+        .with_label(Label::new_primary(span).with_message("Expected integer but got string"));
+
+    assert_eq!(
+        emit_to_string(&files, &error, &DefaultConfig),
+        "error: Unexpected type in `+` application\n\
+         - test:2:9\n\
+         2 | (+ test \"\")\n\
+         \x20 |         ^^ Expected integer but got string\n"
+    );
+}
+
+#[test]
+fn test_columns_count_characters() {
+    let mut codespan = Files::new();
+    let file = codespan.add("test", "let café = 1 + \"\";");
+
+    let files = CodespanFiles::new(&codespan);
+    let str_start = "let café = 1 + ".len();
+    let str_column = "let café = 1 + ".chars().count();
+
+    assert_eq!(
+        files.location(file, str_start),
+        Some(Location::new(0, str_column))
+    );
+    assert_eq!(files.byte_index(file, 0, str_column), Some(str_start));
+}
@@ -0,0 +1,51 @@
+use language_reporting::*;
+
+fn emit_runs(
+    files: &SimpleReportingFiles,
+    diagnostic: &Diagnostic<SimpleSpan>,
+) -> Vec<(Style, String)> {
+    let mut writer = ColorAccumulator::new();
+    emit(&mut writer, files, diagnostic, &DefaultConfig).unwrap();
+    writer.runs()
+}
+
+fn style_of<'a>(runs: &'a [(Style, String)], text: &str) -> &'a Style {
+    let (style, _) = runs
+        .iter()
+        .find(|(_, run)| run.contains(text))
+        .unwrap_or_else(|| panic!("no run contains {:?}", text));
+
+    style
+}
+
+#[test]
+fn test_assert_on_styled_runs() {
+    let mut files = SimpleReportingFiles::default();
+    let file = files.add("test", "(+ test \"\")\n");
+
+    let error = Diagnostic::new_error("Hello")
+        .with_label(Label::new_primary(SimpleSpan::new(file, 8, 10)).with_message("here"));
+
+    let runs = emit_runs(&files, &error);
+
+    assert_eq!(style_of(&runs, "error"), &Style::new().fg(Color::Red).bold());
+    assert_eq!(style_of(&runs, "Hello"), &Style::new().bold());
+    assert_eq!(style_of(&runs, "^^ here"), &Style::new().fg(Color::Red));
+    assert_eq!(style_of(&runs, "(+ test "), &Style::new());
+}
+
+#[test]
+fn test_runs_match_the_string_form() {
+    let mut files = SimpleReportingFiles::default();
+    let file = files.add("test", "(+ test \"\")\n");
+
+    let warning = Diagnostic::new(Severity::Warning, "Unused value")
+        .with_label(Label::new_primary(SimpleSpan::new(file, 8, 10)));
+
+    let text: String = emit_runs(&files, &warning)
+        .into_iter()
+        .map(|(_, run)| run)
+        .collect();
+
+    assert_eq!(text, emit_to_string(&files, &warning, &DefaultConfig));
+}